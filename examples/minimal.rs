@@ -13,9 +13,10 @@ fn main() {
     println!("{:#?}", res_1.unwrap());
 
     // Using the other strategy. See `OCatchStrategy` to get more detail.
+    // Args can be anything that yields strings, like arrays or `Vec<String>`.
     let res_2 = fork_exec_and_catch(
         "ls",
-        vec!["ls", "-la"],
+        ["ls", "-la"],
         OCatchStrategy::StdCombined
     );
     println!("OCatchStrategy::StdCombined:");
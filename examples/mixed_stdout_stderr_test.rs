@@ -8,8 +8,7 @@ const DELAY_US: u64 = 50;
 
 /// This binary can be used to check the output catching of my lib.
 /// It produces a mixture of STDOUT and STDERR lines in a defined
-/// order. The bin `run_mixed_stdout_stderr_test` is a support
-/// bin that executes this binary inside the library.
+/// order. The test `run_mixed_stdout_stderr_test` executes this binary inside the library.
 /// This way I can make tests for the correct output order.
 fn main() {
     for i in 0..ITERATIONS {
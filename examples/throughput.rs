@@ -0,0 +1,32 @@
+//! Measures the throughput of the read path for a child that writes as fast as it can.
+//! Run with `cargo run --release --example throughput`.
+
+use std::time::Instant;
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+/// Size of the output in MiB.
+const OUTPUT_MIB: usize = 20;
+
+fn main() {
+    let script = format!("yes | head -c {}M", OUTPUT_MIB);
+    for strategy in [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Multiplexed,
+    ] {
+        let start = Instant::now();
+        let res = Command::new("sh")
+            .args(vec!["-c", &script])
+            .strategy(strategy)
+            .catch()
+            .unwrap();
+        let elapsed = start.elapsed();
+        println!(
+            "{:>14}: {} lines in {:.2?} ({:.1} MiB/s)",
+            strategy.to_string(),
+            res.stdcombined().len(),
+            elapsed,
+            OUTPUT_MIB as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
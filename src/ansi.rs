@@ -0,0 +1,70 @@
+//! Removal of ANSI escape sequences (colors, cursor movement, ...) from captured lines.
+
+/// The escape character that starts every ANSI escape sequence.
+const ESC: char = '\u{1b}';
+/// The bell character that may terminate an OSC sequence.
+const BEL: char = '\u{07}';
+
+/// Removes all ANSI escape sequences from the line. Supported are CSI sequences
+/// (`ESC [ ... final byte`, e.g. colors), OSC sequences (`ESC ] ... BEL` or
+/// `ESC ] ... ESC \`, e.g. window titles) and all other two byte escape sequences.
+pub fn strip_ansi_escapes(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != ESC {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameter and intermediate bytes, terminated by a final byte in 0x40..=0x7e
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or by ST (ESC \)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == BEL {
+                        break;
+                    }
+                    if c == ESC && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // two byte sequence or a dangling ESC at the end of the line
+            _ => {}
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi_escapes() {
+        assert_eq!("hello", strip_ansi_escapes("hello"));
+        assert_eq!(
+            "red text",
+            strip_ansi_escapes("\u{1b}[31mred\u{1b}[0m text")
+        );
+        assert_eq!(
+            "bold",
+            strip_ansi_escapes("\u{1b}[1;4;38;5;208mbold\u{1b}[m")
+        );
+        assert_eq!("title", strip_ansi_escapes("\u{1b}]0;my title\u{07}title"));
+        assert_eq!(
+            "title",
+            strip_ansi_escapes("\u{1b}]0;my title\u{1b}\\title")
+        );
+        assert_eq!("ab", strip_ansi_escapes("a\u{1b}=b"));
+        assert_eq!("äöü", strip_ansi_escapes("\u{1b}[32mäöü\u{1b}"));
+    }
+}
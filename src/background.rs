@@ -0,0 +1,53 @@
+//! Capturing in a background thread. See [`crate::Command::catch_in_background`].
+
+use crate::error::UECOError;
+use crate::reader::panic_message;
+use crate::running::RunningChild;
+use crate::{ProcessOutput, SendableOutput};
+use std::thread;
+use std::thread::JoinHandle;
+
+/// Handle to a child whose output is read by a background thread, see
+/// [`crate::Command::catch_in_background`]. The thread also reaps the child. Dropping the
+/// handle doesn't stop anything: the child runs to its end and the output gets discarded.
+pub struct BackgroundCapture {
+    pid: libc::pid_t,
+    drain: JoinHandle<Result<SendableOutput, UECOError>>,
+}
+
+impl BackgroundCapture {
+    /// Starts the thread that reads all output of the child.
+    pub(crate) fn start(child: RunningChild) -> Result<Self, UECOError> {
+        let pid = child.pid();
+        // if the thread can't be spawned, the closure and the child in it get dropped,
+        // which kills the child
+        let drain = thread::Builder::new()
+            .name("ueco-background".to_string())
+            .spawn(move || child.wait_with_output().map(SendableOutput::from))
+            .map_err(|err| UECOError::ThreadSpawnFailed {
+                errno: err.raw_os_error().unwrap_or(0),
+            })?;
+        Ok(Self { pid, drain })
+    }
+
+    /// Pid of the child.
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// Returns true once the child exited and all output was read, i.e.
+    /// [`BackgroundCapture::output`] doesn't block.
+    pub fn is_finished(&self) -> bool {
+        self.drain.is_finished()
+    }
+
+    /// Waits for the background thread and returns the output.
+    pub fn output(self) -> Result<ProcessOutput, UECOError> {
+        match self.drain.join() {
+            Ok(res) => res.map(ProcessOutput::from),
+            Err(payload) => Err(UECOError::ReaderThreadPanicked {
+                message: panic_message(payload.as_ref()),
+            }),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Running many commands concurrently.
+
+use crate::command::Command;
+use crate::error::UECOError;
+use crate::{ProcessOutput, SendableOutput};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// The result of a capture together with the context that the caller attached to it, see
+/// [`batch_run_with_context`] and [`Command::catch_with_context`]. The context can be
+/// anything that correlates the result with its input, like the name of the input file.
+#[derive(Debug)]
+pub struct CaptureResult<T> {
+    /// The context that was attached when the capture was started.
+    pub context: T,
+    /// The result of the capture.
+    pub output: Result<ProcessOutput, UECOError>,
+}
+
+/// Result of a single command of [`batch_run`], as it is sent from a worker thread.
+type WorkerResult = (usize, Result<SendableOutput, UECOError>);
+
+/// Runs all commands and catches their output, with up to `parallelism` commands running at
+/// the same time. This is useful for batch workloads, like running a program for hundreds of
+/// inputs. Each command is executed like with [`Command::catch`] on one of `parallelism`
+/// worker threads. The results are returned in the order of `commands`, independent of the
+/// order in which the commands finished. A failing command doesn't affect the others.
+///
+/// A `parallelism` of 0 is treated as 1. Don't use much more than the number of CPUs for
+/// CPU-bound programs.
+pub fn batch_run(
+    commands: Vec<Command>,
+    parallelism: usize,
+) -> Vec<Result<ProcessOutput, UECOError>> {
+    let workers = parallelism.max(1).min(commands.len());
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel::<WorkerResult>();
+    let mut results = (0..commands.len()).map(|_| None).collect::<Vec<_>>();
+    let mut spawn_errno = 0;
+    thread::scope(|scope| {
+        for i in 0..workers {
+            let sender = sender.clone();
+            let commands = &commands;
+            let next = &next;
+            let res = thread::Builder::new()
+                .name(format!("ueco-batch-{}", i))
+                .spawn_scoped(scope, move || loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let command = match commands.get(index) {
+                        Some(command) => command,
+                        None => break,
+                    };
+                    let res = command.catch().map(SendableOutput::from);
+                    // the receiver lives until all workers finished
+                    sender.send((index, res)).unwrap();
+                });
+            if let Err(err) = res {
+                // the other workers take over the commands
+                trace!("failed to spawn batch worker: {}", err);
+                spawn_errno = err.raw_os_error().unwrap_or(0);
+            }
+        }
+        drop(sender);
+        for (index, res) in receiver {
+            results[index].replace(res.map(ProcessOutput::from));
+        }
+    });
+    results
+        .into_iter()
+        .map(|res| {
+            // only happens if no worker could be spawned at all
+            res.unwrap_or(Err(UECOError::ThreadSpawnFailed { errno: spawn_errno }))
+        })
+        .collect()
+}
+
+/// Like [`batch_run`], but each command comes with a context that is returned together with
+/// its result. This way the results can be correlated with their inputs without a side map.
+/// The contexts stay on the calling thread, so they don't have to be [`Send`].
+///
+/// ```no_run
+/// use unix_exec_output_catcher::{batch_run_with_context, Command};
+///
+/// let jobs = vec!["a.txt", "b.txt"]
+///     .into_iter()
+///     .map(|file| {
+///         let mut command = Command::new("wc");
+///         command.args(vec!["-l", file]);
+///         (file, command)
+///     })
+///     .collect();
+/// for res in batch_run_with_context(jobs, 4) {
+///     println!("{}: {:?}", res.context, res.output.map(|o| o.exit_code()));
+/// }
+/// ```
+pub fn batch_run_with_context<T>(
+    jobs: Vec<(T, Command)>,
+    parallelism: usize,
+) -> Vec<CaptureResult<T>> {
+    let (contexts, commands): (Vec<T>, Vec<Command>) = jobs.into_iter().unzip();
+    contexts
+        .into_iter()
+        .zip(batch_run(commands, parallelism))
+        .map(|(context, output)| CaptureResult { context, output })
+        .collect()
+}
@@ -0,0 +1,79 @@
+//! Abstraction over running a [`Command`], so that code using this crate can be tested
+//! with recorded outputs instead of real processes.
+
+use crate::command::Command;
+use crate::error::UECOError;
+use crate::{ProcessOutput, SendableOutput};
+
+/// Runs a [`Command`] and catches its output. Implemented by [`ForkCatcher`], which
+/// really executes the program, and by [`MockCatcher`], which returns recorded outputs.
+/// Code that takes a `&dyn Catcher` (or a generic `C: Catcher`) can be tested without
+/// spawning processes.
+pub trait Catcher {
+    /// Runs the command like [`Command::catch`].
+    fn catch(&self, command: &Command) -> Result<ProcessOutput, UECOError>;
+}
+
+/// The real [`Catcher`]: forks and executes the program, see [`Command::catch`].
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ForkCatcher;
+
+impl Catcher for ForkCatcher {
+    fn catch(&self, command: &Command) -> Result<ProcessOutput, UECOError> {
+        command.catch()
+    }
+}
+
+/// A [`Catcher`] that returns recorded outputs instead of forking. A command matches a
+/// recording if the executable and the args are the same; all other options of the
+/// [`Command`] are ignored. Commands without a recording fail with
+/// [`UECOError::NoRecordedOutput`]. With the feature `serde`, outputs can be recorded in
+/// fixture files, see [`ProcessOutput::save_fixture`] and [`ProcessOutput::load_fixture`].
+#[derive(Debug, Default)]
+pub struct MockCatcher {
+    /// Executable and args of each recorded command together with its output.
+    recordings: Vec<(String, Vec<String>, SendableOutput)>,
+}
+
+impl MockCatcher {
+    /// Constructor. There are no recordings yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `output` as output of `command`. A later recording for the same command
+    /// replaces the former.
+    pub fn record(&mut self, command: &Command, output: ProcessOutput) -> &mut Self {
+        let (executable, args) = key(command);
+        self.recordings
+            .retain(|(e, a, _)| *e != executable || *a != args);
+        self.recordings
+            .push((executable, args, SendableOutput::from(output)));
+        self
+    }
+}
+
+impl Catcher for MockCatcher {
+    fn catch(&self, command: &Command) -> Result<ProcessOutput, UECOError> {
+        let (executable, args) = key(command);
+        self.recordings
+            .iter()
+            .find(|(e, a, _)| *e == executable && *a == args)
+            .map(|(_, _, output)| ProcessOutput::from(output.clone()))
+            .ok_or_else(|| UECOError::NoRecordedOutput {
+                command: format!("{} {:?}", executable, args),
+            })
+    }
+}
+
+/// What identifies a recording of a command.
+fn key(command: &Command) -> (String, Vec<String>) {
+    (
+        command.get_executable().to_string(),
+        command
+            .get_args()
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect(),
+    )
+}
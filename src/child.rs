@@ -1,11 +1,20 @@
 //! Childprocess related abstractions.
 
 use crate::error::UECOError;
-use crate::exec::exec;
-use crate::libc_util::{libc_ret_to_result, LibcSyscall};
+use crate::exec::{exec, exec_with_env};
+use crate::libc_util::{
+    decode_child_error, encode_child_error, libc_ret_to_result, LibcSyscall, CHILD_ERROR_LEN,
+};
 use crate::pipe::Pipe;
+use crate::ExitStatus;
+use std::ffi::CString;
 use std::fmt::Debug;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Exit code of the child if something failed after fork(), like in a shell
+/// if the command can't be executed.
+const CHILD_SETUP_FAILED_EXIT_CODE: libc::c_int = 127;
 
 /// The state in that a child process can be.
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -20,6 +29,227 @@ pub enum ProcessState {
     FinishedError(i32),
 }
 
+/// Options that are applied in the child after fork() but before exec().
+#[derive(Debug, Default, Clone)]
+pub struct ChildOptions {
+    /// See [`crate::Command::detach_tty`].
+    pub detach_tty: bool,
+    /// See [`crate::Command::sigpipe`]. `None` means inherited from the parent.
+    pub sigpipe: Option<SigpipeDisposition>,
+    /// Complete environment of the child as `KEY=VALUE`, see [`crate::Command::env`].
+    /// `None` means inherited from the parent.
+    pub env: Option<Vec<CString>>,
+    /// See [`crate::Command::verify_redirects`].
+    pub verify_redirects: bool,
+    /// See [`crate::Command::stdio`].
+    pub stdio: StdioConfig,
+    /// See [`crate::Command::chroot`].
+    pub chroot: Option<CString>,
+    /// See [`crate::Command::umask`]. `None` means inherited from the parent.
+    pub umask: Option<libc::mode_t>,
+    /// See [`crate::Command::cpu_affinity`]. `None` means inherited from the parent.
+    #[cfg(target_os = "linux")]
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// `CLONE_*` flags for `unshare()`, see [`crate::Command::unshare`].
+    #[cfg(target_os = "linux")]
+    pub unshare: Option<libc::c_int>,
+    /// See [`crate::Command::pre_exec`].
+    pub pre_exec: Option<PreExecHook>,
+    /// See [`crate::Command::on_spawn`]. The only option that is applied in the parent.
+    pub on_spawn: Option<SpawnHook>,
+    /// Runs in the child instead of exec(), see [`crate::testing::fork_closure_and_catch`].
+    #[cfg(feature = "test-hooks")]
+    pub run_instead_of_exec: Option<PreExecHook>,
+}
+
+/// Closure of [`crate::Command::pre_exec`]. Shared by all clones of the [`crate::Command`].
+#[derive(Clone)]
+pub struct PreExecHook(Arc<dyn Fn() -> std::io::Result<()> + Send + Sync>);
+
+impl PreExecHook {
+    /// Constructor.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn() -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl Debug for PreExecHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PreExecHook")
+    }
+}
+
+/// Closure of [`crate::Command::on_spawn`]. Shared by all clones of the [`crate::Command`].
+#[derive(Clone)]
+pub struct SpawnHook(Arc<dyn Fn(libc::pid_t) -> std::io::Result<()> + Send + Sync>);
+
+impl SpawnHook {
+    /// Constructor.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(libc::pid_t) -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl Debug for SpawnHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SpawnHook")
+    }
+}
+
+/// Blocks until the child with the given pid terminated, but doesn't reap it: Afterwards,
+/// [`ChildProcess::check_state_nbl`] reaps it without blocking. Unlike
+/// [`ChildProcess::wait_bl`], this doesn't need exclusive access to the [`ChildProcess`].
+/// Returns immediately if the child was reaped already.
+pub(crate) fn wait_until_exited(pid: libc::pid_t) -> Result<(), UECOError> {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                // pid is the positive pid of a child, so the cast is lossless
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if ret == -1 {
+            match errno::errno().0 {
+                libc::EINTR => continue,
+                // reaped by someone else in the meantime
+                libc::ECHILD => return Ok(()),
+                _ => {}
+            }
+        }
+        return libc_ret_to_result(ret, LibcSyscall::Waitid);
+    }
+}
+
+/// Pins the current process to `cpus` with `sched_setaffinity()`. Must be called in the
+/// child after fork(); doesn't allocate. See [`crate::Command::cpu_affinity`].
+#[cfg(target_os = "linux")]
+fn set_cpu_affinity(cpus: &[usize]) -> Result<(), UECOError> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus {
+        // CPU_SET() would panic
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(UECOError::SetAffinityFailed {
+                errno: libc::EINVAL,
+            });
+        }
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    let ret = unsafe { libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) };
+    libc_ret_to_result(ret, LibcSyscall::SetAffinity)
+}
+
+/// Disposition of `SIGPIPE` in the child. See [`crate::Command::sigpipe`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SigpipeDisposition {
+    /// `SIG_DFL`: The child gets killed if it writes to a pipe without reader.
+    /// This is what most programs expect, e.g. `yes | head -n 1`.
+    Default,
+    /// `SIG_IGN`: Writing to a pipe without reader fails with `EPIPE` instead.
+    Ignore,
+}
+
+/// What a standard stream of the child is connected to, like `std::process::Stdio`.
+/// See [`crate::Command::stdio`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stdio {
+    /// Connected to a pipe of this library: the output gets caught, STDIN can be written
+    /// via [`crate::Command::input`] or [`crate::RunningChild::stdin`].
+    Capture,
+    /// Left connected to the same file as in the parent, e.g. the terminal.
+    Inherit,
+    /// Connected to `/dev/null`: output is discarded, STDIN is empty.
+    Null,
+}
+
+/// What STDIN, STDOUT, and STDERR of the child are connected to. See [`crate::Command::stdio`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StdioConfig {
+    /// [`Stdio::Inherit`] by default.
+    pub stdin: Stdio,
+    /// [`Stdio::Capture`] by default.
+    pub stdout: Stdio,
+    /// [`Stdio::Capture`] by default.
+    pub stderr: Stdio,
+}
+
+impl Default for StdioConfig {
+    fn default() -> Self {
+        Self {
+            stdin: Stdio::Inherit,
+            stdout: Stdio::Capture,
+            stderr: Stdio::Capture,
+        }
+    }
+}
+
+/// An opinionated environment that makes captures reproducible across machines and
+/// users. See [`crate::Command::reproducible_env`] for what exactly gets set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReproducibleEnv {
+    /// File mode creation mask. `0o022` by default.
+    pub umask: libc::mode_t,
+    /// Names of the variables that the child inherits from the current process, if they
+    /// are set. Empty by default.
+    pub keep: Vec<String>,
+    /// Value of `PATH`. `"/usr/bin:/bin"` by default.
+    pub path: String,
+    /// Value of `TZ`. `"UTC"` by default.
+    pub tz: String,
+}
+
+impl Default for ReproducibleEnv {
+    fn default() -> Self {
+        Self {
+            umask: 0o022,
+            keep: vec![],
+            path: "/usr/bin:/bin".to_string(),
+            tz: "UTC".to_string(),
+        }
+    }
+}
+
+/// Resources that a single child used, as reported by `wait4()` when it was reaped.
+/// See [`crate::ProcessOutput::resource_usage`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResourceUsage {
+    /// CPU time spent in user mode.
+    pub user_time: Duration,
+    /// CPU time spent in kernel mode.
+    pub system_time: Duration,
+    /// Peak resident set size in KiB.
+    pub max_rss_kib: u64,
+}
+
+impl ResourceUsage {
+    /// Converts the `rusage` of `wait4()`.
+    fn from_rusage(rusage: &libc::rusage) -> Self {
+        let to_duration = |time: libc::timeval| {
+            Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+        };
+        Self {
+            user_time: to_duration(rusage.ru_utime),
+            system_time: to_duration(rusage.ru_stime),
+            // KiB on Linux, but bytes on macOS
+            max_rss_kib: if cfg!(target_os = "macos") {
+                rusage.ru_maxrss as u64 / 1024
+            } else {
+                rusage.ru_maxrss as u64
+            },
+        }
+    }
+}
+
 /// Abstraction over a child process.
 pub struct ChildProcess {
     /// String of the executable. Can also be a name
@@ -31,8 +261,10 @@ pub struct ChildProcess {
     /// Once the process has been dispatched/forked, the pid of the child
     /// is set here.
     pid: Option<libc::pid_t>,
-    /// Once the process exited, the exit code stands here.
-    exit_code: Option<i32>,
+    /// Once the process exited, the exit status stands here.
+    exit_status: Option<ExitStatus>,
+    /// Once the process was reaped, its resource usage stands here.
+    resource_usage: Option<ResourceUsage>,
     /// The current process state.
     state: ProcessState,
     /// Reference to the pipe where STDOUT gets redirected.
@@ -43,6 +275,8 @@ pub struct ChildProcess {
     child_after_dispatch_before_exec_fn: Box<dyn Send + FnMut() -> Result<(), UECOError>>,
     /// Code that should be executed in parent after fork()
     parent_after_dispatch_fn: Box<dyn Send + FnMut() -> Result<(), UECOError>>,
+    /// Options that are applied in child after fork() but before exec().
+    options: ChildOptions,
 }
 
 impl ChildProcess {
@@ -53,6 +287,7 @@ impl ChildProcess {
     /// * `parent_after_dispatch_fn` Code that should be executed in parent after fork()
     /// * `stdout_pipe` Reference to the pipe where STDOUT gets redirected.
     /// * `stderr_pipe` Reference to the pipe where STDERR gets redirected.
+    /// * `options` Options that are applied in child after fork() but before exec().
     pub fn new(
         executable: &str,
         args: Vec<&str>,
@@ -60,23 +295,42 @@ impl ChildProcess {
         parent_after_dispatch_fn: Box<dyn Send + FnMut() -> Result<(), UECOError>>,
         stdout_pipe: Arc<Mutex<Pipe>>,
         stderr_pipe: Arc<Mutex<Pipe>>,
+        options: ChildOptions,
     ) -> Self {
         ChildProcess {
             executable: executable.to_string(),
             args: args.iter().map(|s| s.to_string()).collect::<Vec<String>>(),
             pid: None,
-            exit_code: None,
+            exit_status: None,
+            resource_usage: None,
             state: ProcessState::Ready,
             child_after_dispatch_before_exec_fn,
             parent_after_dispatch_fn,
             stdout_pipe,
             stderr_pipe,
+            options,
         }
     }
 
     /// Forks the process. This mean child and parent will run from that
-    /// point concurrently.
+    /// point concurrently. Returns after the child called exec() successfully.
+    /// If anything fails in the child before or during exec(), the child reports
+    /// the error through a pipe and exits. The error is returned here.
     pub fn dispatch(&mut self) -> Result<libc::pid_t, UECOError> {
+        if self.state != ProcessState::Ready {
+            return Err(UECOError::ChildAlreadyDispatched);
+        }
+        // gets closed automatically by a successful exec()
+        let mut error_pipe = Pipe::new_cloexec()?;
+
+        #[cfg(feature = "test-hooks")]
+        if crate::testing::take_injected_fork_failure() {
+            return Err(UECOError::ForkFailed {
+                errno: libc::EAGAIN,
+                reason: crate::error::ForkFailureReason::ProcessLimit,
+            });
+        }
+
         self.state = ProcessState::Running;
         let pid = unsafe { libc::fork() };
         // unwrap error, if pid == -1
@@ -87,76 +341,252 @@ impl ChildProcess {
         if pid == 0 {
             // child process
             trace!("Hello from Child!");
-            let res: Result<(), UECOError> = (self.child_after_dispatch_before_exec_fn)();
-            res?;
-            exec(
-                &self.executable,
-                self.args.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
-            )?;
+            let err = self.setup_and_exec_in_child(&mut error_pipe);
             // here be dragons (after exec())
-            // only happens if exec failed; otherwise at this point
-            // the address space of the process is replaced by the new program
-            Err(UECOError::Unknown)
+            // only happens if something failed; otherwise at this point
+            // the address space of the process is replaced by the new program.
+            // The child must never return into the code of the parent.
+            let _ = error_pipe.write_all(&encode_child_error(&err));
+            unsafe { libc::_exit(CHILD_SETUP_FAILED_EXIT_CODE) }
         } else {
             // parent process
             trace!("Hello from parent!");
             self.pid.replace(pid);
             let res: Result<(), UECOError> = (self.parent_after_dispatch_fn)();
-            res?;
-            Ok(pid)
+            if let Err(err) = res.and_then(|_| error_pipe.mark_as_parent_process()) {
+                // don't leave the child behind
+                self.kill()?;
+                return Err(err);
+            }
+            if let Some(SpawnHook(hook)) = &self.options.on_spawn {
+                if let Err(err) = hook(pid) {
+                    // don't leave the child behind
+                    self.kill()?;
+                    return Err(UECOError::OnSpawnFailed {
+                        errno: err.raw_os_error().unwrap_or(0),
+                    });
+                }
+            }
+            // blocks until exec() closed the pipe or the child reported an error
+            let reported = match error_pipe.read_chunk(CHILD_ERROR_LEN) {
+                Ok(reported) => reported,
+                Err(err) => {
+                    self.kill()?;
+                    return Err(err);
+                }
+            };
+            match reported {
+                None => Ok(pid),
+                Some(bytes) => {
+                    trace!("child reported an error before exec()");
+                    self.wait_bl()?;
+                    Err(decode_child_error(&bytes))
+                }
+            }
+        }
+    }
+
+    /// Executes all setup code in the child and calls exec(). Only returns if something failed.
+    fn setup_and_exec_in_child(&mut self, error_pipe: &mut Pipe) -> UECOError {
+        let res = error_pipe
+            .mark_as_child_process()
+            .and_then(|_| (self.child_after_dispatch_before_exec_fn)())
+            .and_then(|_| self.apply_options_in_child())
+            .and_then(|_| {
+                #[cfg(feature = "test-hooks")]
+                if let Some(PreExecHook(closure)) = &self.options.run_instead_of_exec {
+                    // like a successful exec(); the parent starts reading now
+                    error_pipe.close_write_end()?;
+                    let code = if closure().is_ok() { 0 } else { 1 };
+                    unsafe { libc::_exit(code) }
+                }
+                let args = self.args.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+                match &self.options.env {
+                    Some(env) => exec_with_env(&self.executable, args, env),
+                    None => exec(&self.executable, args),
+                }
+            });
+        match res {
+            Err(err) => err,
+            // exec() only returns on error
+            Ok(_) => UECOError::Unknown,
         }
     }
 
-    /// Check process state nonblocking from parent.
-    pub fn check_state_nbl(&mut self) -> ProcessState {
+    /// Applies the [`ChildOptions`]. Must be called in the child after fork().
+    fn apply_options_in_child(&self) -> Result<(), UECOError> {
+        if self.options.verify_redirects {
+            if self.options.stdio.stdout == Stdio::Capture {
+                self.stdout_pipe
+                    .lock()
+                    .unwrap()
+                    .verify_connected(libc::STDOUT_FILENO)?;
+            }
+            if self.options.stdio.stderr == Stdio::Capture {
+                self.stderr_pipe
+                    .lock()
+                    .unwrap()
+                    .verify_connected(libc::STDERR_FILENO)?;
+            }
+        }
+        let pty = self.stdout_pipe.lock().unwrap().is_pty();
+        if self.options.detach_tty || pty {
+            // new session without a controlling terminal; the child is not affected by
+            // signals (like SIGHUP) of the terminal of the parent anymore
+            let ret = unsafe { libc::setsid() };
+            libc_ret_to_result(ret, LibcSyscall::Setsid)?;
+        }
+        if pty {
+            // writes to /dev/tty end up in the PTY too
+            self.stdout_pipe
+                .lock()
+                .unwrap()
+                .make_controlling_terminal()?;
+        }
+        if let Some(sigpipe) = self.options.sigpipe {
+            let handler = match sigpipe {
+                SigpipeDisposition::Default => libc::SIG_DFL,
+                SigpipeDisposition::Ignore => libc::SIG_IGN,
+            };
+            // SIG_DFL and SIG_IGN survive exec(), custom handlers don't
+            let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+            action.sa_sigaction = handler;
+            let ret = unsafe { libc::sigaction(libc::SIGPIPE, &action, std::ptr::null_mut()) };
+            libc_ret_to_result(ret, LibcSyscall::Sigaction)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(flags) = self.options.unshare {
+            // before chroot(), so that it happens inside of a new mount namespace
+            let ret = unsafe { libc::unshare(flags) };
+            libc_ret_to_result(ret, LibcSyscall::Unshare)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(cpus) = &self.options.cpu_affinity {
+            set_cpu_affinity(cpus)?;
+        }
+        if let Some(umask) = self.options.umask {
+            // always succeeds
+            unsafe { libc::umask(umask) };
+        }
+        if let Some(root) = &self.options.chroot {
+            let ret = unsafe { libc::chroot(root.as_ptr()) };
+            libc_ret_to_result(ret, LibcSyscall::Chroot)?;
+            // otherwise the working directory would still be outside of the new root
+            let ret = unsafe { libc::chdir(b"/\0".as_ptr() as *const libc::c_char) };
+            libc_ret_to_result(ret, LibcSyscall::Chdir)?;
+        }
+        if let Some(PreExecHook(hook)) = &self.options.pre_exec {
+            hook().map_err(|err| UECOError::PreExecFailed {
+                errno: err.raw_os_error().unwrap_or(0),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Check process state nonblocking from parent. Only accepts a status for
+    /// the pid of this child, otherwise [`UECOError::UnexpectedChildReaped`] is returned.
+    pub fn check_state_nbl(&mut self) -> Result<ProcessState, UECOError> {
         if self.state != ProcessState::Running {
-            return self.state;
+            return Ok(self.state);
         }
 
-        let wait_flags = libc::WNOHANG;
-        let mut status_code: libc::c_int = 0;
-        let status_code_ptr = &mut status_code as *mut libc::c_int;
+        // process is still running; nothing to reap yet
+        if !self.wait4(libc::WNOHANG)? {
+            trace!("Child process still running");
+            return Ok(self.state); // RUNNING
+        }
+        trace!("Child process changed state");
+        Ok(self.state)
+    }
+
+    /// Waits blocking until the child process terminated and reaps it.
+    /// Returns immediately if it already finished.
+    pub fn wait_bl(&mut self) -> Result<ExitStatus, UECOError> {
+        while self.state == ProcessState::Running {
+            match self.wait4(0) {
+                Err(UECOError::Wait4Failed { errno: libc::EINTR }) => continue,
+                res => res?,
+            };
+        }
+        Ok(self
+            .exit_status
+            .expect("a finished child always has an exit status"))
+    }
 
-        let ret = unsafe { libc::waitpid(self.pid.unwrap(), status_code_ptr, wait_flags) };
-        libc_ret_to_result(ret, LibcSyscall::Waitpid).unwrap();
+    /// Kills the child process with `SIGKILL` and reaps it. Does nothing if
+    /// the process is not running.
+    pub fn kill(&mut self) -> Result<(), UECOError> {
+        if self.state != ProcessState::Running {
+            return Ok(());
+        }
 
-        // IDE doesn't find this functions but they exist
+        let ret = unsafe { libc::kill(self.pid.unwrap(), libc::SIGKILL) };
+        libc_ret_to_result(ret, LibcSyscall::Kill)?;
+        trace!("Child process killed");
 
-        // I'm not sure on this one..
-        // maybe my assumption is wrong and "child process started"
-        // is actually printed when the process is finished.
-        // But this is not important for the problem here.
+        // blocking wait; the child is dead now
+        self.wait4(0)?;
+        Ok(())
+    }
 
-        // process didn't started running yet
+    /// Calls `wait4()` for this child with the given flags. Unlike
+    /// `getrusage(RUSAGE_CHILDREN)`, this yields the resource usage of exactly this child.
+    /// Returns whether the child changed its state.
+    fn wait4(&mut self, flags: libc::c_int) -> Result<bool, UECOError> {
+        let mut status_code: libc::c_int = 0;
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::wait4(self.pid.unwrap(), &mut status_code, flags, &mut rusage) };
+        libc_ret_to_result(ret, LibcSyscall::Wait4)?;
         if ret == 0 {
-            trace!("Child process not started yet");
-            return self.state; // RUNNING
-        } else if ret == self.pid.unwrap() {
-            trace!("Child process started");
-        }
-
-        // returns true if the child terminated normally
-        let exited_normally: bool = libc::WIFEXITED(status_code);
-        // returns true if the child was terminated by signal
-        let exited_by_signal: bool = libc::WIFSIGNALED(status_code);
-        // exit code (0 = success, or > 1 = error)
-        let exit_code: libc::c_int = libc::WEXITSTATUS(status_code);
-
-        if exited_normally || exited_by_signal {
-            self.exit_code.replace(exit_code);
-            if exit_code == 0 {
-                self.state = ProcessState::FinishedSuccess;
-            } else {
-                self.state = ProcessState::FinishedError(exit_code);
+            return Ok(false);
+        }
+        self.check_reaped_pid(ret)?;
+        self.update_state(status_code);
+        if self.state != ProcessState::Running {
+            self.resource_usage
+                .replace(ResourceUsage::from_rusage(&rusage));
+        }
+        Ok(true)
+    }
+
+    /// Makes sure that `wait4()` reported the status of exactly this child.
+    fn check_reaped_pid(&self, reaped: libc::pid_t) -> Result<(), UECOError> {
+        let expected = self.pid.unwrap();
+        if reaped == expected {
+            Ok(())
+        } else {
+            Err(UECOError::UnexpectedChildReaped {
+                got: reaped,
+                expected,
+            })
+        }
+    }
+
+    /// Updates state and exit status from the raw status of `wait4()`.
+    fn update_state(&mut self, status_code: libc::c_int) {
+        // only a terminated child (exited normally or by signal) is finished
+        match ExitStatus::from_raw(status_code) {
+            Some(exit_status @ ExitStatus::Exited(_))
+            | Some(exit_status @ ExitStatus::Signaled { .. }) => {
+                self.exit_status.replace(exit_status);
+                if exit_status.success() {
+                    self.state = ProcessState::FinishedSuccess;
+                } else {
+                    self.state = ProcessState::FinishedError(exit_status.code());
+                }
             }
+            _ => {}
         }
+    }
 
-        self.state
+    /// Getter for the resource usage. Only available after the child was reaped.
+    pub fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.resource_usage
     }
 
-    /// Getter for exit code.
-    pub fn exit_code(&self) -> Option<i32> {
-        self.exit_code
+    /// Getter for the pid. Only available after [`ChildProcess::dispatch`].
+    pub fn pid(&self) -> Option<libc::pid_t> {
+        self.pid
     }
     /// Getter for stdout_pipe.
     pub fn stdout_pipe(&self) -> &Arc<Mutex<Pipe>> {
@@ -0,0 +1,941 @@
+//! Builder to configure how an executable gets executed and how its output gets caught.
+
+use crate::background::BackgroundCapture;
+use crate::batch::CaptureResult;
+use crate::child::{
+    ChildOptions, PreExecHook, ReproducibleEnv, SigpipeDisposition, SpawnHook, Stdio, StdioConfig,
+};
+use crate::error::UECOError;
+#[cfg(feature = "flate2")]
+use crate::exec::catch_compressed;
+#[cfg(feature = "tokio")]
+use crate::exec::stream;
+use crate::exec::{
+    catch, catch_chunks, catch_with_visitor, expect_success, output, parse_lines, read_lines, start,
+};
+use crate::line_reader::LineReader;
+use crate::reader::{
+    LineMapper, LineVisitor, OverflowPolicy, ReadTermination, ReaderOptions, StopPredicate,
+    StopReason,
+};
+use crate::running::RunningChild;
+use crate::shell_words;
+#[cfg(feature = "tokio")]
+use crate::stream::OutputStream;
+use crate::{ExitStatus, OCatchStrategy, Output, ProcessOutput, ReadGroup, Source};
+use std::convert::TryFrom;
+#[cfg(feature = "flate2")]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Buffer size for [`Command::catch_read_groups`]. Same as the default capacity of a pipe
+/// on Linux, so a single `read()` gets everything that is currently inside the pipe.
+const READ_GROUP_BUF_LEN: usize = 65536;
+
+/// Builder to configure the execution of a program in a child process and how its
+/// output gets caught. [`crate::fork_exec_and_catch`] is a shortcut for a [`Command`]
+/// with the default configuration.
+///
+/// ```no_run
+/// use unix_exec_output_catcher::{Command, OCatchStrategy};
+///
+/// let res = Command::new("ls")
+///     .args(vec!["-la"])
+///     .strategy(OCatchStrategy::StdCombined)
+///     .max_line_bytes(4096)
+///     .catch()
+///     .unwrap();
+/// println!("{:#?}", res);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Command {
+    /// Path or name of the executable. Lookup in $PATH happens automatically.
+    executable: String,
+    /// All args of the program including args[0] that refers to
+    /// the name of the binary.
+    args: Vec<String>,
+    /// See [`OCatchStrategy`].
+    strategy: OCatchStrategy,
+    /// Options for the reader of the output.
+    reader_options: ReaderOptions,
+    /// Bytes that get written to STDIN of the child.
+    input: Option<Vec<u8>>,
+    /// Options that are applied in the child before exec().
+    child_options: ChildOptions,
+    /// Capacity of the pipes for the output.
+    pipe_capacity: Option<usize>,
+    /// Delay between the setup of the pipes and fork().
+    start_delay: Option<Duration>,
+    /// How often fork+exec is retried after transient errors.
+    retries: u32,
+    /// Don't inherit the environment of the current process.
+    env_clear: bool,
+    /// Environment variables for the child.
+    env: Vec<(String, String)>,
+    /// Variables that are inherited despite `env_clear`.
+    env_keep: Vec<String>,
+    /// Run the program via `$SHELL -lc`.
+    login_shell: bool,
+    /// See [`Command::chroot`].
+    chroot: Option<PathBuf>,
+    /// Interval for sampling the memory usage of the child.
+    #[cfg(target_os = "linux")]
+    memory_sample_interval: Option<Duration>,
+}
+
+impl TryFrom<&str> for Command {
+    type Error = UECOError;
+
+    /// See [`Command::parse`].
+    fn try_from(line: &str) -> Result<Self, Self::Error> {
+        Self::parse(line)
+    }
+}
+
+impl Command {
+    /// Constructor. args[0] is set to `executable`, real args can be added via
+    /// [`Command::arg`] and [`Command::args`]. The default strategy is
+    /// [`OCatchStrategy::StdSeparately`].
+    /// * `executable` Path or name of executable without null (\0). Lookup in $PATH happens automatically.
+    pub fn new(executable: &str) -> Self {
+        Self::with_argv(executable, vec![executable])
+    }
+
+    /// Constructor that splits a command line like `ls -la "some file"` into the executable
+    /// and the args. Quotes and backslash escapes are handled like in a POSIX shell, but
+    /// nothing gets evaluated: there are no variables, globs, pipes, or redirects, and no
+    /// shell is executed. Returns [`UECOError::InvalidCommandLine`] for unbalanced quotes
+    /// and [`UECOError::EmptyExecutable`] if the line has no words.
+    pub fn parse(line: &str) -> Result<Self, UECOError> {
+        let words = shell_words::split(line)?;
+        match words.first() {
+            Some(executable) => Ok(Self::with_argv(executable, &words)),
+            None => Err(UECOError::EmptyExecutable),
+        }
+    }
+
+    /// Constructor that takes the complete args vector including args[0].
+    /// This is what [`crate::fork_exec_and_catch`] uses.
+    pub(crate) fn with_argv(
+        executable: &str,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Self {
+        Self {
+            executable: executable.to_string(),
+            args: args.into_iter().map(|s| s.as_ref().to_string()).collect(),
+            strategy: OCatchStrategy::StdSeparately,
+            reader_options: ReaderOptions::default(),
+            input: None,
+            child_options: ChildOptions::default(),
+            pipe_capacity: None,
+            start_delay: None,
+            retries: 0,
+            env_clear: false,
+            env: vec![],
+            env_keep: vec![],
+            login_shell: false,
+            chroot: None,
+            #[cfg(target_os = "linux")]
+            memory_sample_interval: None,
+        }
+    }
+
+    /// Sets args[0], i.e. the name under which the program sees itself, independent of the
+    /// executable that gets executed. By default, it's the `executable` given to
+    /// [`Command::new`]. Multi-call binaries like busybox or toybox select the tool by
+    /// args[0], e.g. `Command::new("/bin/busybox").arg0("ls")` runs `ls`.
+    pub fn arg0(&mut self, arg0: &str) -> &mut Self {
+        if self.args.is_empty() {
+            self.args.push(arg0.to_string());
+        } else {
+            self.args[0] = arg0.to_string();
+        }
+        self
+    }
+
+    /// Adds a single arg.
+    pub fn arg(&mut self, arg: &str) -> &mut Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Adds multiple args. Accepts everything that yields strings, like arrays,
+    /// slices, `Vec<&str>`, `Vec<String>`, or iterators.
+    pub fn args(&mut self, args: impl IntoIterator<Item = impl AsRef<str>>) -> &mut Self {
+        self.args
+            .extend(args.into_iter().map(|s| s.as_ref().to_string()));
+        self
+    }
+
+    /// Sets the [`OCatchStrategy`].
+    pub fn strategy(&mut self, strategy: OCatchStrategy) -> &mut Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Limits the length of a single line in bytes. If the child process outputs a longer
+    /// line, the child gets killed and [`UECOError::LineTooLong`] is returned. This protects
+    /// against untrusted or buggy programs that emit gigabytes without a newline.
+    /// By default there is no limit.
+    pub fn max_line_bytes(&mut self, max_line_bytes: usize) -> &mut Self {
+        self.reader_options.max_line_bytes.replace(max_line_bytes);
+        self
+    }
+
+    /// Limits the size of all caught lines together in bytes (without newlines). This protects
+    /// against programs that produce unbounded output. What happens if the output is larger
+    /// is configured by [`Command::on_overflow`]. By default there is no limit.
+    /// Only applies to [`Command::catch`].
+    pub fn max_output_bytes(&mut self, max_output_bytes: usize) -> &mut Self {
+        self.reader_options
+            .max_output_bytes
+            .replace(max_output_bytes);
+        self
+    }
+
+    /// Stops reading once `max_lines` lines were caught, counted over STDOUT and STDERR
+    /// together, and kills the child. This is useful if only the first lines of a long
+    /// running program are of interest, e.g. a header. [`ProcessOutput::stop_reason`] is
+    /// [`crate::StopReason::MaxLines`] then. With [`OCatchStrategy::StdSeparately`], "first" refers
+    /// to the order in which the lines were read. See [`Command::kill_on_stop`].
+    /// By default there is no limit.
+    /// Only applies to [`Command::catch`].
+    pub fn max_lines(&mut self, max_lines: usize) -> &mut Self {
+        self.reader_options.max_lines.replace(max_lines);
+        self
+    }
+
+    /// Stops reading once a line matches `predicate`, e.g. when a server prints that it's
+    /// ready, and kills the child (see [`Command::kill_on_stop`]). The output contains all
+    /// lines up to and including the matching line and [`ProcessOutput::stop_reason`] is
+    /// [`crate::StopReason::StopCondition`]. The predicate sees the lines after
+    /// [`Command::map_line`]. With [`OCatchStrategy::StdSeparately`], a line of the other
+    /// stream that was read at the same time may be kept too. Only applies to [`Command::catch`].
+    pub fn stop_when<F>(&mut self, predicate: F) -> &mut Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.reader_options
+            .stop_when
+            .replace(StopPredicate::new(predicate));
+        self
+    }
+
+    /// Whether the child gets killed when reading stops early because of
+    /// [`Command::max_lines`] or [`Command::stop_when`]. If not, the remaining output is read
+    /// and discarded until the child exits by itself. The default is `true`.
+    pub fn kill_on_stop(&mut self, kill: bool) -> &mut Self {
+        self.reader_options.keep_child_on_stop = !kill;
+        self
+    }
+
+    /// Limits the total runtime of the child. Once `timeout` expired, reading stops, the
+    /// child gets killed, and everything that was read so far is kept:
+    /// * [`Command::catch_chunks`] and [`Command::catch_read_groups`] stop passing chunks
+    ///   to the callback and return [`UECOError::TimedOut`].
+    /// * [`Command::catch`] returns the lines so far with [`crate::StopReason::Timeout`] as
+    ///   [`ProcessOutput::stop_reason`]. Unless [`Command::read_timeout`] is set, all
+    ///   strategies are read with the poll based reader of [`OCatchStrategy::Multiplexed`];
+    ///   the [`ProcessOutput`] has the same content as with the reader of the strategy.
+    /// * [`Command::catch_with_visitor`] returns [`crate::StopReason::Timeout`].
+    /// * [`Command::lines_iter`] returns [`UECOError::TimedOut`] instead of the exit status.
+    ///
+    /// For [`Command::stream`], wrap the stream in the timeout of your runtime; dropping it
+    /// kills the child. By default there is no timeout.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.reader_options.timeout.replace(timeout);
+        self
+    }
+
+    /// Limits how long a single `read()` of [`OCatchStrategy::StdCombined`],
+    /// [`OCatchStrategy::CombinedPrefixed`], [`OCatchStrategy::Pty`], and
+    /// [`OCatchStrategy::StdSeparately`] waits for output. Otherwise these readers block
+    /// until the next output or EOF. After `read_timeout` without output, the reader wakes
+    /// up and checks [`Command::timeout`] and the state of the child, then continues
+    /// reading; an incomplete line is kept. A shorter timeout reacts faster but wakes up
+    /// more often. [`Command::timeout`] doesn't depend on it. By default, reads block.
+    pub fn read_timeout(&mut self, read_timeout: Duration) -> &mut Self {
+        self.reader_options.read_timeout.replace(read_timeout);
+        self
+    }
+
+    /// Sets a label that prefixes all log messages of this crate for this capture, e.g.
+    /// `"[build-step-3] forked successfully"`. This tells the messages of concurrent captures
+    /// apart. With the feature `tracing`, it's also a field of the `capture` span.
+    pub fn label(&mut self, label: &str) -> &mut Self {
+        self.reader_options.label.replace(label.into());
+        self
+    }
+
+    /// Sets the stack size in bytes of the two reader threads of
+    /// [`OCatchStrategy::StdSeparately`]. By default, they get the default stack size of
+    /// std (usually 2 MiB, see `RUST_MIN_STACK`), which may matter on constrained systems.
+    /// The reader threads need little stack; the OS may round the size up to its minimum.
+    pub fn reader_stack_size(&mut self, stack_size: usize) -> &mut Self {
+        self.reader_options.reader_stack_size.replace(stack_size);
+        self
+    }
+
+    /// Sets how often the state of the child is checked with `waitpid()` while reading
+    /// with [`OCatchStrategy::StdCombined`], [`OCatchStrategy::CombinedPrefixed`],
+    /// [`OCatchStrategy::Pty`], and [`OCatchStrategy::StdSeparately`]. Reading ends once the
+    /// output reached EOF and the child exited. While output arrives, the state is checked
+    /// without blocking at most once per `interval`; a longer interval means fewer syscalls.
+    /// Once the output reached EOF but the child still runs, e.g. because it closed its
+    /// STDOUT, the reader waits for the exit with a blocking `waitpid()` instead of
+    /// polling. Defaults to 5ms.
+    pub fn state_poll_interval(&mut self, interval: Duration) -> &mut Self {
+        self.reader_options.state_poll_interval.replace(interval);
+        self
+    }
+
+    /// Splits the output into records on matches of `separator` instead of newlines. This
+    /// is useful for machine-readable formats whose records are delimited by patterns, e.g.
+    /// `\n---\n` or `\n\n+`. Matches that are empty are ignored. The records take the place
+    /// of the lines, so all options that work on lines apply to them. Applies to
+    /// [`Command::catch`] and [`Command::catch_with_visitor`]; for all strategies, the output
+    /// is read like for [`OCatchStrategy::Multiplexed`] then. The regex works on the raw
+    /// bytes of the output, so build it with [`regex::bytes::RegexBuilder`] for flags such
+    /// as case insensitivity. Requires the feature `regex`.
+    #[cfg(feature = "regex")]
+    pub fn record_separator_regex(&mut self, separator: regex::bytes::Regex) -> &mut Self {
+        self.reader_options.record_separator.replace(separator);
+        self
+    }
+
+    /// Whether output that ends with a newline has an empty last line. By default, a newline
+    /// terminates a line, so `"a\n"` is one line `"a"` and `"a\n\n"` are the lines `"a"` and
+    /// `""`. If enabled, a newline separates lines instead, so `"a\n"` results in `"a"` and `""`,
+    /// and `"a\n\n"` in `"a"`, `""`, and `""`. A last line without newline is always kept.
+    /// Applies to each stream separately. Disabled by default.
+    pub fn keep_trailing_empty_line(&mut self, keep: bool) -> &mut Self {
+        self.reader_options.keep_trailing_empty_line = keep;
+        self
+    }
+
+    /// Passes each line to `f` right after it was read, after the built-in transformations
+    /// like [`Command::strip_ansi`]. If `f` returns `Some`, the returned line gets stored
+    /// instead; if it returns `None`, the line gets dropped. This can be used to filter lines,
+    /// to redact secrets, or to normalize lines. Dropped lines don't count for
+    /// [`Command::max_output_bytes`] and [`Command::max_lines`].
+    ///
+    /// The [`Source`] is `None` for [`OCatchStrategy::StdCombined`] and
+    /// [`OCatchStrategy::Pty`], and determined by the classifier for
+    /// [`OCatchStrategy::CombinedPrefixed`]. With [`OCatchStrategy::StdSeparately`], `f`
+    /// runs on the reader threads of STDOUT and STDERR, therefore it must be [`Send`]. The
+    /// calls never overlap though. Clones of the [`Command`] share `f`.
+    pub fn map_line<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnMut(Option<Source>, String) -> Option<String> + Send + 'static,
+    {
+        self.reader_options.map_line.replace(LineMapper::new(f));
+        self
+    }
+
+    /// Sets when reading the output is complete. The default is [`ReadTermination::Eof`].
+    /// With [`ReadTermination::ChildExit`], reading ends once the child exited and the
+    /// output written until then was read, even if the write end of a pipe or the PTY is
+    /// still open, e.g. because of a background process. The state of the child is checked
+    /// once per [`Command::state_poll_interval`] then. Applies to [`Command::catch`],
+    /// [`Command::catch_with_visitor`], and [`Command::catch_chunks`]; for all strategies,
+    /// the output is read like for [`OCatchStrategy::Multiplexed`] then.
+    pub fn read_termination(&mut self, read_termination: ReadTermination) -> &mut Self {
+        self.reader_options.read_termination = read_termination;
+        self
+    }
+
+    /// Collapses runs of identical consecutive lines of a stream into a single line. This
+    /// keeps the output of programs readable that repeat progress or retry messages, and
+    /// bounds the memory for such output. Lines are compared after all transformations like
+    /// [`Command::map_line`]. The collapsed lines still count for [`Command::max_lines`] and
+    /// [`Command::max_output_bytes`]. Applies to [`Command::catch`]. Disabled by default.
+    pub fn dedup_consecutive(&mut self, dedup: bool) -> &mut Self {
+        self.reader_options.dedup_consecutive = dedup;
+        self
+    }
+
+    /// Whether a line that was collapsed by [`Command::dedup_consecutive`] gets the number of
+    /// occurrences as suffix, like `"retrying (repeated 42x)"`. Disabled by default.
+    pub fn dedup_count_suffix(&mut self, count_suffix: bool) -> &mut Self {
+        self.reader_options.dedup_count_suffix = count_suffix;
+        self
+    }
+
+    /// Whether [`Command::catch`] keeps when each line was read, see
+    /// [`ProcessOutput::timed_lines`]. This way you can merge STDOUT and STDERR yourself
+    /// with [`ProcessOutput::merge_by`]. Only supported by [`OCatchStrategy::StdSeparately`].
+    /// Disabled by default.
+    pub fn retain_timestamps(&mut self, retain: bool) -> &mut Self {
+        self.reader_options.retain_timestamps = retain;
+        self
+    }
+
+    /// Whether [`Command::catch`] accumulates statistics about the line lengths while
+    /// reading, see [`ProcessOutput::stats`]. This helps to decide how to display or
+    /// paginate the output and reveals anomalies like a single huge line. Disabled by
+    /// default.
+    pub fn line_stats(&mut self, line_stats: bool) -> &mut Self {
+        self.reader_options.line_stats = line_stats;
+        self
+    }
+
+    /// Controls whether the output of descendants of the child gets caught. Processes that
+    /// the child starts inherit its STDOUT and STDERR, so their output is caught too, and
+    /// the output only reaches EOF once all of them closed it. With `true`, reading waits
+    /// for this EOF, like by default, so the output of background processes that outlive the
+    /// child is included. With `false`, reading stops once the child exited, like with
+    /// [`ReadTermination::ChildExit`]. In both cases,
+    /// [`ProcessOutput::includes_descendant_output`] tells whether output was read after the
+    /// child exited. Applies to [`Command::catch`]; the output is read like for
+    /// [`OCatchStrategy::Multiplexed`] then.
+    pub fn capture_descendants(&mut self, capture: bool) -> &mut Self {
+        self.reader_options.read_termination = if capture {
+            ReadTermination::Eof
+        } else {
+            ReadTermination::ChildExit
+        };
+        self.reader_options.track_descendants = true;
+        self
+    }
+
+    /// Sets what happens if the output exceeds [`Command::max_output_bytes`].
+    /// The default is [`OverflowPolicy::Error`].
+    pub fn on_overflow(&mut self, policy: OverflowPolicy) -> &mut Self {
+        self.reader_options.overflow_policy = policy;
+        self
+    }
+
+    /// Normalizes the text of each line: a trailing `\r` of a CRLF line ending is removed and
+    /// so is a UTF-8 byte order mark (BOM) at the start of the first line of each stream.
+    /// U+FEFF in later lines is kept as zero width no-break space. This produces clean text
+    /// from tools that emit Windows line endings or a BOM. Applied before all other
+    /// transformations. Use
+    /// [`Command::catch_chunks`] if you need the raw bytes. Disabled by default.
+    pub fn normalize_text(&mut self, normalize_text: bool) -> &mut Self {
+        self.reader_options.normalize_text = normalize_text;
+        self
+    }
+
+    /// Removes ANSI escape sequences (colors, cursor movement, ...) from each line after
+    /// it was read. This is useful for programs that produce colorized output.
+    /// Disabled by default, i.e. you get the raw lines including all escape sequences.
+    pub fn strip_ansi(&mut self, strip_ansi: bool) -> &mut Self {
+        self.reader_options.strip_ansi = strip_ansi;
+        self
+    }
+
+    /// Removes `prefix` from each line that starts with it, as the line is read. This is useful
+    /// for structured logs where each line has a fixed prefix. Lines without the prefix stay
+    /// unchanged. Applied after [`Command::strip_ansi`].
+    pub fn trim_line_prefix(&mut self, prefix: &str) -> &mut Self {
+        self.reader_options
+            .trim_line_prefix
+            .replace(prefix.to_string());
+        self
+    }
+
+    /// Like [`Command::trim_line_prefix`] but removes `suffix` from the end of each line.
+    pub fn trim_line_suffix(&mut self, suffix: &str) -> &mut Self {
+        self.reader_options
+            .trim_line_suffix
+            .replace(suffix.to_string());
+        self
+    }
+
+    /// Writes `input` to STDIN of the child and closes STDIN afterwards, so that the
+    /// child reads EOF. The input is written in a separate thread while the output is read.
+    /// By default, the child inherits STDIN of the parent.
+    pub fn input(&mut self, input: &[u8]) -> &mut Self {
+        self.input.replace(input.to_vec());
+        self
+    }
+
+    /// Sets the capacity of the pipes for STDOUT and STDERR in bytes. The child blocks if it
+    /// writes to a full pipe, so larger pipes reduce context switches for bursty output.
+    /// The kernel rounds the value up; unprivileged processes can't exceed
+    /// `/proc/sys/fs/pipe-max-size` ([`UECOError::FcntlFailed`]). Only supported on Linux,
+    /// ignored elsewhere. By default, the capacity of the system is used (64 KiB on Linux).
+    pub fn pipe_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.pipe_capacity.replace(capacity);
+        self
+    }
+
+    /// Sets an environment variable for the child. It overrides a variable of the
+    /// inherited environment with the same name. If `key` or `value` contains a null byte,
+    /// [`UECOError::ContainsNul`] is returned when the child is started.
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// The child doesn't inherit the environment of the current process. It only gets
+    /// the variables set via [`Command::env`]. This is useful for reproducible or sandboxed
+    /// runs. The executable is looked up in the `PATH` set via [`Command::env`], or in
+    /// `/bin:/usr/bin` if there is none.
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.env_clear = true;
+        self
+    }
+
+    /// Runs the program through the login shell of the user, as `$SHELL -lc "<command>"`,
+    /// so that it gets the full login environment, e.g. the `PATH` that the profile of the
+    /// user sets up. This helps GUI apps or services that start without that environment.
+    /// The executable and the args are quoted, so the shell doesn't evaluate them; args[0]
+    /// gets lost, see [`Command::arg0`]. If `SHELL` isn't set, `/bin/sh` is used.
+    /// [`ProcessOutput::resolved_executable`] is the shell then.
+    ///
+    /// This is slower, because the shell sources the profile of the user first. And it's
+    /// less predictable: The profile can change the environment, the working directory, or
+    /// the output, and it runs with the privileges of the child. Don't use it for commands
+    /// that must behave the same for all users. Disabled by default.
+    pub fn login_shell(&mut self, login_shell: bool) -> &mut Self {
+        self.login_shell = login_shell;
+        self
+    }
+
+    /// Sets the file mode creation mask of the child with `umask()` after fork(), e.g.
+    /// `0o077` so that files it creates are only accessible by the owner. By default, the
+    /// child inherits the mask of the current process.
+    pub fn umask(&mut self, umask: libc::mode_t) -> &mut Self {
+        self.child_options.umask.replace(umask);
+        self
+    }
+
+    /// Pins the child to the CPUs with the given indices by calling `sched_setaffinity()`
+    /// after fork(), e.g. for benchmarks or to isolate it from other work. Descendants of
+    /// the child inherit the affinity. If `sched_setaffinity()` fails, e.g. because none of
+    /// the CPUs is available, [`UECOError::SetAffinityFailed`] is returned. Only available on
+    /// Linux. By default, the child inherits the affinity of the current thread.
+    #[cfg(target_os = "linux")]
+    pub fn cpu_affinity(&mut self, cpus: Vec<usize>) -> &mut Self {
+        self.child_options.cpu_affinity.replace(cpus);
+        self
+    }
+
+    /// Moves the child into new namespaces by calling `unshare()` with the given `CLONE_*`
+    /// flags after fork() and before exec(), e.g. `libc::CLONE_NEWNS | libc::CLONE_NEWNET`
+    /// for a new mount namespace and a network namespace without any network. This happens
+    /// before [`Command::chroot`] and [`Command::pre_exec`]. Note that
+    /// `libc::CLONE_NEWPID` only affects the children of the child, not the child itself.
+    ///
+    /// Most namespaces require privileges (`CAP_SYS_ADMIN`); unprivileged users need
+    /// `libc::CLONE_NEWUSER` additionally, if the system allows user namespaces. If
+    /// `unshare()` fails, [`UECOError::UnshareFailed`] is returned. Only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn unshare(&mut self, flags: libc::c_int) -> &mut Self {
+        self.child_options.unshare.replace(flags);
+        self
+    }
+
+    /// Runs the child in a fixed environment, so that captures are byte-for-byte
+    /// reproducible across machines, e.g. for test and build systems. It sets:
+    /// * the umask to [`ReproducibleEnv::umask`], see [`Command::umask`],
+    /// * the environment like [`Command::env_clear`], but the variables in
+    ///   [`ReproducibleEnv::keep`] are inherited,
+    /// * `PATH` to [`ReproducibleEnv::path`],
+    /// * `LC_ALL` to `C`, so messages, sorting, and number formats don't depend on the
+    ///   locale,
+    /// * `TZ` to [`ReproducibleEnv::tz`].
+    ///
+    /// Variables set via [`Command::env`] afterwards override these values.
+    pub fn reproducible_env(&mut self, reproducible_env: ReproducibleEnv) -> &mut Self {
+        self.umask(reproducible_env.umask);
+        self.env_clear();
+        self.env_keep.extend(reproducible_env.keep);
+        self.env("PATH", &reproducible_env.path)
+            .env("LC_ALL", "C")
+            .env("TZ", &reproducible_env.tz)
+    }
+
+    /// Waits for `delay` after the pipes were created and before the child gets forked.
+    /// This blocks the calling thread. It's useful to coordinate races in tests and
+    /// orchestration, e.g. to start a server before its client. By default there is no delay.
+    pub fn start_delay(&mut self, delay: Duration) -> &mut Self {
+        self.start_delay.replace(delay);
+        self
+    }
+
+    /// Retries starting the child up to `retries` times if it fails with a transient error
+    /// (see [`UECOError::is_transient`]), like `EAGAIN` of `fork()` when the process limit is
+    /// reached for a moment or `ETXTBSY` of `exec()` when the binary is still being written.
+    /// Between the attempts, the calling thread sleeps with an exponential backoff starting
+    /// at 10ms. If all attempts fail, the error of the last one is returned. Only starting
+    /// the child is retried, never a child that ran. The default is 0.
+    pub fn retries(&mut self, retries: u32) -> &mut Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Detaches the child from the controlling terminal of the parent by calling `setsid()`
+    /// after fork(). The child becomes the leader of a new session without a controlling
+    /// terminal. Opening `/dev/tty` fails in the child and it doesn't receive signals
+    /// of the terminal, like `SIGHUP` or `SIGINT` from Ctrl+C. This is useful for programs
+    /// that otherwise prompt on the terminal (e.g. for a password) instead of reading STDIN.
+    /// If `setsid()` fails, [`UECOError::SetsidFailed`] is returned. Disabled by default.
+    /// With [`OCatchStrategy::Pty`], the child always gets a new session, but the PTY
+    /// becomes its controlling terminal.
+    pub fn detach_tty(&mut self, detach_tty: bool) -> &mut Self {
+        self.child_options.detach_tty = detach_tty;
+        self
+    }
+
+    /// Verifies in the child that STDOUT and STDERR refer to the capture pipes after they
+    /// were redirected, by comparing device and inode via `fstat()`. This catches
+    /// misconfigurations early, where output would silently go to the terminal of the
+    /// parent instead of being captured. Returns [`UECOError::RedirectVerificationFailed`]
+    /// then. Costs a few syscalls per child. Disabled by default.
+    pub fn verify_redirects(&mut self, verify_redirects: bool) -> &mut Self {
+        self.child_options.verify_redirects = verify_redirects;
+        self
+    }
+
+    /// Samples the memory usage of the child every `interval` in a background thread
+    /// by reading `/proc/<pid>/status` and reports the peak resident set size via
+    /// [`ProcessOutput::peak_rss_kib`]. Short-lived children may exit before the first
+    /// sample is taken. Only available on Linux. Disabled by default.
+    #[cfg(target_os = "linux")]
+    pub fn sample_memory(&mut self, interval: Duration) -> &mut Self {
+        self.memory_sample_interval.replace(interval);
+        self
+    }
+
+    /// Sets what STDIN, STDOUT, and STDERR of the child are connected to, like
+    /// `std::process::Stdio`. By default, STDOUT and STDERR are caught and STDIN is
+    /// inherited. Output that isn't caught is missing in the [`ProcessOutput`]; its lines
+    /// are empty. [`Command::input`] and [`Command::spawn`] always connect STDIN to a pipe.
+    /// With [`Stdio::Capture`], STDIN is connected to a pipe that gets closed right away
+    /// if there is no input, so the child reads EOF.
+    pub fn stdio(&mut self, stdio: StdioConfig) -> &mut Self {
+        self.child_options.stdio = stdio;
+        self
+    }
+
+    /// Shortcut for setting STDIN via [`Command::stdio`].
+    pub fn stdin(&mut self, stdin: Stdio) -> &mut Self {
+        self.child_options.stdio.stdin = stdin;
+        self
+    }
+
+    /// Shortcut for setting STDOUT via [`Command::stdio`].
+    pub fn stdout(&mut self, stdout: Stdio) -> &mut Self {
+        self.child_options.stdio.stdout = stdout;
+        self
+    }
+
+    /// Shortcut for setting STDERR via [`Command::stdio`].
+    pub fn stderr(&mut self, stderr: Stdio) -> &mut Self {
+        self.child_options.stdio.stderr = stderr;
+        self
+    }
+
+    /// Changes the root directory of the child to `root` with `chroot()` and the working
+    /// directory to the new root. The executable is looked up inside the new root, so it
+    /// and everything it needs, like shared libraries, must exist there. If `chroot()`
+    /// fails, [`UECOError::ChrootFailed`] is returned. If `root` contains a null byte,
+    /// [`UECOError::ContainsNul`] is returned when the child is started.
+    ///
+    /// This requires privileges (`CAP_SYS_CHROOT`). The child still runs with them, so
+    /// drop them in [`Command::pre_exec`], which runs after the `chroot()`; otherwise the
+    /// child can break out of the new root.
+    pub fn chroot(&mut self, root: impl AsRef<Path>) -> &mut Self {
+        self.chroot.replace(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Registers a closure that runs in the child after fork(), right before exec(). At this
+    /// point, all pipes are connected and all other options are applied. This is the place
+    /// to restrict the child further, e.g. to install a seccomp filter, to apply landlock
+    /// rules, or to set resource limits with `setrlimit()`. If the closure returns an error,
+    /// the child exits without exec() and [`UECOError::PreExecFailed`] is returned.
+    ///
+    /// # Safety
+    /// The closure runs in a forked copy of a possibly multithreaded process. Only
+    /// async-signal-safe operations are allowed: no memory allocation, no locks, and no
+    /// other functions that may have been in use by another thread during fork(). Like
+    /// `std::os::unix::process::CommandExt::pre_exec`.
+    pub unsafe fn pre_exec<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn() -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        self.child_options.pre_exec.replace(PreExecHook::new(f));
+        self
+    }
+
+    /// Registers a closure that runs in the parent right after fork() with the pid of the
+    /// child, e.g. to record the child in a registry or to move it into a cgroup. It runs
+    /// concurrently to the setup of the child, so exec() may still fail afterwards.
+    /// Together with [`Command::pre_exec`], this gives full control over both sides of
+    /// fork(). If the closure returns an error, the child gets killed and
+    /// [`UECOError::OnSpawnFailed`] is returned.
+    pub fn on_spawn<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(libc::pid_t) -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        self.child_options.on_spawn.replace(SpawnHook::new(f));
+        self
+    }
+
+    /// Sets the disposition of `SIGPIPE` in the child. By default, the child inherits it
+    /// from the parent. Rust programs ignore `SIGPIPE`, so a child that writes to a closed
+    /// pipe gets `EPIPE` errors instead of being terminated silently. Some programs,
+    /// especially in shell pipelines, misbehave then and print errors like "Broken pipe".
+    /// Use [`SigpipeDisposition::Default`] to restore the behavior they expect.
+    pub fn sigpipe(&mut self, sigpipe: SigpipeDisposition) -> &mut Self {
+        self.child_options.sigpipe.replace(sigpipe);
+        self
+    }
+
+    /// Executes the program in a child process and catches its output. This is blocking.
+    /// See [`crate::fork_exec_and_catch`] for more information.
+    pub fn catch(&self) -> Result<ProcessOutput, UECOError> {
+        catch(self)
+    }
+
+    /// Executes the program and returns its raw output, like
+    /// `std::process::Command::output`, but with the additional [`Output::combined`]
+    /// stream of STDOUT and STDERR in the order they were written. This eases the migration
+    /// from std. The bytes are kept as they are, without splitting them into lines. STDOUT
+    /// and STDERR are read like with [`OCatchStrategy::Multiplexed`], independent of
+    /// [`Command::strategy`], so the order of `combined` is as exact as possible with two
+    /// pipes. Options that work on lines have no effect.
+    ///
+    /// ```no_run
+    /// use unix_exec_output_catcher::Command;
+    ///
+    /// let output = Command::new("ls").output().unwrap();
+    /// assert!(output.status.success());
+    /// println!("{}", String::from_utf8_lossy(&output.combined));
+    /// ```
+    pub fn output(&self) -> Result<Output, UECOError> {
+        output(self)
+    }
+
+    /// Executes the program in a child process and panics with the captured output if it
+    /// can't be executed or doesn't exit with code 0. Intended for tests. See
+    /// [`crate::run_expect`] for more information.
+    #[track_caller]
+    pub fn run_expect(&self) -> ProcessOutput {
+        expect_success(self)
+    }
+
+    /// Executes the program in a child process and parses each line of STDOUT into `T`.
+    /// [`Command::strategy`] is ignored, except [`OCatchStrategy::Multiplexed`]. See
+    /// [`crate::read_parsed`] for more information.
+    pub fn read_parsed<T: FromStr>(&self) -> Result<Vec<T>, UECOError> {
+        parse_lines(self)
+    }
+
+    /// Like [`Command::catch`] but returns the result together with `context`, e.g. the
+    /// input that the command processes. See [`crate::batch_run_with_context`].
+    pub fn catch_with_context<T>(&self, context: T) -> CaptureResult<T> {
+        CaptureResult {
+            context,
+            output: self.catch(),
+        }
+    }
+
+    /// Executes the program in a child process like [`Command::catch`] but returns before
+    /// the output is read. STDIN of the child is a pipe whose write end is available via
+    /// [`RunningChild::stdin`], unless [`Command::input`] is used. This way, the input can be
+    /// written while the child runs, e.g. to talk to an interactive program. Write the input
+    /// from a separate thread while [`RunningChild::wait_with_output`] reads the output;
+    /// otherwise, the child may block on a full output pipe while the parent blocks on a full
+    /// input pipe. See [`crate::ChildStdin`].
+    pub fn spawn(&self) -> Result<RunningChild, UECOError> {
+        start(self, true)
+    }
+
+    /// Starts the program in a child process and returns immediately. A background thread
+    /// reads all output and reaps the child, so the caller can do other work meanwhile and
+    /// collect the output later with [`BackgroundCapture::output`].
+    pub fn catch_in_background(&self) -> Result<BackgroundCapture, UECOError> {
+        BackgroundCapture::start(start(self, false)?)
+    }
+
+    /// Executes the program in a child process and passes the raw output to `on_chunk`
+    /// as it arrives, in chunks of up to `chunk_size` bytes. The output is not split into
+    /// lines, so this also works for binary output or if you want to do your own framing.
+    /// The [`Source`] is `None` for [`OCatchStrategy::StdCombined`] and
+    /// [`OCatchStrategy::CombinedPrefixed`], because both streams share one pipe.
+    /// Options that work on lines, like [`Command::max_line_bytes`], have no effect.
+    /// This is blocking and returns after the child exited.
+    pub fn catch_chunks<F>(&self, chunk_size: usize, on_chunk: F) -> Result<ExitStatus, UECOError>
+    where
+        F: FnMut(Option<Source>, Vec<u8>),
+    {
+        catch_chunks(self, chunk_size, on_chunk)
+    }
+
+    /// Executes the program in a child process and streams its raw output through a gzip
+    /// encoder into `writer` as it arrives, e.g. to archive the output of a long-running
+    /// command. The uncompressed output is never held in memory completely. Both streams
+    /// are written as they are read, like with [`Command::catch_chunks`]; with
+    /// [`OCatchStrategy::StdCombined`], they keep the order in which they were written.
+    /// Returns the exit status and the size of the compressed output in bytes. If writing
+    /// fails, the child gets killed and [`UECOError::CompressedWriteFailed`] is returned.
+    /// Requires the feature `flate2`.
+    #[cfg(feature = "flate2")]
+    pub fn catch_compressed<W: Write>(&self, writer: W) -> Result<(ExitStatus, u64), UECOError> {
+        catch_compressed(self, writer)
+    }
+
+    /// Executes the program in a child process and passes each line to `visitor` as soon as
+    /// it was read, instead of collecting all lines. All options that work on lines apply,
+    /// except that [`OverflowPolicy::Tail`] can't drop lines that were visited already.
+    /// STDOUT and STDERR are read in a single thread, like for
+    /// [`OCatchStrategy::Multiplexed`]. This is blocking and returns the exit status and
+    /// why reading stopped early, if it did. With a [`BufferVisitor`], the lines are
+    /// appended to buffers that the caller owns and can reuse across invocations.
+    pub fn catch_with_visitor(
+        &self,
+        visitor: &mut dyn LineVisitor,
+    ) -> Result<(ExitStatus, Option<StopReason>), UECOError> {
+        catch_with_visitor(self, visitor)
+    }
+
+    /// Executes the program in a child process and returns the raw output grouped by the
+    /// `read()` calls that received it. Bytes that the child writes at once (e.g. when it
+    /// flushes its buffer) usually end up in the same group, so the groups expose the
+    /// natural write boundaries of the program. This is useful to reconstruct terminal like
+    /// output. Writes that happen in quick succession may be merged into one group.
+    /// See [`Command::catch_chunks`] for the meaning of the [`Source`].
+    pub fn catch_read_groups(&self) -> Result<(ExitStatus, Vec<ReadGroup>), UECOError> {
+        let mut groups = vec![];
+        let exit_status = self.catch_chunks(READ_GROUP_BUF_LEN, |source, bytes| {
+            groups.push((source, bytes))
+        })?;
+        Ok((exit_status, groups))
+    }
+
+    /// Executes the program in a child process and returns its output as blocking
+    /// [`Iterator`] of [`crate::OutputLine`]s. Nothing gets collected: each call to `next()`
+    /// reads only until the next line is available. The last item is
+    /// [`crate::OutputLine::Eof`] with the exit status. Dropping the [`LineReader`] early
+    /// kills the child.
+    ///
+    /// ```no_run
+    /// use unix_exec_output_catcher::{Command, OutputLine};
+    ///
+    /// for line in Command::new("ls").lines_iter().unwrap() {
+    ///     match line.unwrap() {
+    ///         OutputLine::Line { text, .. } => println!("{}", text),
+    ///         OutputLine::Eof { exit_status } => println!("{}", exit_status),
+    ///     }
+    /// }
+    /// ```
+    pub fn lines_iter(&self) -> Result<LineReader, UECOError> {
+        read_lines(self)
+    }
+
+    /// Executes the program in a child process and returns its output as asynchronous
+    /// [`futures_core::Stream`] of [`crate::OutputLine`]s. The lines are returned as soon as
+    /// they were read; the last item is [`crate::OutputLine::Eof`] with the exit status.
+    /// Must be called inside a tokio runtime. Requires the feature `tokio`.
+    ///
+    /// ```no_run
+    /// # async fn example() {
+    /// use std::future::poll_fn;
+    /// use std::pin::Pin;
+    /// use futures_core::Stream;
+    /// use unix_exec_output_catcher::{Command, OutputLine};
+    ///
+    /// let mut stream = Command::new("ls").stream().unwrap();
+    /// while let Some(line) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+    ///     match line.unwrap() {
+    ///         OutputLine::Line { text, .. } => println!("{}", text),
+    ///         OutputLine::Eof { exit_status } => println!("{}", exit_status),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn stream(&self) -> Result<OutputStream, UECOError> {
+        stream(self)
+    }
+
+    /// Getter for `executable`.
+    pub(crate) fn get_executable(&self) -> &str {
+        &self.executable
+    }
+
+    /// Getter for `args`.
+    pub(crate) fn get_args(&self) -> Vec<&str> {
+        self.args.iter().map(|s| s.as_str()).collect()
+    }
+
+    /// Getter for `strategy`.
+    pub(crate) fn get_strategy(&self) -> OCatchStrategy {
+        self.strategy
+    }
+
+    /// Getter for `input`.
+    pub(crate) fn get_input(&self) -> Option<&[u8]> {
+        self.input.as_deref()
+    }
+
+    /// Getter for `pipe_capacity`.
+    pub(crate) fn get_pipe_capacity(&self) -> Option<usize> {
+        self.pipe_capacity
+    }
+
+    /// Getter for `env_clear`.
+    pub(crate) fn get_env_clear(&self) -> bool {
+        self.env_clear
+    }
+
+    /// Getter for `env`.
+    pub(crate) fn get_env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    /// Runs `closure` in the child instead of exec(), see
+    /// [`crate::testing::fork_closure_and_catch`].
+    #[cfg(feature = "test-hooks")]
+    pub(crate) fn run_instead_of_exec(&mut self, closure: PreExecHook) -> &mut Self {
+        self.child_options.run_instead_of_exec.replace(closure);
+        self
+    }
+
+    /// Getter for `login_shell`.
+    pub(crate) fn get_login_shell(&self) -> bool {
+        self.login_shell
+    }
+
+    /// Getter for `chroot`.
+    pub(crate) fn get_chroot(&self) -> Option<&Path> {
+        self.chroot.as_deref()
+    }
+
+    /// Getter for `env_keep`.
+    pub(crate) fn get_env_keep(&self) -> &[String] {
+        &self.env_keep
+    }
+
+    /// Getter for `start_delay`.
+    pub(crate) fn get_start_delay(&self) -> Option<Duration> {
+        self.start_delay
+    }
+
+    /// Getter for `retries`.
+    pub(crate) fn get_retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Getter for `child_options`.
+    pub(crate) fn get_child_options(&self) -> &ChildOptions {
+        &self.child_options
+    }
+
+    /// Getter for `memory_sample_interval`.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn get_memory_sample_interval(&self) -> Option<Duration> {
+        self.memory_sample_interval
+    }
+
+    /// Getter for `reader_options`.
+    pub(crate) fn get_reader_options(&self) -> &ReaderOptions {
+        &self.reader_options
+    }
+}
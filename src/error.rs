@@ -1,28 +1,156 @@
 //! Contains all errors that can happen in this library.
 
+use crate::{ExitStatus, OCatchStrategy};
 use derive_more::Display;
 use std::error::Error;
+use std::time::Duration;
 
 /// Short for U(nix) E(xec) C(atch) O(utput)-Error.
 /// Combines all errors that can happen inside this library.
-#[derive(Debug, Display, Copy, Clone)]
+#[derive(Debug, Display, Clone)]
 pub enum UECOError {
-    #[display(fmt = "pipe() failed with error code {}", errno)]
+    #[display(fmt = "pipe() failed: {}", "os_error(*errno)")]
     PipeFailed { errno: i32 },
-    #[display(fmt = "dup2() failed with error code {}", errno)]
+    #[display(fmt = "dup2() failed: {}", "os_error(*errno)")]
     Dup2Failed { errno: i32 },
-    #[display(fmt = "execvp() failed with error code {}", errno)]
+    #[display(fmt = "execvp() failed: {}", "os_error(*errno)")]
     ExecvpFailed { errno: i32 },
-    #[display(fmt = "waitpid() failed with error code {}", errno)]
+    #[display(fmt = "execve() failed: {}", "os_error(*errno)")]
+    ExecveFailed { errno: i32 },
+    #[display(fmt = "waitpid() failed: {}", "os_error(*errno)")]
     WaitpidFailed { errno: i32 },
-    #[display(fmt = "read() failed with error code {}", errno)]
+    #[display(fmt = "read() failed: {}", "os_error(*errno)")]
     ReadFailed { errno: i32 },
-    #[display(fmt = "fork() failed with error code {}", errno)]
-    ForkFailed { errno: i32 },
-    #[display(fmt = "close() failed with error code {}", errno)]
+    /// `reason` classifies `errno` and adds a hint to the message.
+    #[display(fmt = "fork() failed: {}{}", "os_error(*errno)", "reason.hint()")]
+    ForkFailed {
+        errno: i32,
+        reason: ForkFailureReason,
+    },
+    #[display(fmt = "close() failed: {}", "os_error(*errno)")]
     CloseFailed { errno: i32 },
+    #[display(fmt = "write() failed: {}", "os_error(*errno)")]
+    WriteFailed { errno: i32 },
+    #[display(fmt = "fcntl() failed: {}", "os_error(*errno)")]
+    FcntlFailed { errno: i32 },
+    #[display(fmt = "setsid() failed: {}", "os_error(*errno)")]
+    SetsidFailed { errno: i32 },
+    #[display(fmt = "sigaction() failed: {}", "os_error(*errno)")]
+    SigactionFailed { errno: i32 },
+    #[display(fmt = "openpty() failed: {}", "os_error(*errno)")]
+    OpenptyFailed { errno: i32 },
+    #[display(fmt = "tcgetattr() failed: {}", "os_error(*errno)")]
+    TcgetattrFailed { errno: i32 },
+    #[display(fmt = "tcsetattr() failed: {}", "os_error(*errno)")]
+    TcsetattrFailed { errno: i32 },
+    #[display(fmt = "fstat() failed: {}", "os_error(*errno)")]
+    FstatFailed { errno: i32 },
+    #[display(fmt = "open() failed: {}", "os_error(*errno)")]
+    OpenFailed { errno: i32 },
+    #[display(fmt = "ioctl() failed: {}", "os_error(*errno)")]
+    IoctlFailed { errno: i32 },
+    #[display(fmt = "wait4() failed: {}", "os_error(*errno)")]
+    Wait4Failed { errno: i32 },
+    #[display(fmt = "chroot() failed: {}", "os_error(*errno)")]
+    ChrootFailed { errno: i32 },
+    #[display(fmt = "chdir() failed: {}", "os_error(*errno)")]
+    ChdirFailed { errno: i32 },
+    #[display(fmt = "waitid() failed: {}", "os_error(*errno)")]
+    WaitidFailed { errno: i32 },
+    #[display(fmt = "sched_setaffinity() failed: {}", "os_error(*errno)")]
+    SetAffinityFailed { errno: i32 },
+    #[display(fmt = "unshare() failed: {}", "os_error(*errno)")]
+    UnshareFailed { errno: i32 },
+    /// Registering a pipe at the tokio reactor failed. See feature `tokio`.
+    #[display(
+        fmt = "registering a pipe at the async runtime failed: {}",
+        "os_error(*errno)"
+    )]
+    AsyncRegistrationFailed { errno: i32 },
+    #[display(fmt = "poll() failed: {}", "os_error(*errno)")]
+    PollFailed { errno: i32 },
+    #[display(fmt = "kill() failed: {}", "os_error(*errno)")]
+    KillFailed { errno: i32 },
+    #[display(fmt = "Spawning a reader thread failed: {}", "os_error(*errno)")]
+    ThreadSpawnFailed { errno: i32 },
+    #[display(fmt = "A reader thread panicked: {}", message)]
+    ReaderThreadPanicked { message: String },
+    #[display(fmt = "The thread that writes to STDIN of the child panicked.")]
+    StdinWriterPanicked,
+    #[display(fmt = "A line exceeded the limit of {} bytes.", limit)]
+    LineTooLong { limit: usize },
+    #[display(
+        fmt = "waitpid() reaped child with pid {} but expected pid {}",
+        got,
+        expected
+    )]
+    UnexpectedChildReaped {
+        got: libc::pid_t,
+        expected: libc::pid_t,
+    },
+    #[display(fmt = "Executable \"{}\" not found in $PATH", executable)]
+    ExecutableNotFound { executable: String },
+    #[display(fmt = "The output is longer than the limit of {} bytes.", limit)]
+    OutputTooLarge { limit: usize },
+    #[display(fmt = "The child didn't finish within {:?} and was killed.", timeout)]
+    TimedOut { timeout: Duration },
+    /// The closure of [`crate::Command::pre_exec`] failed. `errno` is the raw OS error of
+    /// the returned `std::io::Error`, or 0 if it has none.
+    #[display(fmt = "The pre-exec hook failed: {}", "os_error(*errno)")]
+    PreExecFailed { errno: i32 },
+    /// The closure of [`crate::Command::on_spawn`] failed. The child was killed. `errno` is
+    /// the raw OS error of the returned `std::io::Error`, or 0 if it has none.
+    #[display(fmt = "The spawn hook failed: {}", "os_error(*errno)")]
+    OnSpawnFailed { errno: i32 },
+    /// File descriptor `fd` of the child doesn't refer to the pipe after `dup2()`, so the
+    /// output wouldn't be captured. See [`crate::Command::verify_redirects`].
+    #[display(
+        fmt = "File descriptor {} of the child isn't connected to the capture pipe.",
+        fd
+    )]
+    RedirectVerificationFailed { fd: i32 },
+    #[display(fmt = "The executable must not be empty.")]
+    EmptyExecutable,
+    /// Reading or writing a fixture file failed, see [`crate::ProcessOutput::save_fixture`].
+    #[display(fmt = "Accessing the fixture file failed: {}", "os_error(*errno)")]
+    FixtureIoFailed { errno: i32 },
+    /// Writing the compressed output failed, see [`crate::Command::catch_compressed`].
+    #[display(fmt = "Writing the compressed output failed: {}", "os_error(*errno)")]
+    CompressedWriteFailed { errno: i32 },
+    /// The content of a fixture file is invalid, see [`crate::ProcessOutput::from_fixture`].
+    #[display(fmt = "Invalid fixture: {}", reason)]
+    InvalidFixture { reason: String },
+    /// [`crate::MockCatcher`] has no recorded output for the command.
+    #[display(fmt = "No recorded output for the command {}", command)]
+    NoRecordedOutput { command: String },
+    /// The executable path refers to a directory.
+    #[display(fmt = "\"{}\" is a directory, not an executable.", path)]
+    NotAnExecutable { path: String },
+    /// A line couldn't be parsed, see [`crate::read_parsed`]. `line_no` starts at 1.
+    #[display(fmt = "Line {} couldn't be parsed: \"{}\"", line_no, line)]
+    LineParseError { line_no: usize, line: String },
+    /// The command didn't exit with code 0, see [`crate::read_parsed`]. Carries the
+    /// output on STDERR for diagnosis.
+    #[display(fmt = "The command {}: {}", status, stderr)]
+    UnsuccessfulExit { status: ExitStatus, stderr: String },
+    /// STDOUT wasn't caught separately with this strategy, see
+    /// [`crate::ProcessOutput::stdout_as_str`].
+    #[display(
+        fmt = "STDOUT is not available separately with the strategy {}; use StdSeparately or Multiplexed.",
+        strategy
+    )]
+    StdoutUnavailable { strategy: OCatchStrategy },
+    /// See [`crate::Command::parse`].
+    #[display(fmt = "Invalid command line: {}", reason)]
+    InvalidCommandLine { reason: &'static str },
+    /// A string that is passed to the child, like the root directory of
+    /// [`crate::Command::chroot`], contains a null byte (\0).
+    #[display(fmt = "The {} must not contain a null byte (\\0).", what)]
+    ContainsNul { what: &'static str },
     #[display(fmt = "The pipe is not yet marked as read end.")]
     PipeNotMarkedAsReadEnd,
+    #[display(fmt = "The pipe is not yet marked as write end.")]
+    PipeNotMarkedAsWriteEnd,
     #[display(fmt = "The child was already dispatched/started.")]
     ChildAlreadyDispatched,
 
@@ -30,6 +158,117 @@ pub enum UECOError {
     Unknown,
 }
 
+/// Error of [`crate::capture`]. Tells a program that couldn't be executed apart from one
+/// that failed.
+#[derive(Debug, Display, Clone)]
+pub enum CaptureError {
+    /// The program couldn't be executed.
+    #[display(fmt = "{}", _0)]
+    Spawn(UECOError),
+    /// The program didn't exit with code 0. `output` is the combined output and `code`
+    /// is [`crate::ProcessOutput::exit_code`].
+    #[display(fmt = "The program failed with exit code {}", code)]
+    Failed { output: String, code: i32 },
+}
+
+/// Why `fork()` failed, see [`UECOError::ForkFailed`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ForkFailureReason {
+    /// `EAGAIN`: A limit of processes or threads is reached, e.g. `RLIMIT_NPROC`
+    /// of the user, `pid_max`, or `pids.max` of the cgroup.
+    ProcessLimit,
+    /// `ENOMEM`: The kernel couldn't allocate the memory for the new process.
+    OutOfMemory,
+    /// Any other `errno`.
+    Other,
+}
+
+impl ForkFailureReason {
+    /// Classifies the `errno` of `fork()`.
+    pub fn from_errno(errno: i32) -> Self {
+        match errno {
+            libc::EAGAIN => Self::ProcessLimit,
+            libc::ENOMEM => Self::OutOfMemory,
+            _ => Self::Other,
+        }
+    }
+
+    /// Hint for the message of [`UECOError::ForkFailed`].
+    fn hint(&self) -> &'static str {
+        match self {
+            Self::ProcessLimit => {
+                " (the system or user is out of processes; check `ulimit -u`, \
+                 /proc/sys/kernel/pid_max, and pids.max of the cgroup)"
+            }
+            Self::OutOfMemory => " (the system is out of memory)",
+            Self::Other => "",
+        }
+    }
+}
+
+/// Creates the error of the OS for `errno`. Its `Display` is the human readable
+/// message of `errno`, like "Too many open files (os error 24)".
+fn os_error(errno: i32) -> std::io::Error {
+    std::io::Error::from_raw_os_error(errno)
+}
+
+impl UECOError {
+    /// Whether the error is transient, i.e. the same operation may succeed if it's
+    /// retried a little later: `fork()` failed with `EAGAIN` (a process limit was reached)
+    /// or `exec()` failed with `ETXTBSY` (the binary is open for writing) or `EAGAIN`.
+    /// See [`crate::Command::retries`].
+    pub fn is_transient(&self) -> bool {
+        match self {
+            UECOError::ForkFailed { errno, .. } => *errno == libc::EAGAIN,
+            UECOError::ExecvpFailed { errno } | UECOError::ExecveFailed { errno } => {
+                *errno == libc::ETXTBSY || *errno == libc::EAGAIN
+            }
+            _ => false,
+        }
+    }
+}
+
 // IDE might show that display is not implemented but it gets implemented
 // during build by "derive_more" crate
 impl Error for UECOError {}
+
+impl Error for CaptureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CaptureError::Spawn(err) => Some(err),
+            CaptureError::Failed { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_errno() {
+        let err = UECOError::PipeFailed {
+            errno: libc::EMFILE,
+        };
+        assert_eq!(
+            "pipe() failed: Too many open files (os error 24)",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_display_fork_failed() {
+        let err = UECOError::ForkFailed {
+            errno: libc::ENOMEM,
+            reason: ForkFailureReason::from_errno(libc::ENOMEM),
+        };
+        assert_eq!(
+            "fork() failed: Cannot allocate memory (os error 12) (the system is out of memory)",
+            err.to_string()
+        );
+        assert_eq!(
+            ForkFailureReason::ProcessLimit,
+            ForkFailureReason::from_errno(libc::EAGAIN)
+        );
+    }
+}
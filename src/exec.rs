@@ -1,42 +1,93 @@
 //! Utility functions for exec.
 
-use crate::child::ChildProcess;
-use crate::error::UECOError;
+use crate::background::BackgroundCapture;
+use crate::child::{ChildOptions, ChildProcess, Stdio};
+use crate::command::Command;
+use crate::error::{CaptureError, UECOError};
+use crate::label;
 use crate::libc_util::{libc_ret_to_result, LibcSyscall};
-use crate::pipe::CatchPipes;
-use crate::reader::{OutputReader, SimpleOutputReader, SimultaneousOutputReader};
-use crate::OCatchStrategy;
+use crate::line_reader::LineReader;
+use crate::lines::Lines;
+#[cfg(target_os = "linux")]
+use crate::memory::MemorySampler;
+use crate::pipe::{CatchPipes, Pipe};
+#[cfg(feature = "flate2")]
+use crate::reader::ReadEnd;
+use crate::reader::{
+    ChunkReader, LineVisitor, MultiplexedOutputReader, StopReason, MULTIPLEXED_READ_BUF_LEN,
+};
+use crate::running::{ChildStdin, RunningChild};
+use crate::shell_words;
+#[cfg(feature = "tokio")]
+use crate::stream::OutputStream;
 use crate::ProcessOutput;
-use std::ffi::CString;
+use crate::{ExitStatus, OCatchStrategy, Output, Source};
+#[cfg(feature = "flate2")]
+use flate2::{write::GzEncoder, Compression};
+use std::env;
+use std::ffi::{CString, OsString};
+use std::fs;
+#[cfg(feature = "flate2")]
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 /// Wrapper around [`libc::execvp`].
 /// * `executable` Path or name of executable without null (\0).
 /// * `args` vector of args without null (\0). Remember that the
-///          first real arg starts at index 1. index 0 is usually
-///          the name of the executable. See:
-///          https://unix.stackexchange.com/questions/315812/why-does-argv-include-the-program-name
+///   first real arg starts at index 1. index 0 is usually
+///   the name of the executable. See:
+///   https://unix.stackexchange.com/questions/315812/why-does-argv-include-the-program-name
 pub fn exec(executable: &str, args: Vec<&str>) -> Result<(), UECOError> {
     // panics if the string contains a \0 (null)
     let executable = CString::new(executable).expect("Executable must not contain null!");
     let executable = executable.as_c_str();
 
     // Build array of null terminated C-strings array
-    let args = args
-        .iter()
-        .map(|s| CString::new(*s).expect("Arg not contain null!"))
-        .collect::<Vec<CString>>();
+    let args = to_c_strings(args);
     // Build null terminated array with pointers null terminated c-strings
-    let mut args_nl = args
-        .iter()
-        .map(|cs| cs.as_ptr())
-        .collect::<Vec<*const libc::c_char>>();
-    args_nl.push(std::ptr::null());
+    let args_nl = to_null_terminated(&args);
 
     let ret = unsafe { libc::execvp(executable.as_ptr(), args_nl.as_ptr()) };
-    let res = libc_ret_to_result(ret, LibcSyscall::Execvp);
+    libc_ret_to_result(ret, LibcSyscall::Execvp)
+}
+
+/// Wrapper around [`libc::execve`]. Like [`exec`] but the program gets `env` as
+/// environment instead of the environment of the current process. There is no
+/// lookup in $PATH, so `executable` must be a path.
+/// * `env` entries in the form `KEY=VALUE`.
+pub fn exec_with_env(executable: &str, args: Vec<&str>, env: &[CString]) -> Result<(), UECOError> {
+    // panics if the string contains a \0 (null)
+    let executable = CString::new(executable).expect("Executable must not contain null!");
+    let args = to_c_strings(args);
+    let args_nl = to_null_terminated(&args);
+    let env_nl = to_null_terminated(env);
 
-    res
+    let ret = unsafe { libc::execve(executable.as_ptr(), args_nl.as_ptr(), env_nl.as_ptr()) };
+    libc_ret_to_result(ret, LibcSyscall::Execve)
+}
+
+/// Converts the args to C-strings. Panics if an arg contains a null (\0).
+fn to_c_strings(args: Vec<&str>) -> Vec<CString> {
+    args.iter()
+        .map(|s| CString::new(*s).expect("Arg not contain null!"))
+        .collect()
+}
+
+/// Builds the null terminated array of pointers to the C-strings that `exec*()` expects.
+fn to_null_terminated(strings: &[CString]) -> Vec<*const libc::c_char> {
+    let mut pointers = strings
+        .iter()
+        .map(|cs| cs.as_ptr())
+        .collect::<Vec<*const libc::c_char>>();
+    pointers.push(std::ptr::null());
+    pointers
 }
 
 /// Executes a program in a child process and returns the output of STDOUT and STDERR
@@ -53,41 +104,777 @@ pub fn exec(executable: &str, args: Vec<&str>) -> Result<(), UECOError> {
 ///
 ///
 /// * `executable` Path or name of executable without null (\0). Lookup in $PATH happens automatically.
-/// * `args` vector of args, each without null (\0). Remember that the
-///          first real arg starts at index 1. index 0 is usually
-///          the name of the executable. See:
-///          https://unix.stackexchange.com/questions/315812/why-does-argv-include-the-program-name
+/// * `args` args, each without null (\0). Accepts everything that yields strings, like arrays,
+///   slices, `Vec<&str>`, `Vec<String>`, or iterators. Remember that the
+///   first real arg starts at index 1. index 0 is usually
+///   the name of the executable. See:
+///   https://unix.stackexchange.com/questions/315812/why-does-argv-include-the-program-name
 /// * `strategy` Specify how accurate the `"STDCOMBINED` vecor is. See [`crate::OCatchStrategy`] for
-///              more information.
+///   more information.
+///
+/// Use [`crate::Command`] if you need more control.
 pub fn fork_exec_and_catch(
     executable: &str,
-    args: Vec<&str>,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
     strategy: OCatchStrategy,
 ) -> Result<ProcessOutput, UECOError> {
+    Command::with_argv(executable, args)
+        .strategy(strategy)
+        .catch()
+}
+
+/// The simplest way to run a program: Executes it like [`fork_exec_and_catch`] with
+/// [`crate::OCatchStrategy::StdCombined`] and joins all output lines with `\n`.
+/// * `Ok(output)` if the program exited with code 0
+/// * `Err(CaptureError::Failed { output, code })` if the program failed
+/// * `Err(CaptureError::Spawn(err))` if the program couldn't be executed at all
+///
+/// * `executable`, `args` See [`fork_exec_and_catch`].
+pub fn capture(
+    executable: &str,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<String, CaptureError> {
+    let res = fork_exec_and_catch(executable, args, OCatchStrategy::StdCombined)
+        .map_err(CaptureError::Spawn)?;
+    let output = res
+        .stdcombined_lines()
+        .iter()
+        .map(|l| l.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+    if res.exit_status().success() {
+        Ok(output)
+    } else {
+        Err(CaptureError::Failed {
+            output,
+            code: res.exit_code(),
+        })
+    }
+}
+
+/// Executes the program described by the [`Command`] in a child process and returns the
+/// output. See [`fork_exec_and_catch`].
+pub(crate) fn catch(command: &Command) -> Result<ProcessOutput, UECOError> {
+    let _label = label::enter(command.get_reader_options().label.clone());
+    #[cfg(feature = "tracing")]
+    let _span = capture_span(command).entered();
+    start(command, false)?.wait_with_output()
+}
+
+/// Executes the program described by the [`Command`] and parses each line of STDOUT.
+/// See [`Command::read_parsed`].
+pub(crate) fn parse_lines<T: FromStr>(command: &Command) -> Result<Vec<T>, UECOError> {
+    let mut command = command.clone();
+    if !matches!(command.get_strategy(), OCatchStrategy::Multiplexed) {
+        command.strategy(OCatchStrategy::StdSeparately);
+    }
+    let output = catch(&command)?;
+    if !output.exit_status().success() {
+        let stderr = output.stderr().expect("STDERR is caught separately");
+        return Err(UECOError::UnsuccessfulExit {
+            status: output.exit_status(),
+            stderr: stderr.iter().collect::<Vec<&str>>().join("\n"),
+        });
+    }
+    output
+        .stdout()
+        .expect("STDOUT is caught separately")
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            line.parse().map_err(|_| UECOError::LineParseError {
+                line_no: index + 1,
+                line: line.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Executes the program described by the [`Command`] and panics with the captured output
+/// if it fails. See [`Command::run_expect`].
+#[track_caller]
+pub(crate) fn expect_success(command: &Command) -> ProcessOutput {
+    let command_line = format!("{} {:?}", command.get_executable(), command.get_args());
+    let output = match catch(command) {
+        Ok(output) => output,
+        Err(err) => panic!("`{}` couldn't be executed: {}", command_line, err),
+    };
+    if !output.exit_status().success() {
+        panic!("{}", failure_message(&command_line, &output));
+    }
+    output
+}
+
+/// The panic message of [`expect_success`]: the exit status, STDERR if it was caught
+/// separately, and the combined output.
+fn failure_message(command_line: &str, output: &ProcessOutput) -> String {
+    let mut message = format!("`{}` {}", command_line, output.exit_status());
+    let mut section = |title: &str, lines: &Lines| {
+        message.push_str(&format!("\n--- {} ---", title));
+        if lines.is_empty() {
+            message.push_str("\n(empty)");
+        }
+        for line in lines.iter() {
+            message.push('\n');
+            message.push_str(line);
+        }
+    };
+    if let Some(stderr) = output.stderr() {
+        section("stderr", stderr);
+    }
+    section("combined output", output.stdcombined());
+    message
+}
+
+/// Executes the program described by the [`Command`] and returns its raw output.
+/// See [`Command::output`].
+pub(crate) fn output(command: &Command) -> Result<Output, UECOError> {
+    let mut command = command.clone();
+    command.strategy(OCatchStrategy::Multiplexed);
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    let mut combined = vec![];
+    let status = catch_chunks(&command, MULTIPLEXED_READ_BUF_LEN, |source, chunk| {
+        match source {
+            Some(Source::Stderr) => stderr.extend_from_slice(&chunk),
+            _ => stdout.extend_from_slice(&chunk),
+        }
+        combined.extend(chunk);
+    })?;
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+        combined,
+    })
+}
+
+/// Span around a capture with the executable and, once it's dispatched, the pid of the
+/// child as fields. Requires the feature `tracing`.
+#[cfg(feature = "tracing")]
+fn capture_span(command: &Command) -> tracing::Span {
+    tracing::debug_span!(
+        "capture",
+        executable = command.get_executable(),
+        label = command.get_reader_options().label.as_deref(),
+        pid = tracing::field::Empty
+    )
+}
+
+/// Dispatches the program described by the [`Command`] in a child process without
+/// reading its output yet. See [`Command::spawn`].
+/// * `pipe_stdin` connect STDIN of the child to a pipe that is accessible via
+///   [`RunningChild::stdin`]. Has no effect if [`Command::input`] is used.
+pub(crate) fn start(command: &Command, pipe_stdin: bool) -> Result<RunningChild, UECOError> {
+    let _label = label::enter(command.get_reader_options().label.clone());
+    let SpawnedChild {
+        mut child,
+        stdin_pipe,
+        stdin_writer,
+        resolved_executable,
+    } = spawn(command, pipe_stdin)?;
+    #[cfg(target_os = "linux")]
+    let memory_sampler = start_memory_sampler(command, &mut child)?;
+    Ok(RunningChild::new(
+        child,
+        stdin_pipe.map(ChildStdin::new),
+        stdin_writer,
+        resolved_executable,
+        command.get_strategy(),
+        command.get_reader_options().clone(),
+        #[cfg(target_os = "linux")]
+        memory_sampler,
+    ))
+}
+
+/// Executes the program described by the [`Command`] in a child process and passes the raw
+/// output to `on_chunk` in chunks of up to `chunk_size` bytes as it arrives.
+/// See [`Command::catch_chunks`].
+pub(crate) fn catch_chunks<F>(
+    command: &Command,
+    chunk_size: usize,
+    on_chunk: F,
+) -> Result<ExitStatus, UECOError>
+where
+    F: FnMut(Option<Source>, Vec<u8>),
+{
+    let _label = label::enter(command.get_reader_options().label.clone());
+    #[cfg(feature = "tracing")]
+    let _span = capture_span(command).entered();
+    let SpawnedChild {
+        mut child,
+        stdin_writer,
+        ..
+    } = spawn(command, false)?;
+    let exit_status = ChunkReader::new(&mut child, command.get_strategy(), chunk_size)
+        .timeout(command.get_reader_options().timeout)
+        .read_termination(command.get_reader_options())
+        .read_all_bl(on_chunk)?;
+    join_stdin_writer(stdin_writer)?;
+    Ok(exit_status)
+}
+
+/// Counts the bytes that are written to the inner writer.
+#[cfg(feature = "flate2")]
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+#[cfg(feature = "flate2")]
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.written += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Executes the program described by the [`Command`] in a child process and writes the
+/// gzip compressed output to `writer` as it arrives. See [`Command::catch_compressed`].
+#[cfg(feature = "flate2")]
+pub(crate) fn catch_compressed<W: Write>(
+    command: &Command,
+    writer: W,
+) -> Result<(ExitStatus, u64), UECOError> {
+    let to_error = |err: std::io::Error| UECOError::CompressedWriteFailed {
+        errno: err.raw_os_error().unwrap_or(0),
+    };
+    let counting = CountingWriter {
+        inner: writer,
+        written: 0,
+    };
+    let mut encoder = GzEncoder::new(counting, Compression::default());
+    let _label = label::enter(command.get_reader_options().label.clone());
+    #[cfg(feature = "tracing")]
+    let _span = capture_span(command).entered();
+    let SpawnedChild {
+        mut child,
+        stdin_writer,
+        ..
+    } = spawn(command, false)?;
+    let timeout = command.get_reader_options().timeout;
+    let (exit_status, end) =
+        ChunkReader::new(&mut child, command.get_strategy(), MULTIPLEXED_READ_BUF_LEN)
+            .timeout(timeout)
+            .read_termination(command.get_reader_options())
+            // a failed write kills the child
+            .read_until_bl(|_, chunk| encoder.write_all(&chunk).map(|_| true).map_err(to_error))?;
+    join_stdin_writer(stdin_writer)?;
+    if let (ReadEnd::TimedOut, Some(timeout)) = (end, timeout) {
+        return Err(UECOError::TimedOut { timeout });
+    }
+    let counting = encoder.finish().map_err(to_error)?;
+    Ok((exit_status, counting.written))
+}
+
+/// Executes the program described by the [`Command`] in a child process and passes each
+/// line to the visitor as soon as it was read. See [`Command::catch_with_visitor`].
+pub(crate) fn catch_with_visitor(
+    command: &Command,
+    visitor: &mut dyn LineVisitor,
+) -> Result<(ExitStatus, Option<StopReason>), UECOError> {
+    let _label = label::enter(command.get_reader_options().label.clone());
+    #[cfg(feature = "tracing")]
+    let _span = capture_span(command).entered();
+    let SpawnedChild {
+        mut child,
+        stdin_writer,
+        ..
+    } = spawn(command, false)?;
+    let res = MultiplexedOutputReader::new(
+        &mut child,
+        command.get_reader_options().clone(),
+        command.get_strategy(),
+    )
+    .read_all_bl_with_visitor(visitor)?;
+    join_stdin_writer(stdin_writer)?;
+    Ok(res)
+}
+
+/// Starts the [`MemorySampler`] for the child, if [`Command::sample_memory`] was used.
+/// Kills the child if the sampler can't be started.
+#[cfg(target_os = "linux")]
+fn start_memory_sampler(
+    command: &Command,
+    child: &mut ChildProcess,
+) -> Result<Option<MemorySampler>, UECOError> {
+    let interval = match command.get_memory_sample_interval() {
+        Some(interval) => interval,
+        None => return Ok(None),
+    };
+    let pid = child.pid().expect("child is dispatched");
+    match MemorySampler::start(pid, interval) {
+        Ok(sampler) => Ok(Some(sampler)),
+        Err(err) => {
+            child.kill()?;
+            Err(err)
+        }
+    }
+}
+
+/// Executes the program described by the [`Command`] in a child process and returns
+/// its output as lazy [`LineReader`]. See [`Command::lines_iter`].
+pub(crate) fn read_lines(command: &Command) -> Result<LineReader, UECOError> {
+    let _label = label::enter(command.get_reader_options().label.clone());
+    let SpawnedChild {
+        child,
+        stdin_writer,
+        ..
+    } = spawn(command, false)?;
+    Ok(LineReader::new(
+        child,
+        stdin_writer,
+        command.get_strategy(),
+        command.get_reader_options().clone(),
+    ))
+}
+
+/// Executes the program described by the [`Command`] in a child process and returns
+/// its output as [`OutputStream`]. See [`Command::stream`].
+#[cfg(feature = "tokio")]
+pub(crate) fn stream(command: &Command) -> Result<OutputStream, UECOError> {
+    let SpawnedChild {
+        child,
+        stdin_writer,
+        ..
+    } = spawn(command, false)?;
+    OutputStream::new(
+        child,
+        stdin_writer,
+        command.get_strategy(),
+        command.get_reader_options().clone(),
+    )
+}
+
+/// A dispatched child process and everything that belongs to it.
+struct SpawnedChild {
+    child: ChildProcess,
+    /// Write end of the STDIN pipe of the child, if it was requested and there is
+    /// no [`Command::input`].
+    stdin_pipe: Option<Arc<Mutex<Pipe>>>,
+    /// Thread that writes to STDIN of the child, see [`Command::input`].
+    stdin_writer: Option<JoinHandle<Result<(), UECOError>>>,
+    /// See [`resolve_executable`].
+    resolved_executable: PathBuf,
+}
+
+/// Backoff before the first retry of [`Command::retries`]; doubles with each retry.
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Upper limit of the backoff of [`Command::retries`].
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Sets up all pipes and dispatches the child process described by the [`Command`].
+/// Reading the output is up to the caller. Retries transient errors, see
+/// [`Command::retries`].
+/// * `pipe_stdin` see [`start`]
+fn spawn(command: &Command, pipe_stdin: bool) -> Result<SpawnedChild, UECOError> {
+    let mut backoff = RETRY_BACKOFF;
+    let mut retries = command.get_retries();
+    loop {
+        match spawn_once(command, pipe_stdin) {
+            Err(err) if retries > 0 && err.is_transient() => {
+                trace!(
+                    "starting the child failed: {}; retrying in {:?}",
+                    err,
+                    backoff
+                );
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                retries -= 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// A single attempt of [`spawn`]. If it fails, the pipes get closed and a child that was
+/// forked already is reaped.
+fn spawn_once(command: &Command, pipe_stdin: bool) -> Result<SpawnedChild, UECOError> {
+    let env = child_env(command);
+    let path = match &env {
+        // with an explicit environment, the program is looked up like the child would do it
+        Some(env) => env
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "PATH")
+            .map(|(_, value)| value.clone()),
+        None => env::var_os("PATH"),
+    };
+    let root = command.get_chroot();
+    let chroot = root
+        .map(|root| to_c_string(root.as_os_str().as_bytes().to_vec(), "root directory"))
+        .transpose()?;
+    let (executable, args) = exec_target(command, env.as_deref());
+    let resolved_executable = resolve_executable(&executable, path, root)?;
+    let executable = resolved_executable
+        .to_str()
+        .expect("resolved path is always valid UTF-8");
+    let args = args.iter().map(String::as_str).collect::<Vec<&str>>();
+    let strategy = command.get_strategy();
     let cp = CatchPipes::new(strategy)?;
+    if let Some(capacity) = command.get_pipe_capacity() {
+        cp.set_capacity(capacity)?;
+    }
+    let stdin_pipe = if command.get_input().is_some()
+        || pipe_stdin
+        || command.get_child_options().stdio.stdin == Stdio::Capture
+    {
+        Some(Arc::new(Mutex::new(Pipe::new_cloexec()?)))
+    } else {
+        None
+    };
+    let mut options = command.get_child_options().clone();
+    options.chroot = chroot;
+    options.env = env
+        .map(|env| {
+            env.into_iter()
+                .map(|(key, value)| {
+                    let mut entry = key.into_vec();
+                    entry.push(b'=');
+                    entry.extend(value.into_vec());
+                    to_c_string(entry, "environment")
+                })
+                .collect::<Result<Vec<CString>, UECOError>>()
+        })
+        .transpose()?;
     let child = match strategy {
-        OCatchStrategy::StdCombined => setup_and_execute_strategy_combined(executable, args, cp),
-        OCatchStrategy::StdSeparately => {
-            setup_and_execute_strategy_separately(executable, args, cp)
+        OCatchStrategy::StdCombined | OCatchStrategy::CombinedPrefixed(_) | OCatchStrategy::Pty => {
+            setup_and_execute_strategy_combined(executable, args, cp, stdin_pipe.clone(), options)
+        }
+        OCatchStrategy::StdSeparately | OCatchStrategy::Multiplexed => {
+            setup_and_execute_strategy_separately(executable, args, cp, stdin_pipe.clone(), options)
         }
     };
     let mut child = child?;
+    if let Some(delay) = command.get_start_delay() {
+        trace!("delaying the start of the child by {:?}", delay);
+        thread::sleep(delay);
+    }
     child.dispatch()?;
-    let output = match strategy {
-        OCatchStrategy::StdCombined => SimpleOutputReader::new(&mut child).read_all_bl(),
-        OCatchStrategy::StdSeparately => {
-            SimultaneousOutputReader::new(Arc::new(Mutex::new(child))).read_all_bl()
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("pid", child.pid());
+    // feed STDIN concurrently to reading; otherwise the child may block on full output pipes
+    let (stdin_pipe, stdin_writer) = match (stdin_pipe, command.get_input()) {
+        (Some(pipe), Some(input)) => (None, Some(spawn_stdin_writer(pipe, input.to_vec())?)),
+        (stdin_pipe, _) => (stdin_pipe, None),
+    };
+    Ok(SpawnedChild {
+        child,
+        stdin_pipe,
+        stdin_writer,
+        resolved_executable,
+    })
+}
+
+/// Converts `bytes` for the child. Returns [`UECOError::ContainsNul`] with `what` if they
+/// contain a null byte.
+fn to_c_string(bytes: Vec<u8>, what: &'static str) -> Result<CString, UECOError> {
+    CString::new(bytes).map_err(|_| UECOError::ContainsNul { what })
+}
+
+/// The executable and the args that the child executes: the program of the [`Command`]
+/// itself or, with [`Command::login_shell`], the login shell that runs it. `SHELL` is looked
+/// up in `env`, the environment of the child, if it has an explicit one.
+fn exec_target(command: &Command, env: Option<&[(OsString, OsString)]>) -> (String, Vec<String>) {
+    let args = command.get_args();
+    if !command.get_login_shell() {
+        return (
+            command.get_executable().to_string(),
+            args.iter().map(|arg| arg.to_string()).collect(),
+        );
+    }
+    let shell = match env {
+        Some(env) => env
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "SHELL")
+            .map(|(_, value)| value.clone()),
+        None => env::var_os("SHELL"),
+    }
+    .and_then(|shell| shell.into_string().ok())
+    .filter(|shell| !shell.is_empty())
+    .unwrap_or_else(|| "/bin/sh".to_string());
+    // args[0] can't be passed through the shell
+    let mut words = vec![command.get_executable()];
+    words.extend(args.iter().skip(1));
+    let command_line = shell_words::join(&words);
+    (shell.clone(), vec![shell, "-lc".to_string(), command_line])
+}
+
+/// Waits for the thread that writes to STDIN of the child, if there is one.
+pub(crate) fn join_stdin_writer(
+    stdin_writer: Option<JoinHandle<Result<(), UECOError>>>,
+) -> Result<(), UECOError> {
+    match stdin_writer {
+        Some(stdin_writer) => stdin_writer
+            .join()
+            .unwrap_or(Err(UECOError::StdinWriterPanicked)),
+        None => Ok(()),
+    }
+}
+
+/// Builds the environment of the child from the environment of the current process and
+/// [`Command::env`] / [`Command::env_clear`] / [`Command::reproducible_env`]. Returns `None`
+/// if the child simply inherits the environment of the current process.
+fn child_env(command: &Command) -> Option<Vec<(OsString, OsString)>> {
+    if !command.get_env_clear() && command.get_env().is_empty() {
+        return None;
+    }
+    let mut env: Vec<(OsString, OsString)> = if command.get_env_clear() {
+        env::vars_os()
+            .filter(|(key, _)| {
+                command
+                    .get_env_keep()
+                    .iter()
+                    .any(|keep| key == keep.as_str())
+            })
+            .collect()
+    } else {
+        env::vars_os().collect()
+    };
+    for (key, value) in command.get_env() {
+        env.retain(|(existing, _)| existing != key.as_str());
+        env.push((key.into(), value.into()));
+    }
+    Some(env)
+}
+
+/// Default for $PATH if it's not set. Same as the default of glibc.
+const DEFAULT_PATH: &str = "/bin:/usr/bin";
+
+/// Resolves the executable to the path of the binary that will be executed.
+/// If the executable contains a slash, it's used as it is. Otherwise the directories
+/// in $PATH are searched for an executable file with that name (like `execvp()` does).
+/// Returns [`UECOError::ExecutableNotFound`] if there is none,
+/// [`UECOError::EmptyExecutable`] if `executable` is empty, and
+/// [`UECOError::NotAnExecutable`] if a path with a slash refers to a directory.
+/// * `path` value of $PATH; [`DEFAULT_PATH`] if `None`
+/// * `root` the directory that the child uses as root, see [`Command::chroot`]. The
+///   returned path is relative to it.
+fn resolve_executable(
+    executable: &str,
+    path: Option<OsString>,
+    root: Option<&Path>,
+) -> Result<PathBuf, UECOError> {
+    if executable.is_empty() {
+        return Err(UECOError::EmptyExecutable);
+    }
+    if executable.contains('/') {
+        let candidate = PathBuf::from(executable);
+        let on_disk = match root {
+            Some(root) => root.join(candidate.strip_prefix("/").unwrap_or(&candidate)),
+            None => candidate.clone(),
+        };
+        // everything else is reported by exec() in the child
+        if fs::metadata(&on_disk).map(|m| m.is_dir()).unwrap_or(false) {
+            return Err(UECOError::NotAnExecutable {
+                path: executable.to_string(),
+            });
         }
+        return Ok(candidate);
+    }
+    let path = path.unwrap_or_else(|| DEFAULT_PATH.into());
+    env::split_paths(&path)
+        // an empty entry refers to the current working directory
+        .map(|dir| {
+            if dir.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                dir
+            }
+        })
+        .map(|dir| dir.join(executable))
+        // the path is passed as &str to exec; skip everything that is not valid UTF-8
+        .filter(|candidate| candidate.to_str().is_some())
+        .find(|candidate| match root {
+            // the working directory of the child is the new root
+            Some(root) => {
+                is_executable_file(&root.join(candidate.strip_prefix("/").unwrap_or(candidate)))
+            }
+            None => is_executable_file(candidate),
+        })
+        .ok_or_else(|| UECOError::ExecutableNotFound {
+            executable: executable.to_string(),
+        })
+}
+
+/// Checks whether the path refers to a regular file that the process is allowed to execute.
+fn is_executable_file(path: &Path) -> bool {
+    let is_file = fs::metadata(path).map(|m| m.is_file()).unwrap_or(false);
+    let path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => path,
+        Err(_) => return false,
     };
-    output
+    is_file && unsafe { libc::access(path.as_ptr(), libc::X_OK) } == 0
+}
+
+/// Spawns a thread that writes `input` into the write end of the STDIN pipe of the
+/// child and closes it afterwards, so that the child reads EOF.
+fn spawn_stdin_writer(
+    pipe: Arc<Mutex<Pipe>>,
+    input: Vec<u8>,
+) -> Result<JoinHandle<Result<(), UECOError>>, UECOError> {
+    thread::Builder::new()
+        .name("ueco-stdin-writer".to_string())
+        .spawn(move || {
+            let mut pipe = pipe.lock().unwrap();
+            pipe.write_all(&input)?;
+            pipe.close_write_end()
+        })
+        .map_err(|err| UECOError::ThreadSpawnFailed {
+            errno: err.raw_os_error().unwrap_or(0),
+        })
+}
+
+/// Executes a program like [`fork_exec_and_catch`] but writes `input` to its STDIN first.
+/// STDIN gets closed afterwards, so the program reads EOF. This is useful for programs
+/// that read all input and produce output afterwards, like `bc`, `jq`, or `sort`.
+/// The input is written in a separate thread while the output is read.
+///
+/// * `executable`, `args` See [`fork_exec_and_catch`].
+/// * `input` bytes that get written to STDIN of the program.
+/// * `strategy` See [`crate::OCatchStrategy`].
+pub fn run_with_input(
+    executable: &str,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+    input: &[u8],
+    strategy: OCatchStrategy,
+) -> Result<ProcessOutput, UECOError> {
+    Command::with_argv(executable, args)
+        .input(input)
+        .strategy(strategy)
+        .catch()
+}
+
+/// Executes a program like [`fork_exec_and_catch`] but returns a [`LineReader`] that
+/// reads the output lazily, one line per call to `next()`, instead of collecting it.
+/// This way, the memory usage stays constant even for huge outputs and you can stop
+/// early: dropping the reader kills the program. The last item is
+/// [`crate::OutputLine::Eof`] with the exit status.
+///
+/// * `executable`, `args` See [`fork_exec_and_catch`].
+/// * `strategy` See [`crate::OCatchStrategy`].
+pub fn lines_iter(
+    executable: &str,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+    strategy: OCatchStrategy,
+) -> Result<LineReader, UECOError> {
+    Command::with_argv(executable, args)
+        .strategy(strategy)
+        .lines_iter()
+}
+
+/// Starts a program like [`fork_exec_and_catch`] but returns right after it was started.
+/// The output is read by a background thread and can be collected later with
+/// [`BackgroundCapture::output`]. See [`Command::catch_in_background`].
+///
+/// * `executable`, `args` See [`fork_exec_and_catch`].
+/// * `strategy` See [`crate::OCatchStrategy`].
+pub fn catch_in_background(
+    executable: &str,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+    strategy: OCatchStrategy,
+) -> Result<BackgroundCapture, UECOError> {
+    Command::with_argv(executable, args)
+        .strategy(strategy)
+        .catch_in_background()
+}
+
+/// Executes a program like [`fork_exec_and_catch`] with
+/// [`crate::OCatchStrategy::StdSeparately`] and panics if it can't be executed or doesn't
+/// exit with code 0. The panic message contains the exit status, STDERR, and the combined
+/// output, so a failing test is diagnosable right away. Intended for tests, like
+/// `assert_cmd`; use [`fork_exec_and_catch`] to handle failures.
+///
+/// * `executable`, `args` See [`fork_exec_and_catch`].
+#[track_caller]
+pub fn run_expect(
+    executable: &str,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+) -> ProcessOutput {
+    Command::with_argv(executable, args)
+        .strategy(OCatchStrategy::StdSeparately)
+        .run_expect()
+}
+
+/// Executes a program like [`fork_exec_and_catch`] and parses each line of STDOUT into `T`,
+/// e.g. for a command that prints one number per line. STDERR isn't parsed. Returns
+/// [`UECOError::UnsuccessfulExit`] if the program doesn't exit with code 0 and
+/// [`UECOError::LineParseError`] for the first line that can't be parsed.
+///
+/// * `executable`, `args` See [`fork_exec_and_catch`].
+pub fn read_parsed<T: FromStr>(
+    executable: &str,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<Vec<T>, UECOError> {
+    Command::with_argv(executable, args).read_parsed()
+}
+
+/// Connects STDIN of the child to the read end of the pipe, if there is one.
+/// Otherwise, STDIN is connected according to `stdio`.
+/// Must be called in the child after fork().
+fn setup_stdin_pipe_in_child(
+    stdin_pipe: &Option<Arc<Mutex<Pipe>>>,
+    stdio: Stdio,
+) -> Result<(), UECOError> {
+    if let Some(stdin_pipe) = stdin_pipe {
+        let mut stdin_pipe = stdin_pipe.lock().unwrap();
+        stdin_pipe.mark_as_child_process_stdin()?;
+        stdin_pipe.connect_to_stdin()?;
+    } else if stdio == Stdio::Null {
+        connect_to_dev_null(libc::STDIN_FILENO)?;
+    }
+    Ok(())
+}
+
+/// Connects STDOUT or STDERR of the child to the write end of the pipe, to `/dev/null`,
+/// or leaves it as it is, according to `stdio`. Must be called in the child after fork().
+fn connect_output_in_child(pipe: &Pipe, source: Source, stdio: Stdio) -> Result<(), UECOError> {
+    match (stdio, source) {
+        (Stdio::Capture, Source::Stdout) => pipe.connect_to_stdout(),
+        (Stdio::Capture, Source::Stderr) => pipe.connect_to_stderr(),
+        (Stdio::Inherit, _) => Ok(()),
+        (Stdio::Null, Source::Stdout) => connect_to_dev_null(libc::STDOUT_FILENO),
+        (Stdio::Null, Source::Stderr) => connect_to_dev_null(libc::STDERR_FILENO),
+    }
+}
+
+/// Connects `fd` to `/dev/null`. Must be called in the child after fork().
+fn connect_to_dev_null(fd: libc::c_int) -> Result<(), UECOError> {
+    // no allocation after fork()
+    let path = b"/dev/null\0".as_ptr() as *const libc::c_char;
+    let null_fd = unsafe { libc::open(path, libc::O_RDWR) };
+    libc_ret_to_result(null_fd, LibcSyscall::Open)?;
+    let ret = unsafe { libc::dup2(null_fd, fd) };
+    libc_ret_to_result(ret, LibcSyscall::Dup2)?;
+    let ret = unsafe { libc::close(null_fd) };
+    libc_ret_to_result(ret, LibcSyscall::Close)
+}
+
+/// Marks the STDIN pipe as write end, if there is one.
+/// Must be called in the parent after fork().
+fn setup_stdin_pipe_in_parent(stdin_pipe: &Option<Arc<Mutex<Pipe>>>) -> Result<(), UECOError> {
+    if let Some(stdin_pipe) = stdin_pipe {
+        stdin_pipe.lock().unwrap().mark_as_parent_process_stdin()?;
+    }
+    Ok(())
 }
 
 /// Setups up parent and child process and executes everything. Obtains the output
-/// using the [`crate::OCatchStrategy::StdCombined`]-strategy.
+/// using the [`crate::OCatchStrategy::StdCombined`]-strategy (or
+/// [`crate::OCatchStrategy::CombinedPrefixed`]).
 fn setup_and_execute_strategy_combined(
     executable: &str,
     args: Vec<&str>,
     cp: CatchPipes,
+    stdin_pipe: Option<Arc<Mutex<Pipe>>>,
+    options: ChildOptions,
 ) -> Result<ChildProcess, UECOError> {
     let pipe = if let CatchPipes::Combined(pipe) = cp {
         pipe
@@ -96,18 +883,22 @@ fn setup_and_execute_strategy_combined(
     };
     let pipe = Arc::new(Mutex::new(pipe));
     let pipe_closure = pipe.clone();
+    let stdin_pipe_closure = stdin_pipe.clone();
+    let stdio = options.stdio;
     // gets called after fork() after
     let child_setup = move || {
         let mut pipe_closure = pipe_closure.lock().unwrap();
         pipe_closure.mark_as_child_process()?;
-        pipe_closure.connect_to_stdout()?;
-        pipe_closure.connect_to_stderr()?;
+        connect_output_in_child(&pipe_closure, Source::Stdout, stdio.stdout)?;
+        connect_output_in_child(&pipe_closure, Source::Stderr, stdio.stderr)?;
+        setup_stdin_pipe_in_child(&stdin_pipe_closure, stdio.stdin)?;
         Ok(())
     };
     let pipe_closure = pipe.clone();
     let parent_setup = move || {
         let mut pipe_closure = pipe_closure.lock().unwrap();
         pipe_closure.mark_as_parent_process()?;
+        setup_stdin_pipe_in_parent(&stdin_pipe)?;
         Ok(())
     };
     let child = ChildProcess::new(
@@ -117,6 +908,7 @@ fn setup_and_execute_strategy_combined(
         Box::new(parent_setup),
         pipe.clone(),
         pipe,
+        options,
     );
     Ok(child)
 }
@@ -127,6 +919,8 @@ fn setup_and_execute_strategy_separately(
     executable: &str,
     args: Vec<&str>,
     cp: CatchPipes,
+    stdin_pipe: Option<Arc<Mutex<Pipe>>>,
+    options: ChildOptions,
 ) -> Result<ChildProcess, UECOError> {
     let (stdout_pipe, stderr_pipe) = if let CatchPipes::Separately { stdout, stderr } = cp {
         (stdout, stderr)
@@ -137,14 +931,17 @@ fn setup_and_execute_strategy_separately(
     let stderr_pipe = Arc::new(Mutex::new(stderr_pipe));
     let stdout_pipe_closure = stdout_pipe.clone();
     let stderr_pipe_closure = stderr_pipe.clone();
+    let stdin_pipe_closure = stdin_pipe.clone();
+    let stdio = options.stdio;
     // gets called after fork() after
     let child_setup = move || {
         let mut stdout_pipe_closure = stdout_pipe_closure.lock().unwrap();
         let mut stderr_pipe_closure = stderr_pipe_closure.lock().unwrap();
         stdout_pipe_closure.mark_as_child_process()?;
         stderr_pipe_closure.mark_as_child_process()?;
-        stdout_pipe_closure.connect_to_stdout()?;
-        stderr_pipe_closure.connect_to_stderr()?;
+        connect_output_in_child(&stdout_pipe_closure, Source::Stdout, stdio.stdout)?;
+        connect_output_in_child(&stderr_pipe_closure, Source::Stderr, stdio.stderr)?;
+        setup_stdin_pipe_in_child(&stdin_pipe_closure, stdio.stdin)?;
         Ok(())
     };
     let stdout_pipe_closure = stdout_pipe.clone();
@@ -154,6 +951,7 @@ fn setup_and_execute_strategy_separately(
         let mut stderr_pipe_closure = stderr_pipe_closure.lock().unwrap();
         stdout_pipe_closure.mark_as_parent_process()?;
         stderr_pipe_closure.mark_as_parent_process()?;
+        setup_stdin_pipe_in_parent(&stdin_pipe)?;
         Ok(())
     };
     let child = ChildProcess::new(
@@ -163,6 +961,7 @@ fn setup_and_execute_strategy_separately(
         Box::new(parent_setup),
         stdout_pipe,
         stderr_pipe,
+        options,
     );
     Ok(child)
 }
@@ -0,0 +1,140 @@
+//! Fixture files of [`ProcessOutput`]s, e.g. for [`crate::MockCatcher`]. Requires the
+//! feature `serde`.
+
+use crate::error::UECOError;
+use crate::lines::Lines;
+use crate::{
+    ExitStatus, LineStats, OCatchStrategy, ProcessOutput, ResourceUsage, Source, StopReason,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The recorded data of a [`ProcessOutput`] as it is stored in a fixture file (JSON).
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    exit_status: ExitStatus,
+    stdout: Option<Vec<String>>,
+    stderr: Option<Vec<String>>,
+    stdcombined: Vec<String>,
+    stdcombined_sources: Option<Vec<Source>>,
+    /// Name of the [`OCatchStrategy`].
+    strategy: String,
+    resolved_executable: PathBuf,
+    #[serde(default)]
+    peak_rss_kib: Option<u64>,
+    #[serde(default)]
+    stop_reason: Option<StopReason>,
+    #[serde(default)]
+    resource_usage: Option<ResourceUsage>,
+    #[serde(default)]
+    descendant_output: Option<bool>,
+    #[serde(default)]
+    stats: Option<LineStats>,
+    #[serde(default)]
+    min_interline_gap: Option<Duration>,
+}
+
+/// Classifier of a loaded [`OCatchStrategy::CombinedPrefixed`]. The original classifier
+/// isn't recorded; the sources of the lines are.
+fn recorded_classifier(_line: &str) -> Source {
+    Source::Stdout
+}
+
+/// Parses the name of a strategy as it is written by its `Display` impl.
+fn parse_strategy(name: &str) -> Result<OCatchStrategy, UECOError> {
+    match name {
+        "StdCombined" => Ok(OCatchStrategy::StdCombined),
+        "StdSeparately" => Ok(OCatchStrategy::StdSeparately),
+        "CombinedPrefixed" => Ok(OCatchStrategy::CombinedPrefixed(recorded_classifier)),
+        "Pty" => Ok(OCatchStrategy::Pty),
+        "Multiplexed" => Ok(OCatchStrategy::Multiplexed),
+        _ => Err(UECOError::InvalidFixture {
+            reason: format!("unknown strategy \"{}\"", name),
+        }),
+    }
+}
+
+/// Converts lines for the fixture.
+fn to_strings(lines: &Lines) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+/// Converts recorded lines back into [`Lines`].
+fn to_lines(lines: Vec<String>) -> Lines {
+    let mut res = Lines::new();
+    lines.iter().for_each(|line| res.push(line));
+    res
+}
+
+impl ProcessOutput {
+    /// Serializes the output into the JSON format of fixture files, see
+    /// [`ProcessOutput::save_fixture`].
+    pub fn to_fixture(&self) -> String {
+        #[cfg(target_os = "linux")]
+        let peak_rss_kib = self.peak_rss_kib;
+        #[cfg(not(target_os = "linux"))]
+        let peak_rss_kib = None;
+        let fixture = Fixture {
+            exit_status: self.exit_status,
+            stdout: self.stdout.as_ref().map(to_strings),
+            stderr: self.stderr.as_ref().map(to_strings),
+            stdcombined: to_strings(&self.stdcombined),
+            stdcombined_sources: self.stdcombined_sources.clone(),
+            strategy: self.strategy.to_string(),
+            resolved_executable: self.resolved_executable.clone(),
+            peak_rss_kib,
+            stop_reason: self.stop_reason,
+            resource_usage: self.resource_usage,
+            descendant_output: self.descendant_output,
+            stats: self.stats,
+            min_interline_gap: self.min_interline_gap,
+        };
+        serde_json::to_string_pretty(&fixture).expect("the fixture only contains plain data")
+    }
+
+    /// Parses an output from the JSON format of fixture files. Returns
+    /// [`UECOError::InvalidFixture`] if `json` is not a valid fixture. For
+    /// [`OCatchStrategy::CombinedPrefixed`], the classifier isn't recorded; the
+    /// [`Source`]s of the lines are.
+    pub fn from_fixture(json: &str) -> Result<Self, UECOError> {
+        let fixture: Fixture =
+            serde_json::from_str(json).map_err(|err| UECOError::InvalidFixture {
+                reason: err.to_string(),
+            })?;
+        let res = ProcessOutput::new(
+            fixture.stdout.map(to_lines),
+            fixture.stderr.map(to_lines),
+            to_lines(fixture.stdcombined),
+            fixture.stdcombined_sources,
+            fixture.exit_status,
+            parse_strategy(&fixture.strategy)?,
+        )
+        .with_resolved_executable(fixture.resolved_executable)
+        .with_stop_reason(fixture.stop_reason)
+        .with_resource_usage(fixture.resource_usage)
+        .with_descendant_output(fixture.descendant_output)
+        .with_stats(fixture.stats)
+        .with_min_interline_gap(fixture.min_interline_gap);
+        #[cfg(target_os = "linux")]
+        let res = res.with_peak_rss_kib(fixture.peak_rss_kib);
+        Ok(res)
+    }
+
+    /// Records the output in a fixture file, so that it can be replayed in tests without
+    /// spawning the process, see [`crate::MockCatcher`]. The file contains JSON.
+    pub fn save_fixture(&self, path: impl AsRef<Path>) -> Result<(), UECOError> {
+        fs::write(path, self.to_fixture()).map_err(|err| UECOError::FixtureIoFailed {
+            errno: err.raw_os_error().unwrap_or(0),
+        })
+    }
+
+    /// Loads an output that was recorded with [`ProcessOutput::save_fixture`].
+    pub fn load_fixture(path: impl AsRef<Path>) -> Result<Self, UECOError> {
+        let json = fs::read_to_string(path).map_err(|err| UECOError::FixtureIoFailed {
+            errno: err.raw_os_error().unwrap_or(0),
+        })?;
+        Self::from_fixture(&json)
+    }
+}
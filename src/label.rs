@@ -0,0 +1,60 @@
+//! Label of the current capture for log messages, see [`crate::Command::label`].
+
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+thread_local! {
+    /// Label of the capture that the current thread works on.
+    static LABEL: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// Sets the label of the current thread until it gets dropped; restores the previous
+/// label afterwards. Created by [`enter`].
+pub(crate) struct LabelGuard {
+    previous: Option<Arc<str>>,
+}
+
+/// Sets `label` as label of the current thread. Threads that work on the same capture
+/// must enter the label themselves.
+pub(crate) fn enter(label: Option<Arc<str>>) -> LabelGuard {
+    let previous = LABEL.with(|current| current.replace(label));
+    LabelGuard { previous }
+}
+
+impl Drop for LabelGuard {
+    fn drop(&mut self) {
+        LABEL.with(|current| current.replace(self.previous.take()));
+    }
+}
+
+/// Prefix of all log messages: `"[label] "` or nothing if there is no label.
+pub(crate) struct Prefix;
+
+impl Display for Prefix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        LABEL.with(|label| match label.borrow().as_deref() {
+            Some(label) => write!(f, "[{}] ", label),
+            None => Ok(()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!("", Prefix.to_string());
+        {
+            let _outer = enter(Some("outer".into()));
+            {
+                let _inner = enter(Some("inner".into()));
+                assert_eq!("[inner] ", Prefix.to_string());
+            }
+            assert_eq!("[outer] ", Prefix.to_string());
+        }
+        assert_eq!("", Prefix.to_string());
+    }
+}
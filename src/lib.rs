@@ -7,20 +7,71 @@
 //! My library gives you access to `stdout`, `stderr`, **and** `"stdcombined"`. This way you get all
 //! output lines in the order they appeared. That's the unique feature of this crate.
 
+use crate::error::UECOError;
 use derive_more::Display;
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::fmt::{Debug, Formatter};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-#[macro_use]
-extern crate log;
+/// Emits a trace message via `log`, or via `tracing` if the feature `tracing` is enabled.
+/// The message is prefixed with the label of the capture, see [`Command::label`].
+macro_rules! trace {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::trace!("{}{}", crate::label::Prefix, format_args!($($arg)+));
+        #[cfg(not(feature = "tracing"))]
+        log::trace!("{}{}", crate::label::Prefix, format_args!($($arg)+));
+    }};
+}
 
+mod ansi;
+mod background;
+mod batch;
+mod catcher;
 mod child;
+mod command;
 pub mod error;
 mod exec;
+#[cfg(feature = "serde")]
+mod fixture;
+mod label;
 mod libc_util;
+mod line_reader;
+mod lines;
+mod macros;
+#[cfg(target_os = "linux")]
+mod memory;
 mod pipe;
 mod reader;
+mod running;
+mod shell_words;
+#[cfg(feature = "tokio")]
+mod stream;
+#[cfg(feature = "test-hooks")]
+pub mod testing;
 
-pub use exec::fork_exec_and_catch;
+pub use background::BackgroundCapture;
+pub use batch::{batch_run, batch_run_with_context, CaptureResult};
+pub use catcher::{Catcher, ForkCatcher, MockCatcher};
+pub use child::{ReproducibleEnv, ResourceUsage, SigpipeDisposition, Stdio, StdioConfig};
+pub use command::Command;
+pub use exec::{
+    capture, catch_in_background, fork_exec_and_catch, lines_iter, read_parsed, run_expect,
+    run_with_input,
+};
+pub use libc_util::signal_name;
+pub use line_reader::{LineReader, OutputLine, ThrottledLines};
+pub use lines::{Lines, LinesIter};
+pub use reader::{
+    BufferVisitor, LineStats, LineVisitor, OverflowPolicy, ReadTermination, StopReason,
+};
+pub use running::{ChildStdin, RunningChild};
+#[cfg(feature = "tokio")]
+pub use stream::OutputStream;
 
 /// Holds the information from the executed process. It depends on the `strategy` option of
 /// [`crate::fork_exec_and_catch`] how the output is structured.
@@ -29,62 +80,669 @@ pub use exec::fork_exec_and_catch;
 /// * `stdout_lines` and `stderr_lines` are correct but `stdcombined_lines` is only
 ///   maybe in correct order
 /// * or `stdout_lines` and `stderr_lines` are `None`, but `stdcombined_lines` is in correct order
-#[derive(Debug)]
+///
+/// The lines are stored in [`Lines`], i.e. one contiguous buffer per stream. Prefer
+/// [`ProcessOutput::stdout`], [`ProcessOutput::stderr`], and [`ProcessOutput::stdcombined`]
+/// for large outputs. The `Rc<String>`-based getters are created lazily on first use.
 pub struct ProcessOutput {
-    /// Exit code of the process. 0 is success, >1 is error.
-    /// See https://man7.org/linux/man-pages/man3/errno.3.html
-    exit_code: i32,
+    /// How the process terminated. Derived from the raw status of `waitpid()`.
+    exit_status: ExitStatus,
     /// * `None` for [`crate::OCatchStrategy::StdCombined`]
-    /// * `Some` for [`crate::OCatchStrategy::StdSeparately`]
-    stdout_lines: Option<Vec<Rc<String>>>,
+    /// * `Some` for [`crate::OCatchStrategy::StdSeparately`] and [`crate::OCatchStrategy::Multiplexed`]
+    stdout: Option<Lines>,
     /// * `None` for [`crate::OCatchStrategy::StdCombined`]
-    /// * `Some` for [`crate::OCatchStrategy::StdSeparately`]
-    stderr_lines: Option<Vec<Rc<String>>>,
+    /// * `Some` for [`crate::OCatchStrategy::StdSeparately`] and [`crate::OCatchStrategy::Multiplexed`]
+    stderr: Option<Lines>,
     /// * All output lines in correct order for [`crate::OCatchStrategy::StdCombined`]
     /// * All output lines in not guaranteed correct order for [`crate::OCatchStrategy::StdSeparately`]
-    stdcombined_lines: Vec<Rc<String>>,
+    stdcombined: Lines,
+    /// The [`Source`] of each line in `stdcombined`. Only available for
+    /// [`crate::OCatchStrategy::StdSeparately`], [`crate::OCatchStrategy::Multiplexed`], and
+    /// [`crate::OCatchStrategy::CombinedPrefixed`].
+    stdcombined_sources: Option<Vec<Source>>,
     /// The strategy that was used. See [`crate::OCatchStrategy::StdSeparately`].
     strategy: OCatchStrategy,
+    /// Path of the binary that was executed, after the lookup in $PATH.
+    resolved_executable: PathBuf,
+    /// Peak RSS of the child in KiB, see [`crate::Command::sample_memory`].
+    #[cfg(target_os = "linux")]
+    peak_rss_kib: Option<u64>,
+    /// Why reading stopped before the child exited by itself, if it did.
+    stop_reason: Option<StopReason>,
+    /// Resource usage of exactly this child, see [`ProcessOutput::resource_usage`].
+    resource_usage: Option<ResourceUsage>,
+    /// See [`ProcessOutput::includes_descendant_output`].
+    descendant_output: Option<bool>,
+    /// See [`ProcessOutput::stats`].
+    stats: Option<LineStats>,
+    /// When each line of `stdcombined` was read, see [`crate::Command::retain_timestamps`].
+    stdcombined_timestamps: Option<Vec<Instant>>,
+    /// See [`ProcessOutput::min_interline_gap`].
+    min_interline_gap: Option<Duration>,
+    /// Lazily created `Rc<String>` representation of `stdout`.
+    stdout_lines: OnceCell<Option<Vec<Rc<String>>>>,
+    /// Lazily created `Rc<String>` representation of `stderr`.
+    stderr_lines: OnceCell<Option<Vec<Rc<String>>>>,
+    /// Lazily created `Rc<String>` representation of `stdcombined`.
+    stdcombined_lines: OnceCell<Vec<Rc<String>>>,
 }
 
 impl ProcessOutput {
     /// Constructor.
     fn new(
-        stdout_lines: Option<Vec<Rc<String>>>,
-        stderr_lines: Option<Vec<Rc<String>>>,
-        stdcombined_lines: Vec<Rc<String>>,
-        exit_code: i32,
+        stdout: Option<Lines>,
+        stderr: Option<Lines>,
+        stdcombined: Lines,
+        stdcombined_sources: Option<Vec<Source>>,
+        exit_status: ExitStatus,
         strategy: OCatchStrategy,
     ) -> Self {
         Self {
-            stdout_lines,
-            stderr_lines,
-            stdcombined_lines,
-            exit_code,
+            stdout,
+            stderr,
+            stdcombined,
+            stdcombined_sources,
+            exit_status,
             strategy,
+            resolved_executable: PathBuf::new(),
+            #[cfg(target_os = "linux")]
+            peak_rss_kib: None,
+            stop_reason: None,
+            resource_usage: None,
+            descendant_output: None,
+            stats: None,
+            stdcombined_timestamps: None,
+            min_interline_gap: None,
+            stdout_lines: OnceCell::new(),
+            stderr_lines: OnceCell::new(),
+            stdcombined_lines: OnceCell::new(),
         }
     }
 
-    /// Getter for `stdout_lines`. This is only available if [`OCatchStrategy::StdSeparately`] was used.
+    /// Sets the path of the executed binary.
+    fn with_resolved_executable(mut self, resolved_executable: PathBuf) -> Self {
+        self.resolved_executable = resolved_executable;
+        self
+    }
+
+    /// Sets why reading stopped early.
+    fn with_stop_reason(mut self, stop_reason: Option<StopReason>) -> Self {
+        self.stop_reason = stop_reason;
+        self
+    }
+
+    /// Sets the resource usage of the child.
+    fn with_resource_usage(mut self, resource_usage: Option<ResourceUsage>) -> Self {
+        self.resource_usage = resource_usage;
+        self
+    }
+
+    /// Sets whether the output of descendants was read after the child exited.
+    fn with_descendant_output(mut self, descendant_output: Option<bool>) -> Self {
+        self.descendant_output = descendant_output;
+        self
+    }
+
+    /// Sets the statistics about the line lengths.
+    fn with_stats(mut self, stats: Option<LineStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Sets when each line of `stdcombined` was read.
+    fn with_timestamps(mut self, timestamps: Option<Vec<Instant>>) -> Self {
+        self.stdcombined_timestamps = timestamps;
+        self
+    }
+
+    /// Sets the smallest gap between lines of different streams.
+    fn with_min_interline_gap(mut self, min_interline_gap: Option<Duration>) -> Self {
+        self.min_interline_gap = min_interline_gap;
+        self
+    }
+
+    /// Sets the peak RSS of the child.
+    #[cfg(target_os = "linux")]
+    fn with_peak_rss_kib(mut self, peak_rss_kib: Option<u64>) -> Self {
+        self.peak_rss_kib = peak_rss_kib;
+        self
+    }
+
+    /// Getter for `stdout`. This is only available if [`OCatchStrategy::StdSeparately`]
+    /// or [`OCatchStrategy::Multiplexed`] was used.
+    pub fn stdout(&self) -> Option<&Lines> {
+        self.stdout.as_ref()
+    }
+    /// Getter for `stderr`. This is only available if [`OCatchStrategy::StdSeparately`]
+    /// or [`OCatchStrategy::Multiplexed`] was used.
+    pub fn stderr(&self) -> Option<&Lines> {
+        self.stderr.as_ref()
+    }
+    /// Getter for `stdcombined`. The correctness of the ordering depends on the used [`OCatchStrategy`].
+    pub fn stdcombined(&self) -> &Lines {
+        &self.stdcombined
+    }
+    /// Returns each line of `stdcombined` together with its [`Source`]. This is only available
+    /// if [`OCatchStrategy::StdSeparately`] or [`OCatchStrategy::CombinedPrefixed`] was used.
+    /// For the latter, the sources are heuristic, because they are determined by the
+    /// classifier of the strategy.
+    pub fn stdcombined_tagged(&self) -> Option<Vec<(Source, &str)>> {
+        self.stdcombined_sources.as_ref().map(|sources| {
+            sources
+                .iter()
+                .copied()
+                .zip(self.stdcombined.iter())
+                .collect()
+        })
+    }
+    /// Returns each line of `stdcombined` together with its [`Source`] and when it was read.
+    /// Only available with [`OCatchStrategy::StdSeparately`] if
+    /// [`Command::retain_timestamps`] was set.
+    pub fn timed_lines(&self) -> Option<Vec<TimedLine>> {
+        let sources = self.stdcombined_sources.as_ref()?;
+        let timestamps = self.stdcombined_timestamps.as_ref()?;
+        Some(
+            sources
+                .iter()
+                .zip(timestamps)
+                .zip(self.stdcombined_lines())
+                .map(|((source, timestamp), line)| TimedLine {
+                    source: *source,
+                    timestamp: *timestamp,
+                    line: line.clone(),
+                })
+                .collect(),
+        )
+    }
+    /// Merges the lines of STDOUT and STDERR into one list like `stdcombined`, but `cmp`
+    /// decides which of the next lines of both streams comes first. Each stream keeps its
+    /// own order. This gives full control over the order if you know more than this crate,
+    /// e.g. if the lines contain timestamps themselves. If `cmp` returns
+    /// [`Ordering::Equal`], the line of STDOUT comes first. Only available if the
+    /// timestamps were retained, see [`ProcessOutput::timed_lines`].
+    pub fn merge_by(
+        &self,
+        cmp: impl Fn(&TimedLine, &TimedLine) -> Ordering,
+    ) -> Option<Vec<Rc<String>>> {
+        let (stdout, stderr): (Vec<_>, Vec<_>) = self
+            .timed_lines()?
+            .into_iter()
+            .partition(|line| line.source == Source::Stdout);
+        let mut merged = Vec::with_capacity(stdout.len() + stderr.len());
+        let mut stdout = stdout.into_iter().peekable();
+        let mut stderr = stderr.into_iter().peekable();
+        loop {
+            let next = match (stdout.peek(), stderr.peek()) {
+                (Some(out), Some(err)) if cmp(err, out) == Ordering::Less => stderr.next(),
+                (Some(_), _) => stdout.next(),
+                (None, Some(_)) => stderr.next(),
+                (None, None) => break,
+            };
+            merged.push(next.unwrap().line);
+        }
+        Some(merged)
+    }
+    /// Splits `stdcombined` into a guess of STDOUT and a guess of STDERR by classifying each
+    /// line with `classify`. This is only a heuristic: With [`OCatchStrategy::StdCombined`]
+    /// the information about the real source of a line is lost. It helps if you need the
+    /// correct order of [`OCatchStrategy::StdCombined`] but also a rough split afterwards.
+    /// Returns `(stdout_guess, stderr_guess)`, each in the original order.
+    pub fn classify_combined(
+        &self,
+        classify: impl Fn(&str) -> Source,
+    ) -> (Vec<Rc<String>>, Vec<Rc<String>>) {
+        self.stdcombined_lines()
+            .iter()
+            .cloned()
+            .partition(|line| classify(line) == Source::Stdout)
+    }
+    /// Getter for `stdout_lines`. This is only available if [`OCatchStrategy::StdSeparately`]
+    /// or [`OCatchStrategy::Multiplexed`] was used. `None` means that the strategy didn't
+    /// catch STDOUT separately, while an empty vector means that STDOUT was caught but the
+    /// child wrote nothing to it. See [`ProcessOutput::stdout_was_captured`] and
+    /// [`ProcessOutput::stdout_or_empty`].
     pub fn stdout_lines(&self) -> Option<&Vec<Rc<String>>> {
-        self.stdout_lines.as_ref()
+        self.stdout_lines
+            .get_or_init(|| self.stdout.as_ref().map(to_rc_lines))
+            .as_ref()
+    }
+    /// Whether STDOUT was caught separately, i.e. whether [`ProcessOutput::stdout_lines`]
+    /// is `Some`, even if it's empty. With [`Command::stdout`] set to something else than
+    /// [`Stdio::Capture`], STDOUT still counts as caught, but it's always empty.
+    pub fn stdout_was_captured(&self) -> bool {
+        self.stdout.is_some()
     }
-    /// Getter for `stderr_lines`. This is only available if [`OCatchStrategy::StdSeparately`] was used.
+    /// The lines of STDOUT, or an empty slice if there are none or if STDOUT wasn't caught
+    /// separately. For callers that don't care about the difference, see
+    /// [`ProcessOutput::stdout_lines`].
+    pub fn stdout_or_empty(&self) -> &[Rc<String>] {
+        self.stdout_lines().map_or(&[], |lines| lines.as_slice())
+    }
+    /// Joins the lines of STDOUT with `\n`. Unlike [`ProcessOutput::stdout_lines`], a
+    /// strategy without separate STDOUT is reported as [`UECOError::StdoutUnavailable`]
+    /// instead of `None`, so it can be propagated with `?`. STDOUT is only available if
+    /// [`OCatchStrategy::StdSeparately`] or [`OCatchStrategy::Multiplexed`] was used.
+    pub fn stdout_as_str(&self) -> Result<String, UECOError> {
+        let stdout = self.stdout.as_ref().ok_or(UECOError::StdoutUnavailable {
+            strategy: self.strategy,
+        })?;
+        Ok(stdout.iter().collect::<Vec<&str>>().join("\n"))
+    }
+    /// Getter for `stderr_lines`. This is only available if [`OCatchStrategy::StdSeparately`]
+    /// or [`OCatchStrategy::Multiplexed`] was used. Like for
+    /// [`ProcessOutput::stdout_lines`], `None` and an empty vector mean different things.
     pub fn stderr_lines(&self) -> Option<&Vec<Rc<String>>> {
-        self.stderr_lines.as_ref()
+        self.stderr_lines
+            .get_or_init(|| self.stderr.as_ref().map(to_rc_lines))
+            .as_ref()
+    }
+    /// Whether STDERR was caught separately. See [`ProcessOutput::stdout_was_captured`].
+    pub fn stderr_was_captured(&self) -> bool {
+        self.stderr.is_some()
+    }
+    /// The lines of STDERR, or an empty slice. See [`ProcessOutput::stdout_or_empty`].
+    pub fn stderr_or_empty(&self) -> &[Rc<String>] {
+        self.stderr_lines().map_or(&[], |lines| lines.as_slice())
     }
     /// Getter for `stdcombined_lines`. The correctness of the ordering depends on the used [`OCatchStrategy`].
     pub fn stdcombined_lines(&self) -> &Vec<Rc<String>> {
-        &self.stdcombined_lines
+        self.stdcombined_lines
+            .get_or_init(|| to_rc_lines(&self.stdcombined))
+    }
+    /// Consumes the output and returns the lines of STDOUT as owned `String`s, without the
+    /// `Rc` of [`ProcessOutput::stdout_lines`]. This is only available if
+    /// [`OCatchStrategy::StdSeparately`] or [`OCatchStrategy::Multiplexed`] was used.
+    pub fn into_stdout_strings(self) -> Option<Vec<String>> {
+        self.stdout.map(Lines::into_strings)
+    }
+    /// Consumes the output and returns the lines of STDERR as owned `String`s. This is only
+    /// available if [`OCatchStrategy::StdSeparately`] or [`OCatchStrategy::Multiplexed`] was used.
+    pub fn into_stderr_strings(self) -> Option<Vec<String>> {
+        self.stderr.map(Lines::into_strings)
+    }
+    /// Consumes the output and returns the lines of `stdcombined` as owned `String`s.
+    pub fn into_combined_strings(self) -> Vec<String> {
+        self.stdcombined.into_strings()
+    }
+    /// Returns the last line of STDOUT, e.g. for an error summary. `None` if there is no
+    /// output on STDOUT or if it wasn't caught separately (see [`ProcessOutput::stdout_lines`]).
+    pub fn last_stdout_line(&self) -> Option<&Rc<String>> {
+        self.stdout_lines().and_then(|lines| lines.last())
+    }
+    /// Returns the last line of STDERR. `None` if there is no output on STDERR or if it
+    /// wasn't caught separately (see [`ProcessOutput::stderr_lines`]).
+    pub fn last_stderr_line(&self) -> Option<&Rc<String>> {
+        self.stderr_lines().and_then(|lines| lines.last())
+    }
+    /// Returns the last line of the combined output. `None` if there is no output at all.
+    pub fn last_combined_line(&self) -> Option<&Rc<String>> {
+        self.stdcombined_lines().last()
+    }
+    /// Returns the line of STDOUT with index `n`, starting at 0. `None` if there is no such
+    /// line or if STDOUT wasn't caught separately (see [`ProcessOutput::stdout_lines`]).
+    pub fn stdout_line(&self, n: usize) -> Option<&Rc<String>> {
+        self.stdout_lines().and_then(|lines| lines.get(n))
+    }
+    /// Returns the line of STDERR with index `n`, starting at 0. `None` if there is no such
+    /// line or if STDERR wasn't caught separately (see [`ProcessOutput::stderr_lines`]).
+    pub fn stderr_line(&self, n: usize) -> Option<&Rc<String>> {
+        self.stderr_lines().and_then(|lines| lines.get(n))
+    }
+    /// Returns the line of the combined output with index `n`, starting at 0. `None` if
+    /// there is no such line.
+    pub fn combined_line(&self, n: usize) -> Option<&Rc<String>> {
+        self.stdcombined_lines().get(n)
+    }
+    /// Groups the lines of the combined output into paragraphs, i.e. runs of consecutive
+    /// lines that are separated by blank lines, like the blocks of `git log`. Lines that
+    /// are empty or consist only of whitespace count as blank and aren't part of any
+    /// paragraph.
+    pub fn combined_paragraphs(&self) -> Vec<Vec<Rc<String>>> {
+        self.stdcombined_lines()
+            .split(|line| line.trim().is_empty())
+            .filter(|paragraph| !paragraph.is_empty())
+            .map(|paragraph| paragraph.to_vec())
+            .collect()
     }
-    /// Getter for `exit_code` of the executed child process.
+    /// Replays the output, e.g. to forward the output of a child to the real STDOUT and
+    /// STDERR. Each line is followed by `\n`. If STDOUT and STDERR were caught separately
+    /// (see [`ProcessOutput::stdout`]), the lines of STDOUT are written to `out` and the
+    /// lines of STDERR to `err`, in the order of `stdcombined`. Otherwise, all lines are
+    /// written to `out`.
+    pub fn write_to(&self, out: &mut dyn Write, err: &mut dyn Write) -> io::Result<()> {
+        match (&self.stdout, &self.stdcombined_sources) {
+            (Some(_), Some(sources)) => {
+                for (source, line) in sources.iter().zip(self.stdcombined.iter()) {
+                    match source {
+                        Source::Stdout => writeln!(out, "{}", line)?,
+                        Source::Stderr => writeln!(err, "{}", line)?,
+                    }
+                }
+            }
+            _ => {
+                for line in self.stdcombined.iter() {
+                    writeln!(out, "{}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Returns true if the process produced no output at all (neither STDOUT nor STDERR).
+    pub fn is_empty(&self) -> bool {
+        self.stdcombined.is_empty()
+    }
+    /// Returns true if the process wrote at least one line to STDOUT. Always false
+    /// if STDOUT wasn't caught separately, see [`ProcessOutput::stdout_lines`].
+    pub fn has_stdout(&self) -> bool {
+        self.stdout.as_ref().is_some_and(|l| !l.is_empty())
+    }
+    /// Returns true if the process wrote at least one line to STDERR. Always false
+    /// if STDERR wasn't caught separately, see [`ProcessOutput::stderr_lines`].
+    pub fn has_stderr(&self) -> bool {
+        self.stderr.as_ref().is_some_and(|l| !l.is_empty())
+    }
+    /// Getter for the [`ExitStatus`] of the executed child process.
+    pub fn exit_status(&self) -> ExitStatus {
+        self.exit_status
+    }
+    /// Exit code of the executed child process. 0 is success, >1 is error.
+    /// This is a shortcut for [`ExitStatus::code`], see there for how signals are mapped.
     pub fn exit_code(&self) -> i32 {
-        self.exit_code
+        self.exit_status.code()
+    }
+    /// Path of the binary that was actually executed. If the executable was given by
+    /// name, this is the result of the lookup in $PATH, e.g. `/usr/bin/python3` for `python3`.
+    pub fn resolved_executable(&self) -> &Path {
+        &self.resolved_executable
+    }
+    /// Why reading stopped before the child exited by itself, e.g. because
+    /// [`crate::Command::max_lines`] lines were caught. The output is incomplete then and
+    /// the child was killed, unless it exited at the same time or
+    /// [`crate::Command::kill_on_stop`] is disabled. `None` if all output was read.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+    /// Whether output was read after the child exited, which only happens if descendants
+    /// of the child, e.g. background processes, inherited its STDOUT or STDERR and kept it
+    /// open. `None` unless [`Command::capture_descendants`] was set. Note that output that
+    /// descendants wrote while the child still ran can't be told apart and is always
+    /// included.
+    pub fn includes_descendant_output(&self) -> Option<bool> {
+        self.descendant_output
+    }
+    /// Number of caught lines and their minimal, maximal, and average length, accumulated
+    /// while reading. Lines that [`crate::OverflowPolicy::Tail`] dropped afterwards are
+    /// included. `None` unless [`Command::line_stats`] was set.
+    pub fn stats(&self) -> Option<LineStats> {
+        self.stats
+    }
+    /// How much the order of `stdcombined` of [`OCatchStrategy::StdSeparately`] can be
+    /// trusted: the smallest time between two consecutive lines of `stdcombined` that were
+    /// read from different streams. Lines of the same stream are always in the right order.
+    /// If the gap is below [`INTERLEAVING_SAFETY_GAP`], the two lines may be swapped. `None`
+    /// for the other strategies and if the output never switches between the streams.
+    pub fn min_interline_gap(&self) -> Option<Duration> {
+        self.min_interline_gap
+    }
+    /// CPU times and peak RSS of exactly this child, obtained via `wait4()` when it was
+    /// reaped. Unlike `getrusage(RUSAGE_CHILDREN)`, this doesn't include other children
+    /// of the current process. `None` if the output was created without reaping a child.
+    pub fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.resource_usage
+    }
+    /// Peak resident set size (RSS) of the child in KiB. Only available if
+    /// [`crate::Command::sample_memory`] was used and at least one sample could be taken
+    /// before the child exited.
+    #[cfg(target_os = "linux")]
+    pub fn peak_rss_kib(&self) -> Option<u64> {
+        self.peak_rss_kib
     }
     /// Getter for the used [`OCatchStrategy`].
     pub fn strategy(&self) -> OCatchStrategy {
         self.strategy
     }
+    /// Whether the order of [`ProcessOutput::stdcombined_lines`] is exact or only
+    /// best-effort. This depends on the used [`OCatchStrategy`], see
+    /// [`OCatchStrategy::ordering_guarantee`].
+    pub fn ordering_guarantee(&self) -> OrderingGuarantee {
+        self.strategy.ordering_guarantee()
+    }
+    /// Consumes the output and returns the lines of STDOUT, STDERR, and STDCOMBINED
+    /// (like [`ProcessOutput::stdout_lines`] and friends), the exit code
+    /// (see [`ProcessOutput::exit_code`]), and the used strategy. This way you take ownership
+    /// of the vectors without cloning them. Vectors that were already created by the
+    /// getters are reused.
+    pub fn into_parts(self) -> OutputParts {
+        let stdout = match self.stdout_lines.into_inner() {
+            Some(lines) => lines,
+            None => self.stdout.as_ref().map(to_rc_lines),
+        };
+        let stderr = match self.stderr_lines.into_inner() {
+            Some(lines) => lines,
+            None => self.stderr.as_ref().map(to_rc_lines),
+        };
+        let stdcombined = match self.stdcombined_lines.into_inner() {
+            Some(lines) => lines,
+            None => to_rc_lines(&self.stdcombined),
+        };
+        (
+            stdout,
+            stderr,
+            stdcombined,
+            self.exit_status.code(),
+            self.strategy,
+        )
+    }
+}
+
+/// Return type of [`ProcessOutput::into_parts`]: STDOUT lines, STDERR lines,
+/// STDCOMBINED lines, exit code, and the used strategy.
+pub type OutputParts = (
+    Option<Vec<Rc<String>>>,
+    Option<Vec<Rc<String>>>,
+    Vec<Rc<String>>,
+    i32,
+    OCatchStrategy,
+);
+
+impl Debug for ProcessOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("ProcessOutput");
+        debug
+            .field("exit_status", &self.exit_status)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .field("stdcombined", &self.stdcombined)
+            .field("stdcombined_sources", &self.stdcombined_sources)
+            .field("strategy", &self.strategy)
+            .field("resolved_executable", &self.resolved_executable);
+        #[cfg(target_os = "linux")]
+        debug.field("peak_rss_kib", &self.peak_rss_kib);
+        debug
+            .field("stop_reason", &self.stop_reason)
+            .field("resource_usage", &self.resource_usage)
+            .field("descendant_output", &self.descendant_output)
+            .field("stats", &self.stats)
+            .field("stdcombined_timestamps", &self.stdcombined_timestamps)
+            .field("min_interline_gap", &self.min_interline_gap)
+            .finish()
+    }
+}
+
+/// The data of a [`ProcessOutput`] without the lazily created `Rc<String>` representations,
+/// which are not [`Send`]. Used to move an output to another thread, see [`batch_run`] and
+/// [`BackgroundCapture`], and to store the recordings of a [`MockCatcher`].
+#[derive(Debug, Clone)]
+struct SendableOutput {
+    exit_status: ExitStatus,
+    stdout: Option<Lines>,
+    stderr: Option<Lines>,
+    stdcombined: Lines,
+    stdcombined_sources: Option<Vec<Source>>,
+    strategy: OCatchStrategy,
+    resolved_executable: PathBuf,
+    #[cfg(target_os = "linux")]
+    peak_rss_kib: Option<u64>,
+    stop_reason: Option<StopReason>,
+    resource_usage: Option<ResourceUsage>,
+    descendant_output: Option<bool>,
+    stats: Option<LineStats>,
+    stdcombined_timestamps: Option<Vec<Instant>>,
+    min_interline_gap: Option<Duration>,
+}
+
+impl From<ProcessOutput> for SendableOutput {
+    fn from(output: ProcessOutput) -> Self {
+        Self {
+            exit_status: output.exit_status,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            stdcombined: output.stdcombined,
+            stdcombined_sources: output.stdcombined_sources,
+            strategy: output.strategy,
+            resolved_executable: output.resolved_executable,
+            #[cfg(target_os = "linux")]
+            peak_rss_kib: output.peak_rss_kib,
+            stop_reason: output.stop_reason,
+            resource_usage: output.resource_usage,
+            descendant_output: output.descendant_output,
+            stats: output.stats,
+            stdcombined_timestamps: output.stdcombined_timestamps,
+            min_interline_gap: output.min_interline_gap,
+        }
+    }
+}
+
+impl From<SendableOutput> for ProcessOutput {
+    fn from(output: SendableOutput) -> Self {
+        let res = ProcessOutput::new(
+            output.stdout,
+            output.stderr,
+            output.stdcombined,
+            output.stdcombined_sources,
+            output.exit_status,
+            output.strategy,
+        )
+        .with_resolved_executable(output.resolved_executable)
+        .with_stop_reason(output.stop_reason)
+        .with_resource_usage(output.resource_usage)
+        .with_descendant_output(output.descendant_output)
+        .with_stats(output.stats)
+        .with_timestamps(output.stdcombined_timestamps)
+        .with_min_interline_gap(output.min_interline_gap);
+        #[cfg(target_os = "linux")]
+        let res = res.with_peak_rss_kib(output.peak_rss_kib);
+        res
+    }
+}
+
+/// Creates the `Rc<String>` representation of the lines.
+fn to_rc_lines(lines: &Lines) -> Vec<Rc<String>> {
+    lines.iter().map(|l| Rc::new(l.to_string())).collect()
+}
+
+/// Describes how the child process terminated. Derived from the raw status
+/// that `waitpid()` reports.
+#[derive(Debug, Display, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExitStatus {
+    /// The process exited normally (`WIFEXITED`) with the given exit code.
+    #[display(fmt = "exited with code {}", _0)]
+    Exited(i32),
+    /// The process was terminated by a signal (`WIFSIGNALED`).
+    #[display(
+        fmt = "killed by {} (signal {}, core dumped: {})",
+        "signal_name(*signal)",
+        signal,
+        core_dumped
+    )]
+    Signaled { signal: i32, core_dumped: bool },
+}
+
+impl ExitStatus {
+    /// Creates the [`ExitStatus`] from the raw status of `waitpid()`.
+    /// Returns `None` if the status doesn't describe one of the known states.
+    pub(crate) fn from_raw(status: libc::c_int) -> Option<Self> {
+        if libc::WIFEXITED(status) {
+            Some(ExitStatus::Exited(libc::WEXITSTATUS(status)))
+        } else if libc::WIFSIGNALED(status) {
+            Some(ExitStatus::Signaled {
+                signal: libc::WTERMSIG(status),
+                core_dumped: libc::WCOREDUMP(status),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the status as a single exit code, following the shell convention:
+    /// * [`ExitStatus::Exited`] => the exit code itself
+    /// * [`ExitStatus::Signaled`] => `128 + signal`, e.g. 137 for `SIGKILL`
+    pub fn code(&self) -> i32 {
+        match *self {
+            ExitStatus::Exited(code) => code,
+            ExitStatus::Signaled { signal, .. } => 128 + signal,
+        }
+    }
+
+    /// The exit code if the process exited normally. Unlike [`ExitStatus::code`], a signal
+    /// can't be confused with an exit code.
+    pub fn exited(&self) -> Option<i32> {
+        match *self {
+            ExitStatus::Exited(code) => Some(code),
+            ExitStatus::Signaled { .. } => None,
+        }
+    }
+
+    /// The signal that terminated the process, if it was killed by one.
+    pub fn signal(&self) -> Option<i32> {
+        match *self {
+            ExitStatus::Exited(_) => None,
+            ExitStatus::Signaled { signal, .. } => Some(signal),
+        }
+    }
+
+    /// Whether the process was killed by a signal and produced a core dump.
+    pub fn core_dumped(&self) -> bool {
+        match *self {
+            ExitStatus::Exited(_) => false,
+            ExitStatus::Signaled { core_dumped, .. } => core_dumped,
+        }
+    }
+
+    /// Returns true if the process exited normally with exit code 0.
+    pub fn success(&self) -> bool {
+        *self == ExitStatus::Exited(0)
+    }
+}
+
+/// Below this gap between lines of different streams, the order of `stdcombined` of
+/// [`OCatchStrategy::StdSeparately`] is likely wrong. See [`ProcessOutput::min_interline_gap`].
+pub const INTERLEAVING_SAFETY_GAP: Duration = Duration::from_micros(100);
+
+/// A line of [`ProcessOutput::timed_lines`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedLine {
+    /// The stream that the line was read from.
+    pub source: Source,
+    /// When the line was read.
+    pub timestamp: Instant,
+    /// The line without the trailing newline.
+    pub line: Rc<String>,
+}
+
+/// Raw output of a child process, shaped like `std::process::Output` but with the
+/// additional `combined` stream. See [`Command::output`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    /// How the child process terminated.
+    pub status: ExitStatus,
+    /// All bytes that the child wrote to STDOUT.
+    pub stdout: Vec<u8>,
+    /// All bytes that the child wrote to STDERR.
+    pub stderr: Vec<u8>,
+    /// All bytes of STDOUT and STDERR in the order they were read.
+    pub combined: Vec<u8>,
 }
 
 /// Determines the strategy that is used to get STDOUT, STDERR, and "STDCOMBINED".
@@ -97,13 +755,87 @@ pub enum OCatchStrategy {
     /// Catches all output lines from STDOUT and STDERR separately. There is also a
     /// "STDCOMBINED" vector, but the order is not 100% correct.  It's only approximately correct
     /// on a best effort base. If between each STDOUT/STDERR-alternating output is ≈100µs
-    /// (a few thousand cycles) it should be definitely fine, but there is no guarantee for that.
+    /// (a few thousand cycles, see [`INTERLEAVING_SAFETY_GAP`]) it should be definitely fine,
+    /// but there is no guarantee for that. [`ProcessOutput::min_interline_gap`] tells the
+    /// smallest gap that was observed.
     /// Also the incorrectness is not deterministic. This is because
     /// STDOUT and STDERR are two separate streams. Scheduling and buffering result in
     /// different results.
     StdSeparately,
+    /// Like [`OCatchStrategy::StdCombined`]: Catches all output lines of STDOUT and STDERR in
+    /// correct order. Because both streams share one pipe, the real source of a line is
+    /// unknown. Instead, the given classifier determines the [`Source`] of each line by its
+    /// content, e.g. by a prefix like `"error:"`. See [`ProcessOutput::stdcombined_tagged`].
+    /// This only works for output where the content tells the streams apart.
+    #[display(fmt = "CombinedPrefixed")]
+    CombinedPrefixed(fn(&str) -> Source),
+    /// Like [`OCatchStrategy::StdCombined`] but STDOUT and STDERR of the child are connected
+    /// to a pseudo terminal (PTY) instead of a pipe.
+    ///
+    /// Many programs check with `isatty()` whether they write to a terminal and behave
+    /// differently if not: They omit colors and progress bars, switch from line buffering
+    /// to block buffering (so output appears late or in a different order), or refuse to
+    /// run at all. Therefore, captured output often differs from an interactive run. With
+    /// this strategy, the child sees a terminal and behaves like in an interactive run.
+    /// The output may contain terminal escape sequences, see [`crate::Command::strip_ansi`].
+    /// STDIN is not affected.
+    ///
+    /// The child becomes the leader of a new session with the PTY as controlling terminal.
+    /// Output that a program writes directly to `/dev/tty`, e.g. password prompts, is
+    /// caught too. With the other strategies, such output bypasses the pipes and goes to
+    /// the terminal of the parent, or opening `/dev/tty` fails if there is none.
+    Pty,
+    /// Like [`OCatchStrategy::StdSeparately`]: Catches STDOUT and STDERR separately and
+    /// provides all three views: STDOUT, STDERR, and "STDCOMBINED". Instead of one thread per
+    /// stream, a single thread waits for both pipes with `poll()` and splits the data into
+    /// lines in the order it arrives. Without the scheduling of two threads in between, the
+    /// order of "STDCOMBINED" is much more accurate. It is still not guaranteed: If the child
+    /// writes to both streams before the reader wakes up, the data of both pipes is available
+    /// at the same time and the real order is lost; STDOUT is read first then. The same holds
+    /// if a line is written by multiple `write()`s.
+    Multiplexed,
 }
 
+impl OCatchStrategy {
+    /// Whether the order of "STDCOMBINED" is exact with this strategy.
+    pub fn ordering_guarantee(&self) -> OrderingGuarantee {
+        match self {
+            // both streams share one pipe or terminal
+            OCatchStrategy::StdCombined
+            | OCatchStrategy::CombinedPrefixed(_)
+            | OCatchStrategy::Pty => OrderingGuarantee::Exact,
+            OCatchStrategy::StdSeparately | OCatchStrategy::Multiplexed => {
+                OrderingGuarantee::BestEffort
+            }
+        }
+    }
+}
+
+/// Whether the order of the lines in "STDCOMBINED" can be trusted, see
+/// [`ProcessOutput::ordering_guarantee`].
+#[derive(Debug, Display, Copy, Clone, PartialEq, Eq)]
+pub enum OrderingGuarantee {
+    /// The lines are in the order in which the child wrote them.
+    Exact,
+    /// The order is reconstructed from two separate pipes and may differ from the order
+    /// in which the child wrote the lines.
+    BestEffort,
+}
+
+/// The output stream a line was written to by the child process.
+#[derive(Debug, Display, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Source {
+    /// STDOUT
+    Stdout,
+    /// STDERR
+    Stderr,
+}
+
+/// The bytes received by a single `read()` call together with their [`Source`].
+/// See [`Command::catch_read_groups`].
+pub type ReadGroup = (Option<Source>, Vec<u8>);
+
 #[cfg(test)]
 mod tests {
 
@@ -1,8 +1,9 @@
 //! libc utilities
 
-use crate::error::UECOError;
+use crate::error::{ForkFailureReason, UECOError};
 
 /// Syscall, that resulted in an error.
+#[derive(Debug, Copy, Clone)]
 pub enum LibcSyscall {
     Fork,
     Pipe,
@@ -10,15 +11,56 @@ pub enum LibcSyscall {
     Close,
     Read,
     Execvp,
+    Execve,
     Waitpid,
+    Kill,
+    Fcntl,
+    Setsid,
+    Sigaction,
+    Openpty,
+    Tcgetattr,
+    Tcsetattr,
+    Fstat,
+    Open,
+    Ioctl,
+    Wait4,
+    Chroot,
+    Chdir,
+    Waitid,
+    SetAffinity,
+    Unshare,
 }
 
+/// Return types of libc functions that signal an error with `-1`: `c_int` (and `pid_t`)
+/// as well as `ssize_t` of `read()`/`write()`. Passing the return value without a cast
+/// makes sure that large values, like byte counts above `i32::MAX`, aren't truncated.
+pub trait SyscallRet: Copy {
+    /// Whether the value signals an error.
+    fn is_error(self) -> bool;
+}
+
+impl SyscallRet for libc::c_int {
+    fn is_error(self) -> bool {
+        self == -1
+    }
+}
+
+impl SyscallRet for libc::ssize_t {
+    fn is_error(self) -> bool {
+        self == -1
+    }
+}
+
+// The errors of the child are encoded with a 4 byte errno and `pid_t` is passed around as
+// `c_int`. Fail to compile on platforms where this doesn't hold.
+const _: () = assert!(std::mem::size_of::<libc::c_int>() == std::mem::size_of::<i32>());
+const _: () = assert!(std::mem::size_of::<libc::pid_t>() == std::mem::size_of::<libc::c_int>());
+const _: () = assert!(std::mem::size_of::<libc::ssize_t>() == std::mem::size_of::<usize>());
+
 /// Convenient function that returns the return value of a libc function into
 /// an rust result.
-pub fn libc_ret_to_result(res: libc::c_int, syscall: LibcSyscall) -> Result<(), UECOError> {
-    if res == 0 {
-        Ok(())
-    } else if res == -1 {
+pub fn libc_ret_to_result<T: SyscallRet>(res: T, syscall: LibcSyscall) -> Result<(), UECOError> {
+    if res.is_error() {
         let errno = errno::errno().0;
         let err = syscall_to_uecoerror(syscall, errno);
         Err(err)
@@ -32,12 +74,214 @@ pub fn libc_ret_to_result(res: libc::c_int, syscall: LibcSyscall) -> Result<(),
 /// Translates the libc syscall to an error of this lib.
 fn syscall_to_uecoerror(syscall: LibcSyscall, errno: libc::c_int) -> UECOError {
     match syscall {
-        LibcSyscall::Fork => UECOError::ForkFailed { errno },
+        LibcSyscall::Fork => UECOError::ForkFailed {
+            errno,
+            reason: ForkFailureReason::from_errno(errno),
+        },
         LibcSyscall::Pipe => UECOError::PipeFailed { errno },
         LibcSyscall::Dup2 => UECOError::Dup2Failed { errno },
         LibcSyscall::Close => UECOError::CloseFailed { errno },
         LibcSyscall::Read => UECOError::ReadFailed { errno },
         LibcSyscall::Execvp => UECOError::ExecvpFailed { errno },
+        LibcSyscall::Execve => UECOError::ExecveFailed { errno },
         LibcSyscall::Waitpid => UECOError::WaitpidFailed { errno },
+        LibcSyscall::Kill => UECOError::KillFailed { errno },
+        LibcSyscall::Fcntl => UECOError::FcntlFailed { errno },
+        LibcSyscall::Setsid => UECOError::SetsidFailed { errno },
+        LibcSyscall::Sigaction => UECOError::SigactionFailed { errno },
+        LibcSyscall::Openpty => UECOError::OpenptyFailed { errno },
+        LibcSyscall::Tcgetattr => UECOError::TcgetattrFailed { errno },
+        LibcSyscall::Tcsetattr => UECOError::TcsetattrFailed { errno },
+        LibcSyscall::Fstat => UECOError::FstatFailed { errno },
+        LibcSyscall::Open => UECOError::OpenFailed { errno },
+        LibcSyscall::Ioctl => UECOError::IoctlFailed { errno },
+        LibcSyscall::Wait4 => UECOError::Wait4Failed { errno },
+        LibcSyscall::Chroot => UECOError::ChrootFailed { errno },
+        LibcSyscall::Chdir => UECOError::ChdirFailed { errno },
+        LibcSyscall::Waitid => UECOError::WaitidFailed { errno },
+        LibcSyscall::SetAffinity => UECOError::SetAffinityFailed { errno },
+        LibcSyscall::Unshare => UECOError::UnshareFailed { errno },
+    }
+}
+
+/// Returns the name of a signal, like `SIGSEGV` for 11, or `"unknown signal"`.
+/// Useful for messages like "child killed by SIGSEGV" instead of "signal 11".
+pub fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGCHLD => "SIGCHLD",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGSTOP => "SIGSTOP",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        libc::SIGURG => "SIGURG",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGVTALRM => "SIGVTALRM",
+        libc::SIGPROF => "SIGPROF",
+        libc::SIGWINCH => "SIGWINCH",
+        libc::SIGIO => "SIGIO",
+        libc::SIGSYS => "SIGSYS",
+        _ => "unknown signal",
+    }
+}
+
+/// Size of an encoded error, see [`encode_child_error`].
+pub const CHILD_ERROR_LEN: usize = 8;
+
+/// Encodes an error that happened in the child after fork() so that it can be sent to
+/// the parent through a pipe. Only failed syscalls can be encoded properly, all other
+/// errors become [`UECOError::Unknown`] in [`decode_child_error`].
+pub fn encode_child_error(err: &UECOError) -> [u8; CHILD_ERROR_LEN] {
+    let (syscall, errno) = match *err {
+        UECOError::ForkFailed { errno, .. } => (LibcSyscall::Fork, errno),
+        UECOError::PipeFailed { errno } => (LibcSyscall::Pipe, errno),
+        UECOError::Dup2Failed { errno } => (LibcSyscall::Dup2, errno),
+        UECOError::CloseFailed { errno } => (LibcSyscall::Close, errno),
+        UECOError::ReadFailed { errno } => (LibcSyscall::Read, errno),
+        UECOError::ExecvpFailed { errno } => (LibcSyscall::Execvp, errno),
+        UECOError::ExecveFailed { errno } => (LibcSyscall::Execve, errno),
+        UECOError::WaitpidFailed { errno } => (LibcSyscall::Waitpid, errno),
+        UECOError::KillFailed { errno } => (LibcSyscall::Kill, errno),
+        UECOError::FcntlFailed { errno } => (LibcSyscall::Fcntl, errno),
+        UECOError::SetsidFailed { errno } => (LibcSyscall::Setsid, errno),
+        UECOError::SigactionFailed { errno } => (LibcSyscall::Sigaction, errno),
+        UECOError::OpenptyFailed { errno } => (LibcSyscall::Openpty, errno),
+        UECOError::TcgetattrFailed { errno } => (LibcSyscall::Tcgetattr, errno),
+        UECOError::TcsetattrFailed { errno } => (LibcSyscall::Tcsetattr, errno),
+        UECOError::FstatFailed { errno } => (LibcSyscall::Fstat, errno),
+        UECOError::OpenFailed { errno } => (LibcSyscall::Open, errno),
+        UECOError::IoctlFailed { errno } => (LibcSyscall::Ioctl, errno),
+        UECOError::Wait4Failed { errno } => (LibcSyscall::Wait4, errno),
+        UECOError::ChrootFailed { errno } => (LibcSyscall::Chroot, errno),
+        UECOError::ChdirFailed { errno } => (LibcSyscall::Chdir, errno),
+        UECOError::WaitidFailed { errno } => (LibcSyscall::Waitid, errno),
+        UECOError::SetAffinityFailed { errno } => (LibcSyscall::SetAffinity, errno),
+        UECOError::UnshareFailed { errno } => (LibcSyscall::Unshare, errno),
+        UECOError::RedirectVerificationFailed { fd } => {
+            return encode_tagged(REDIRECT_VERIFICATION_FAILED_TAG, fd)
+        }
+        UECOError::PreExecFailed { errno } => return encode_tagged(PRE_EXEC_FAILED_TAG, errno),
+        _ => return [0; CHILD_ERROR_LEN],
+    };
+    // tag 0 is reserved for unknown errors
+    encode_tagged(syscall as u32 + 1, errno)
+}
+
+/// Encodes a tag and a value (errno or file descriptor) for [`encode_child_error`].
+fn encode_tagged(tag: u32, value: i32) -> [u8; CHILD_ERROR_LEN] {
+    let mut bytes = [0; CHILD_ERROR_LEN];
+    bytes[..4].copy_from_slice(&tag.to_ne_bytes());
+    bytes[4..].copy_from_slice(&value.to_ne_bytes());
+    bytes
+}
+
+/// Tag of [`UECOError::RedirectVerificationFailed`], which isn't caused by a syscall.
+/// The file descriptor takes the place of the errno.
+const REDIRECT_VERIFICATION_FAILED_TAG: u32 = u32::MAX;
+
+/// Tag of [`UECOError::PreExecFailed`], which isn't caused by a syscall of this crate.
+const PRE_EXEC_FAILED_TAG: u32 = u32::MAX - 1;
+
+/// Decodes an error that was encoded by [`encode_child_error`].
+pub fn decode_child_error(bytes: &[u8]) -> UECOError {
+    if bytes.len() != CHILD_ERROR_LEN {
+        return UECOError::Unknown;
+    }
+    let mut tag = [0; 4];
+    tag.copy_from_slice(&bytes[..4]);
+    let mut errno = [0; 4];
+    errno.copy_from_slice(&bytes[4..]);
+    let errno = i32::from_ne_bytes(errno);
+    let tag = u32::from_ne_bytes(tag);
+    match tag {
+        REDIRECT_VERIFICATION_FAILED_TAG => {
+            return UECOError::RedirectVerificationFailed { fd: errno }
+        }
+        PRE_EXEC_FAILED_TAG => return UECOError::PreExecFailed { errno },
+        _ => {}
+    }
+    let syscall = match tag.wrapping_sub(1) {
+        x if x == LibcSyscall::Fork as u32 => LibcSyscall::Fork,
+        x if x == LibcSyscall::Pipe as u32 => LibcSyscall::Pipe,
+        x if x == LibcSyscall::Dup2 as u32 => LibcSyscall::Dup2,
+        x if x == LibcSyscall::Close as u32 => LibcSyscall::Close,
+        x if x == LibcSyscall::Read as u32 => LibcSyscall::Read,
+        x if x == LibcSyscall::Execvp as u32 => LibcSyscall::Execvp,
+        x if x == LibcSyscall::Execve as u32 => LibcSyscall::Execve,
+        x if x == LibcSyscall::Waitpid as u32 => LibcSyscall::Waitpid,
+        x if x == LibcSyscall::Kill as u32 => LibcSyscall::Kill,
+        x if x == LibcSyscall::Fcntl as u32 => LibcSyscall::Fcntl,
+        x if x == LibcSyscall::Setsid as u32 => LibcSyscall::Setsid,
+        x if x == LibcSyscall::Sigaction as u32 => LibcSyscall::Sigaction,
+        x if x == LibcSyscall::Openpty as u32 => LibcSyscall::Openpty,
+        x if x == LibcSyscall::Tcgetattr as u32 => LibcSyscall::Tcgetattr,
+        x if x == LibcSyscall::Tcsetattr as u32 => LibcSyscall::Tcsetattr,
+        x if x == LibcSyscall::Fstat as u32 => LibcSyscall::Fstat,
+        x if x == LibcSyscall::Open as u32 => LibcSyscall::Open,
+        x if x == LibcSyscall::Ioctl as u32 => LibcSyscall::Ioctl,
+        x if x == LibcSyscall::Wait4 as u32 => LibcSyscall::Wait4,
+        x if x == LibcSyscall::Chroot as u32 => LibcSyscall::Chroot,
+        x if x == LibcSyscall::Chdir as u32 => LibcSyscall::Chdir,
+        x if x == LibcSyscall::Waitid as u32 => LibcSyscall::Waitid,
+        x if x == LibcSyscall::SetAffinity as u32 => LibcSyscall::SetAffinity,
+        x if x == LibcSyscall::Unshare as u32 => LibcSyscall::Unshare,
+        _ => return UECOError::Unknown,
+    };
+    syscall_to_uecoerror(syscall, errno)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_name() {
+        assert_eq!("SIGSEGV", signal_name(libc::SIGSEGV));
+        assert_eq!("SIGKILL", signal_name(9));
+        assert_eq!("unknown signal", signal_name(-1));
+    }
+
+    #[test]
+    fn test_encode_decode_child_error() {
+        let err = decode_child_error(&encode_child_error(&UECOError::ExecvpFailed { errno: 2 }));
+        assert!(matches!(err, UECOError::ExecvpFailed { errno: 2 }));
+        let err = decode_child_error(&encode_child_error(
+            &UECOError::RedirectVerificationFailed { fd: 2 },
+        ));
+        assert!(matches!(
+            err,
+            UECOError::RedirectVerificationFailed { fd: 2 }
+        ));
+        let err = decode_child_error(&encode_child_error(&UECOError::SetsidFailed { errno: 1 }));
+        assert!(matches!(err, UECOError::SetsidFailed { errno: 1 }));
+        let err = decode_child_error(&encode_child_error(&UECOError::ChildAlreadyDispatched));
+        assert!(matches!(err, UECOError::Unknown));
+    }
+
+    #[test]
+    fn test_libc_ret_to_result_ssize_t() {
+        // a byte count whose lower 32 bits are -1 as i32 must not be taken for an error
+        let ret = u32::MAX as libc::ssize_t;
+        if ret > 0 {
+            assert!(libc_ret_to_result(ret, LibcSyscall::Read).is_ok());
+        }
+        assert!(libc_ret_to_result(-1 as libc::ssize_t, LibcSyscall::Read).is_err());
+        assert!(libc_ret_to_result(0 as libc::c_int, LibcSyscall::Close).is_ok());
     }
 }
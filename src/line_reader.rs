@@ -0,0 +1,266 @@
+//! Lazy, blocking consumption of the output as [`Iterator`] of [`OutputLine`]s.
+
+use crate::child::ChildProcess;
+use crate::error::UECOError;
+use crate::exec::join_stdin_writer;
+use crate::reader::{
+    ChunkPoller, LineSplitter, LineVisitor, ReadEnd, ReaderOptions, MULTIPLEXED_READ_BUF_LEN,
+};
+use crate::{ExitStatus, OCatchStrategy, Source};
+use std::collections::VecDeque;
+use std::task::Poll;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// An item of a [`LineReader`] or an [`crate::OutputStream`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputLine {
+    /// A line of output without the trailing newline.
+    Line {
+        /// Global sequence number of the line, i.e. the position in the output of all streams.
+        seq: u64,
+        text: String,
+        /// The stream the line was written to. Always known for
+        /// [`OCatchStrategy::StdSeparately`], determined by the classifier for
+        /// [`OCatchStrategy::CombinedPrefixed`], and `None` for [`OCatchStrategy::StdCombined`].
+        source: Option<Source>,
+        /// When the line was read.
+        timestamp: Instant,
+    },
+    /// The last item. All output was read and the child exited.
+    Eof { exit_status: ExitStatus },
+}
+
+/// Lines that were read but not yet returned by the [`LineReader`].
+#[derive(Debug, Default)]
+struct PendingLines(VecDeque<(Option<Source>, String, Instant)>);
+
+impl LineVisitor for PendingLines {
+    fn visit(&mut self, source: Option<Source>, line: &str, instant: Instant) {
+        self.0.push_back((source, line.to_string(), instant));
+    }
+}
+
+/// Output of a child process as [`Iterator`] of [`OutputLine`]s, see
+/// [`crate::Command::lines_iter`]. Nothing is read in advance: each call to `next()`
+/// blocks only until the next line is available. After all lines, [`OutputLine::Eof`]
+/// carries the exit status. If the reader gets dropped early, the child gets killed.
+pub struct LineReader {
+    /// `None` once it was reaped.
+    child: Option<ChildProcess>,
+    stdin_writer: Option<JoinHandle<Result<(), UECOError>>>,
+    poller: ChunkPoller,
+    splitter: LineSplitter<PendingLines>,
+    options: ReaderOptions,
+    /// Sequence number of the next line.
+    next_seq: u64,
+    /// Why reading ended, once it did.
+    end: Option<ReadEnd>,
+    finished: bool,
+}
+
+impl LineReader {
+    /// Constructor. The timeout of the [`ReaderOptions`] starts now.
+    pub(crate) fn new(
+        child: ChildProcess,
+        stdin_writer: Option<JoinHandle<Result<(), UECOError>>>,
+        strategy: OCatchStrategy,
+        options: ReaderOptions,
+    ) -> Self {
+        let mut poller = ChunkPoller::new(&child, strategy, MULTIPLEXED_READ_BUF_LEN);
+        poller.start_timeout(options.timeout);
+        Self {
+            child: Some(child),
+            stdin_writer,
+            poller,
+            splitter: LineSplitter::new(strategy, PendingLines::default()),
+            options,
+            next_seq: 0,
+            end: None,
+            finished: false,
+        }
+    }
+
+    /// Returns a [`ThrottledLines`] that coalesces the lines into batches, at most one
+    /// batch per `interval`. This keeps real-time displays responsive with bursty output.
+    pub fn throttle(self, interval: Duration) -> ThrottledLines {
+        ThrottledLines {
+            lines: self,
+            interval,
+            last_batch: None,
+            batch: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Reads one round, waiting at most until `until`.
+    fn read_more(&mut self, until: Option<Instant>) -> Result<(), UECOError> {
+        let splitter = &mut self.splitter;
+        let options = &self.options;
+        let end = self.poller.poll_once_until(
+            &mut |source, chunk: Vec<u8>| splitter.push_chunk(options, source, &chunk, false),
+            until,
+        )?;
+        if let Some(end) = end {
+            self.splitter.finish(&self.options, end)?;
+            self.end.replace(end);
+        }
+        Ok(())
+    }
+
+    /// Reaps the child once reading ended. Kills it first unless all output was read.
+    fn wait(&mut self) -> Result<ExitStatus, UECOError> {
+        let mut child = self.child.take().expect("child is reaped only once");
+        if self.end != Some(ReadEnd::Eof) {
+            child.kill()?;
+        }
+        let exit_status = child.wait_bl()?;
+        join_stdin_writer(self.stdin_writer.take())?;
+        match (self.end, self.options.timeout) {
+            (Some(ReadEnd::TimedOut), Some(timeout)) => Err(UECOError::TimedOut { timeout }),
+            _ => Ok(exit_status),
+        }
+    }
+
+    /// Kills the child after an error.
+    fn fail(&mut self, err: UECOError) -> Option<Result<OutputLine, UECOError>> {
+        self.finished = true;
+        if let Some(child) = self.child.as_mut() {
+            if let Err(kill_err) = child.kill() {
+                return Some(Err(kill_err));
+            }
+        }
+        Some(Err(err))
+    }
+}
+
+impl LineReader {
+    /// Like `next()` but returns [`Poll::Pending`] if no line is available until `until`.
+    fn next_until(
+        &mut self,
+        until: Option<Instant>,
+    ) -> Poll<Option<Result<OutputLine, UECOError>>> {
+        // read at least once, so that data that is ready gets returned even if `until` passed
+        let mut polled = false;
+        loop {
+            if let Some((source, text, timestamp)) = self.splitter.visitor.0.pop_front() {
+                let seq = self.next_seq;
+                self.next_seq += 1;
+                return Poll::Ready(Some(Ok(OutputLine::Line {
+                    seq,
+                    text,
+                    source,
+                    timestamp,
+                })));
+            }
+            if self.finished {
+                return Poll::Ready(None);
+            }
+            if self.end.is_some() {
+                self.finished = true;
+                return Poll::Ready(Some(
+                    self.wait()
+                        .map(|exit_status| OutputLine::Eof { exit_status }),
+                ));
+            }
+            if let Some(until) = until {
+                if polled && Instant::now() >= until {
+                    return Poll::Pending;
+                }
+            }
+            if let Err(err) = self.read_more(until) {
+                return Poll::Ready(self.fail(err));
+            }
+            polled = true;
+        }
+    }
+}
+
+impl Iterator for LineReader {
+    type Item = Result<OutputLine, UECOError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_until(None) {
+            Poll::Ready(item) => item,
+            Poll::Pending => unreachable!("waits without limit"),
+        }
+    }
+}
+
+impl Drop for LineReader {
+    /// Kills the child if the reader wasn't consumed completely.
+    fn drop(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            // errors can't be handled here in a meaningful way
+            let _ = child.kill();
+        }
+    }
+}
+
+/// Lines of a [`LineReader`] in batches, see [`LineReader::throttle`]. Each batch holds all
+/// lines that arrived since the previous batch; a batch is returned as soon as there is at
+/// least one line, but at most once per interval. The last batch ends with
+/// [`OutputLine::Eof`] and is returned right away. After an error, the lines before it are
+/// returned first.
+pub struct ThrottledLines {
+    lines: LineReader,
+    interval: Duration,
+    /// When the previous batch was returned.
+    last_batch: Option<Instant>,
+    batch: Vec<OutputLine>,
+    /// Error that is returned after the pending batch.
+    error: Option<UECOError>,
+}
+
+impl ThrottledLines {
+    /// Takes the current batch and starts the next interval.
+    fn take_batch(&mut self) -> Vec<OutputLine> {
+        self.last_batch.replace(Instant::now());
+        std::mem::take(&mut self.batch)
+    }
+}
+
+impl Iterator for ThrottledLines {
+    type Item = Result<Vec<OutputLine>, UECOError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+        loop {
+            // wait without limit for the first line of a batch
+            let until = if self.batch.is_empty() {
+                None
+            } else {
+                Some(
+                    self.last_batch
+                        .map(|last| last + self.interval)
+                        .unwrap_or_else(Instant::now),
+                )
+            };
+            match self.lines.next_until(until) {
+                Poll::Ready(Some(Ok(line))) => {
+                    let eof = matches!(line, OutputLine::Eof { .. });
+                    self.batch.push(line);
+                    if eof {
+                        return Some(Ok(self.take_batch()));
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    if self.batch.is_empty() {
+                        return Some(Err(err));
+                    }
+                    self.error.replace(err);
+                    return Some(Ok(self.take_batch()));
+                }
+                Poll::Ready(None) => {
+                    if self.batch.is_empty() {
+                        return None;
+                    }
+                    return Some(Ok(self.take_batch()));
+                }
+                Poll::Pending => return Some(Ok(self.take_batch())),
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Memory efficient storage of the captured output lines.
+
+use std::borrow::Cow;
+use std::fmt::{Debug, Formatter};
+use std::ops::Range;
+
+/// Stores all lines of one stream in a single contiguous buffer. Each line is
+/// described by its range inside the buffer. Compared to one heap allocation
+/// per line, this saves a lot of allocations and memory for large outputs.
+/// The lines are accessed as `&str` slices into the buffer.
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Lines {
+    /// All lines concatenated without any separator.
+    buf: String,
+    /// The range of each line inside `buf`.
+    ranges: Vec<Range<usize>>,
+}
+
+impl Lines {
+    /// Constructor.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a line. The line must not contain the trailing newline.
+    pub(crate) fn push(&mut self, line: &str) {
+        let start = self.buf.len();
+        self.buf.push_str(line);
+        self.ranges.push(start..self.buf.len());
+    }
+
+    /// Returns the line at the given index or `None` if it's out of range.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.ranges.get(index).map(|range| &self.buf[range.clone()])
+    }
+
+    /// Returns the number of lines.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Returns true if there are no lines.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns an iterator over all lines.
+    pub fn iter(&self) -> LinesIter<'_> {
+        LinesIter {
+            lines: self,
+            index: 0,
+        }
+    }
+
+    /// Returns all lines as [`Cow`]s that borrow from the buffer, i.e. without allocating
+    /// per line. Invalid UTF-8 was already replaced with `U+FFFD` while reading, so
+    /// every line is [`Cow::Borrowed`]. This is useful for APIs that take `Cow<str>` and
+    /// only allocate if they modify a line.
+    pub fn lines_cow(&self) -> impl ExactSizeIterator<Item = Cow<'_, str>> + '_ {
+        self.iter().map(Cow::Borrowed)
+    }
+
+    /// Returns all lines as owned `String`s.
+    pub fn into_strings(self) -> Vec<String> {
+        self.iter().map(str::to_string).collect()
+    }
+}
+
+impl Debug for Lines {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a Lines {
+    type Item = &'a str;
+    type IntoIter = LinesIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the lines of [`Lines`] as `&str`.
+#[derive(Debug, Clone)]
+pub struct LinesIter<'a> {
+    lines: &'a Lines,
+    index: usize,
+}
+
+impl<'a> Iterator for LinesIter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.get(self.index)?;
+        self.index += 1;
+        Some(line)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.lines.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for LinesIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let mut lines = Lines::new();
+        assert!(lines.is_empty());
+        lines.push("foo");
+        lines.push("");
+        lines.push("bär");
+        assert_eq!(3, lines.len());
+        assert_eq!(Some("bär"), lines.get(2));
+        assert_eq!(None, lines.get(3));
+        assert_eq!(vec!["foo", "", "bär"], lines.iter().collect::<Vec<&str>>());
+        assert!(lines
+            .lines_cow()
+            .all(|line| matches!(line, Cow::Borrowed(_))));
+    }
+}
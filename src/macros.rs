@@ -0,0 +1,43 @@
+//! Macros for the common case.
+
+/// Executes a program like [`crate::Command::catch`] and returns its
+/// [`crate::ProcessOutput`]. args\[0\] is set to the executable automatically, so only the
+/// real args are listed. Options follow the args in the form `key = value`:
+/// * `args = [...]` further args, e.g. from variables
+/// * `strategy = Variant` a variant of [`crate::OCatchStrategy`] without fields
+/// * `env = [(key, value), ...]` see [`crate::Command::env`]
+///
+/// A typo in an option name is a compile error. Use [`crate::Command`] for everything else.
+///
+/// ```no_run
+/// use unix_exec_output_catcher::catch;
+///
+/// let res = catch!("ls", "-la", "-h").unwrap();
+/// let res = catch!("ls", args = ["-la"], strategy = StdCombined).unwrap();
+/// let res = catch!("sh", "-c", "echo $FOO", env = [("FOO", "bar")]).unwrap();
+/// ```
+#[macro_export]
+macro_rules! catch {
+    (@opts $command:ident;) => {};
+    (@opts $command:ident; args = [$($arg:expr),* $(,)?] $(, $($rest:tt)*)?) => {
+        $( $command.args([$arg]); )*
+        $crate::catch!(@opts $command; $($($rest)*)?);
+    };
+    (@opts $command:ident; strategy = $strategy:ident $(, $($rest:tt)*)?) => {
+        $command.strategy($crate::OCatchStrategy::$strategy);
+        $crate::catch!(@opts $command; $($($rest)*)?);
+    };
+    (@opts $command:ident; env = [$(($key:expr, $value:expr)),* $(,)?] $(, $($rest:tt)*)?) => {
+        $( $command.env($key, $value); )*
+        $crate::catch!(@opts $command; $($($rest)*)?);
+    };
+    (@opts $command:ident; $arg:expr $(, $($rest:tt)*)?) => {
+        $command.args([$arg]);
+        $crate::catch!(@opts $command; $($($rest)*)?);
+    };
+    ($executable:expr $(, $($rest:tt)*)?) => {{
+        let mut command = $crate::Command::new($executable);
+        $crate::catch!(@opts command; $($($rest)*)?);
+        command.catch()
+    }};
+}
@@ -0,0 +1,84 @@
+//! Sampling of the memory usage of the child process via `/proc`. Linux only.
+
+use crate::error::UECOError;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Background thread that periodically reads `/proc/<pid>/status` while the child
+/// runs and remembers the peak resident set size (RSS).
+#[derive(Debug)]
+pub struct MemorySampler {
+    /// Tells the thread to stop sampling.
+    stop: Arc<AtomicBool>,
+    /// Returns the peak RSS in KiB or `None` if no sample could be taken.
+    handle: JoinHandle<Option<u64>>,
+}
+
+impl MemorySampler {
+    /// Starts sampling the process with the given pid every `interval`.
+    pub fn start(pid: libc::pid_t, interval: Duration) -> Result<Self, UECOError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = thread::Builder::new()
+            .name("ueco-memory-sampler".to_string())
+            .spawn(move || {
+                let path = format!("/proc/{}/status", pid);
+                let mut peak = None;
+                loop {
+                    // the file disappears or has no memory info once the child is a zombie
+                    if let Some(kib) = fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|status| parse_peak_rss_kib(&status))
+                    {
+                        peak = peak.max(Some(kib));
+                    }
+                    if stop_thread.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    thread::park_timeout(interval);
+                }
+                peak
+            })
+            .map_err(|err| UECOError::ThreadSpawnFailed {
+                errno: err.raw_os_error().unwrap_or(0),
+            })?;
+        Ok(Self { stop, handle })
+    }
+
+    /// Stops sampling and returns the peak RSS in KiB. `None` if the child exited
+    /// before the first sample could be taken.
+    pub fn stop(self) -> Option<u64> {
+        self.stop.store(true, Ordering::SeqCst);
+        self.handle.thread().unpark();
+        // the thread doesn't panic; if it does anyway, there is just no sample
+        self.handle.join().unwrap_or(None)
+    }
+}
+
+/// Parses the content of `/proc/<pid>/status` and returns the maximum of `VmHWM`
+/// (peak RSS so far) and `VmRSS` (current RSS) in KiB.
+fn parse_peak_rss_kib(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .filter(|line| line.starts_with("VmHWM:") || line.starts_with("VmRSS:"))
+        // format: "VmHWM:\t    1234 kB"
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .filter_map(|kib| kib.parse::<u64>().ok())
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_peak_rss_kib() {
+        let status = "Name:\tcat\nVmPeak:\t    8000 kB\nVmHWM:\t    1024 kB\nVmRSS:\t     980 kB\n";
+        assert_eq!(Some(1024), parse_peak_rss_kib(status));
+        assert_eq!(None, parse_peak_rss_kib("Name:\tcat\nState:\tZ (zombie)\n"));
+    }
+}
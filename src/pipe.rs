@@ -3,7 +3,7 @@
 use crate::error::UECOError;
 use crate::libc_util::{libc_ret_to_result, LibcSyscall};
 use crate::OCatchStrategy;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 /// Convenient wrapper around the pipes that we
 /// need for the desired output catch strategy.
@@ -16,15 +16,38 @@ pub enum CatchPipes {
 impl CatchPipes {
     pub fn new(strategy: OCatchStrategy) -> Result<Self, UECOError> {
         match strategy {
-            OCatchStrategy::StdCombined => Ok(CatchPipes::Combined(Pipe::new()?)),
-            OCatchStrategy::StdSeparately => Ok(CatchPipes::Separately {
-                stdout: Pipe::new()?,
-                stderr: Pipe::new()?,
-            }),
+            OCatchStrategy::StdCombined | OCatchStrategy::CombinedPrefixed(_) => {
+                Ok(CatchPipes::Combined(Pipe::new_cloexec()?))
+            }
+            OCatchStrategy::Pty => Ok(CatchPipes::Combined(Pipe::new_pty()?)),
+            OCatchStrategy::StdSeparately | OCatchStrategy::Multiplexed => {
+                Ok(CatchPipes::Separately {
+                    stdout: Pipe::new_cloexec()?,
+                    stderr: Pipe::new_cloexec()?,
+                })
+            }
+        }
+    }
+
+    /// Sets the capacity of all pipes, see [`Pipe::set_capacity`].
+    pub fn set_capacity(&self, capacity: usize) -> Result<(), UECOError> {
+        match self {
+            CatchPipes::Combined(pipe) => pipe.set_capacity(capacity),
+            CatchPipes::Separately { stdout, stderr } => {
+                stdout.set_capacity(capacity)?;
+                stderr.set_capacity(capacity)
+            }
         }
     }
 }
 
+/// Size of a single `read()` of [`Pipe::read_line`]. Same as the default capacity of a
+/// pipe on Linux, so a single `read()` usually gets everything that is inside the pipe.
+const LINE_READ_BUF_LEN: usize = 65536;
+
+/// Marks a file descriptor of a [`Pipe`] as closed.
+const CLOSED_FD: libc::c_int = -1;
+
 /// The index inside the [i32;2]-array that is filled by `pipe()`.
 #[derive(Debug, PartialEq)]
 pub enum PipeEnd {
@@ -32,6 +55,17 @@ pub enum PipeEnd {
     Write = 1,
 }
 
+/// Result of [`Pipe::read_line`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ReadLine {
+    /// A line without the trailing newline and when it was read.
+    Line(Instant, String),
+    /// The pipe reached EOF.
+    Eof,
+    /// No data arrived within the read timeout.
+    TimedOut,
+}
+
 /// Abstraction over pipe.
 #[derive(Debug)]
 pub struct Pipe {
@@ -40,8 +74,17 @@ pub struct Pipe {
     /// the process must be forked and after that, in each address space
     /// the pipe is marked es the right end.
     end: Option<PipeEnd>,
+    /// [`CLOSED_FD`] once closed.
     read_fd: libc::c_int,
+    /// [`CLOSED_FD`] once closed.
     write_fd: libc::c_int,
+    /// The "pipe" is a pseudo terminal, see [`Pipe::new_pty`].
+    pty: bool,
+    /// The last byte that [`Pipe::read_line`] read was a newline.
+    after_newline: bool,
+    /// Bytes that [`Pipe::read_line`] read but didn't return yet, starting at `line_pos`.
+    line_buf: Vec<u8>,
+    line_pos: usize,
 }
 
 impl Pipe {
@@ -57,28 +100,181 @@ impl Pipe {
             end: None,
             read_fd: fds[PipeEnd::Read as usize],
             write_fd: fds[PipeEnd::Write as usize],
+            pty: false,
+            after_newline: false,
+            line_buf: Vec::new(),
+            line_pos: 0,
         };
 
         Ok(pipe)
     }
 
+    /// Creates a pseudo terminal (PTY) that is used like a pipe: The slave is the write
+    /// end and the master is the read end. For the child, the write end is a terminal,
+    /// i.e. `isatty()` returns true. The translation of `\n` to `\r\n` is turned off,
+    /// so that the output is the same as with a pipe.
+    pub(crate) fn new_pty() -> Result<Self, UECOError> {
+        let mut master: libc::c_int = 0;
+        let mut slave: libc::c_int = 0;
+        let ret = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        libc_ret_to_result(ret, LibcSyscall::Openpty)?;
+        // from here on, the file descriptors get closed on drop
+        let pipe = Self {
+            end: None,
+            read_fd: master,
+            write_fd: slave,
+            pty: true,
+            after_newline: false,
+            line_buf: Vec::new(),
+            line_pos: 0,
+        };
+
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::tcgetattr(slave, &mut termios) };
+        libc_ret_to_result(ret, LibcSyscall::Tcgetattr)?;
+        termios.c_oflag &= !libc::ONLCR;
+        let ret = unsafe { libc::tcsetattr(slave, libc::TCSANOW, &termios) };
+        libc_ret_to_result(ret, LibcSyscall::Tcsetattr)?;
+        pipe.set_cloexec()?;
+
+        trace!("pty created successfully");
+        Ok(pipe)
+    }
+
+    /// Like [`Pipe::new`] but both file descriptors get the `FD_CLOEXEC` flag.
+    /// They get closed automatically when a child calls exec(). This way, children that
+    /// are forked concurrently for other commands don't inherit them, which would delay
+    /// EOF until they exit. The child of the pipe connects them to STDOUT/STDERR/STDIN via
+    /// dup2(), which clears the flag for the new file descriptor.
+    pub(crate) fn new_cloexec() -> Result<Self, UECOError> {
+        let pipe = Self::new()?;
+        pipe.set_cloexec()?;
+        Ok(pipe)
+    }
+
+    /// Sets the `FD_CLOEXEC` flag for both file descriptors.
+    fn set_cloexec(&self) -> Result<(), UECOError> {
+        for fd in [self.read_fd, self.write_fd].iter() {
+            let ret = unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+            libc_ret_to_result(ret, LibcSyscall::Fcntl)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the capacity of the pipe via `fcntl(F_SETPIPE_SZ)`. The kernel rounds it up
+    /// to a power of two number of pages. Only supported on Linux, does nothing elsewhere.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set_capacity(&self, capacity: usize) -> Result<(), UECOError> {
+        if self.pty {
+            trace!("the capacity of a pty can't be set");
+            return Ok(());
+        }
+        let capacity = capacity.min(libc::c_int::MAX as usize) as libc::c_int;
+        let ret = unsafe { libc::fcntl(self.write_fd, libc::F_SETPIPE_SZ, capacity) };
+        libc_ret_to_result(ret, LibcSyscall::Fcntl)?;
+        trace!("pipe capacity set to {} bytes", ret);
+        Ok(())
+    }
+
+    /// Sets the capacity of the pipe via `fcntl(F_SETPIPE_SZ)`. The kernel rounds it up
+    /// to a power of two number of pages. Only supported on Linux, does nothing elsewhere.
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn set_capacity(&self, _capacity: usize) -> Result<(), UECOError> {
+        trace!("setting the pipe capacity is not supported on this platform");
+        Ok(())
+    }
+
     pub(crate) fn mark_as_parent_process(&mut self) -> Result<(), UECOError> {
         trace!("pipe marked as read end");
         self.end.replace(PipeEnd::Read);
-        self.close_fd(self.write_fd)
+        self.close_end(PipeEnd::Write)
     }
 
     pub(crate) fn mark_as_child_process(&mut self) -> Result<(), UECOError> {
         trace!("pipe marked as write end");
         self.end.replace(PipeEnd::Write);
-        self.close_fd(self.read_fd)
+        self.close_end(PipeEnd::Read)
+    }
+
+    /// Like [`Pipe::mark_as_child_process`] but for a pipe that is connected to STDIN
+    /// of the child: The child reads, the parent writes.
+    pub(crate) fn mark_as_child_process_stdin(&mut self) -> Result<(), UECOError> {
+        trace!("stdin pipe marked as read end");
+        self.end.replace(PipeEnd::Read);
+        self.close_end(PipeEnd::Write)
+    }
+
+    /// Like [`Pipe::mark_as_parent_process`] but for a pipe that is connected to STDIN
+    /// of the child: The child reads, the parent writes.
+    pub(crate) fn mark_as_parent_process_stdin(&mut self) -> Result<(), UECOError> {
+        trace!("stdin pipe marked as write end");
+        self.end.replace(PipeEnd::Write);
+        self.close_end(PipeEnd::Read)
+    }
+
+    /// Writes all bytes into the write end of the pipe. If the reader closed the pipe
+    /// already (`EPIPE`), the remaining bytes are discarded. This happens if the
+    /// child exits without reading all input.
+    pub(crate) fn write_all(&self, bytes: &[u8]) -> Result<(), UECOError> {
+        if self.end != Some(PipeEnd::Write) {
+            return Err(UECOError::PipeNotMarkedAsWriteEnd);
+        }
+
+        let mut written = 0;
+        while written < bytes.len() {
+            let remaining = &bytes[written..];
+            let ret = unsafe {
+                libc::write(
+                    self.write_fd,
+                    remaining.as_ptr() as *const libc::c_void,
+                    remaining.len(),
+                )
+            };
+            if ret == -1 {
+                match errno::errno().0 {
+                    libc::EINTR => continue,
+                    libc::EPIPE => {
+                        trace!("reader of the pipe is gone; discarding remaining input");
+                        return Ok(());
+                    }
+                    errno => return Err(UECOError::WriteFailed { errno }),
+                }
+            }
+            // not negative here, so the cast is lossless
+            written += ret as usize;
+        }
+        Ok(())
+    }
+
+    /// Closes the write end of the pipe. The reader reads EOF afterwards.
+    pub(crate) fn close_write_end(&mut self) -> Result<(), UECOError> {
+        self.close_end(PipeEnd::Write)
     }
 
     /// Try to read the next line from the read end of the pipe.
-    /// Returns ERR if a syscall failed. Returns OK(None) if
-    /// EOF was reached. Returns (Ok(Some(String)) if a new line
-    /// was read.
-    pub(crate) fn read_line(&self) -> Result<Option<(Instant, String)>, UECOError> {
+    /// Returns ERR if a syscall failed or if the line is longer than
+    /// `max_line_bytes`. Returns [`ReadLine::Eof`] if EOF was reached.
+    /// Returns [`ReadLine::Line`] if a new line was read. The last line is returned
+    /// even if it doesn't end with a newline.
+    /// * `keep_trailing_empty_line` if the output ends with a newline, an empty line is
+    ///   returned before EOF, see [`crate::Command::keep_trailing_empty_line`]
+    /// * `read_timeout` the longest time a single `read()` may wait for data; returns
+    ///   [`ReadLine::TimedOut`] then. The bytes of an incomplete line are kept for the next
+    ///   call. See [`crate::Command::read_timeout`].
+    pub(crate) fn read_line(
+        &mut self,
+        max_line_bytes: Option<usize>,
+        keep_trailing_empty_line: bool,
+        read_timeout: Option<Duration>,
+    ) -> Result<ReadLine, UECOError> {
         if *self
             .end
             .as_ref()
@@ -88,25 +284,149 @@ impl Pipe {
             return Err(UECOError::PipeNotMarkedAsReadEnd);
         }
 
-        let mut chars = Vec::new();
+        // bytes of the current line that were already searched for a newline
+        let mut scanned = 0;
+        loop {
+            let pending = &self.line_buf[self.line_pos..];
+            if let Some(pos) = memchr::memchr(b'\n', &pending[scanned..]) {
+                let pos = scanned + pos;
+                if let Some(limit) = max_line_bytes {
+                    if pos > limit {
+                        return Err(UECOError::LineTooLong { limit });
+                    }
+                }
+                trace!("newline (\\n) found");
+                // decode the whole line; multi-byte UTF-8 sequences may span several reads
+                let string = String::from_utf8_lossy(&pending[..pos]).into_owned();
+                self.line_pos += pos + 1;
+                self.after_newline = true;
+                return Ok(ReadLine::Line(Instant::now(), string));
+            }
+            scanned = pending.len();
+            if let Some(limit) = max_line_bytes {
+                if scanned > limit {
+                    return Err(UECOError::LineTooLong { limit });
+                }
+            }
+            if let Some(read_timeout) = read_timeout {
+                if !self.wait_readable(read_timeout)? {
+                    return Ok(ReadLine::TimedOut);
+                }
+            }
+            if !self.fill_line_buf()? {
+                // EOF
+                let after_newline = std::mem::replace(&mut self.after_newline, false);
+                let pending = &self.line_buf[self.line_pos..];
+                if pending.is_empty() && !(after_newline && keep_trailing_empty_line) {
+                    return Ok(ReadLine::Eof);
+                }
+                trace!("last line without newline");
+                let string = String::from_utf8_lossy(pending).into_owned();
+                self.line_buf.clear();
+                self.line_pos = 0;
+                return Ok(ReadLine::Line(Instant::now(), string));
+            }
+        }
+    }
+
+    /// Waits with `poll()` up to `timeout` until the read end is readable, i.e. data or EOF
+    /// is available. Returns `false` if it isn't, also if a signal interrupted the wait.
+    fn wait_readable(&self, timeout: Duration) -> Result<bool, UECOError> {
+        let mut poll_fd = libc::pollfd {
+            fd: self.read_fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // round up; a timeout of less than 1ms must not become a busy loop
+        let millis = timeout.as_micros().div_ceil(1000);
+        let millis = millis.min(libc::c_int::MAX as u128) as libc::c_int;
+        let ret = unsafe { libc::poll(&mut poll_fd, 1, millis) };
+        if ret == -1 {
+            return match errno::errno().0 {
+                libc::EINTR => Ok(false),
+                errno => Err(UECOError::PollFailed { errno }),
+            };
+        }
+        Ok(ret > 0)
+    }
+
+    /// Reads the next chunk into the buffer of [`Pipe::read_line`]. The bytes that
+    /// were consumed already are dropped first. Returns `false` at EOF.
+    fn fill_line_buf(&mut self) -> Result<bool, UECOError> {
+        self.line_buf.drain(..self.line_pos);
+        self.line_pos = 0;
+        let len = self.line_buf.len();
+        self.line_buf.resize(len + LINE_READ_BUF_LEN, 0);
+        let ret = loop {
+            let ret = unsafe {
+                libc::read(
+                    self.read_fd,
+                    self.line_buf[len..].as_mut_ptr() as *mut libc::c_void,
+                    LINE_READ_BUF_LEN,
+                )
+            };
+            if ret == -1 {
+                match errno::errno().0 {
+                    libc::EINTR => continue,
+                    errno if self.is_pty_eof(errno) => break 0,
+                    errno => {
+                        self.line_buf.truncate(len);
+                        return Err(UECOError::ReadFailed { errno });
+                    }
+                }
+            }
+            // not negative here, so the cast is lossless
+            break ret as usize;
+        };
+        self.line_buf.truncate(len + ret);
+        Ok(ret > 0)
+    }
+
+    /// Reads the next chunk of up to `max_len` bytes from the read end of the pipe.
+    /// Blocks until data is available. Returns Ok(None) if EOF was reached.
+    pub(crate) fn read_chunk(&self, max_len: usize) -> Result<Option<Vec<u8>>, UECOError> {
+        if self.end != Some(PipeEnd::Read) {
+            return Err(UECOError::PipeNotMarkedAsReadEnd);
+        }
 
-        let instant;
+        let mut buf = vec![0_u8; max_len];
         loop {
-            // read from file descriptor byte by byte (each iteration results in a syscall)
-            let char = self.read_char()?;
-            if char.is_none() {
-                return Ok(None); // EOF
+            let ret = unsafe {
+                libc::read(
+                    self.read_fd,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if ret == -1 {
+                match errno::errno().0 {
+                    libc::EINTR => continue,
+                    errno if self.is_pty_eof(errno) => return Ok(None),
+                    errno => return Err(UECOError::ReadFailed { errno }),
+                }
             }
-            let char = char.unwrap();
-            if char == '\n' {
-                instant = Instant::now();
-                trace!("newline (\\n) found");
-                break;
+            // EOF
+            if ret == 0 {
+                return Ok(None);
             }
-            chars.push(char);
+            buf.truncate(ret as usize);
+            return Ok(Some(buf));
         }
-        let string = chars.into_iter().collect::<String>();
-        Ok(Some((instant, string)))
+    }
+
+    /// Sets `O_NONBLOCK` for the read end. Reading returns [`UECOError::ReadFailed`]
+    /// with `EAGAIN` afterwards if no data is available.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn set_read_end_nonblocking(&self) -> Result<(), UECOError> {
+        let flags = unsafe { libc::fcntl(self.read_fd, libc::F_GETFL) };
+        libc_ret_to_result(flags, LibcSyscall::Fcntl)?;
+        let ret = unsafe { libc::fcntl(self.read_fd, libc::F_SETFL, flags | libc::O_NONBLOCK) };
+        libc_ret_to_result(ret, LibcSyscall::Fcntl)
+    }
+
+    /// Getter for the file descriptor of the read end.
+    pub(crate) fn read_fd(&self) -> libc::c_int {
+        self.read_fd
     }
 
     /// Connects stdout of the process to the write end of the pipe.
@@ -125,28 +445,209 @@ impl Pipe {
         libc_ret_to_result(res, LibcSyscall::Dup2)
     }
 
-    /// Reads a single char from the read end of the pipe (Some(char)) or EOF (None).
-    fn read_char(&self) -> Result<Option<char>, UECOError> {
-        const BUF_LEN: usize = 1; // Todo this is not efficient
-        let mut buf: [char; BUF_LEN] = ['\0'];
-        let buf_ptr = buf.as_mut_ptr() as *mut libc::c_void;
-        let ret = unsafe { libc::read(self.read_fd, buf_ptr, BUF_LEN) };
+    /// Connects stdin of the process to the read end of the pipe.
+    /// You probably only want to do this in the child process.
+    pub(crate) fn connect_to_stdin(&self) -> Result<(), UECOError> {
+        let res = unsafe { libc::dup2(self.read_fd, libc::STDIN_FILENO) };
+        // unwrap error, if res == -1
+        libc_ret_to_result(res, LibcSyscall::Dup2)
+    }
 
-        // check error and unwrap
-        libc_ret_to_result(ret as i32, LibcSyscall::Read)?;
+    /// Whether this is a PTY, see [`Pipe::new_pty`].
+    pub(crate) fn is_pty(&self) -> bool {
+        self.pty
+    }
+
+    /// Makes the PTY the controlling terminal of the calling process, so that `/dev/tty`
+    /// refers to it. The process must be a session leader without a controlling terminal,
+    /// i.e. it must call `setsid()` first. Must be called in the child process.
+    pub(crate) fn make_controlling_terminal(&self) -> Result<(), UECOError> {
+        let ret = unsafe { libc::ioctl(self.write_fd, libc::TIOCSCTTY as _, 0) };
+        libc_ret_to_result(ret, LibcSyscall::Ioctl)
+    }
 
-        // EOF
-        if ret == 0 {
-            Ok(None)
+    /// Checks that `fd` refers to the same file as the write end of the pipe, i.e. that
+    /// [`Pipe::connect_to_stdout`] or [`Pipe::connect_to_stderr`] really redirected it.
+    /// Returns [`UECOError::RedirectVerificationFailed`] otherwise.
+    /// You probably only want to do this in the child process.
+    pub(crate) fn verify_connected(&self, fd: libc::c_int) -> Result<(), UECOError> {
+        let expected = fstat(self.write_fd)?;
+        let actual = fstat(fd)?;
+        if (expected.st_dev, expected.st_ino) == (actual.st_dev, actual.st_ino) {
+            Ok(())
         } else {
-            let char = buf[0];
-            Ok(Some(char))
+            Err(UECOError::RedirectVerificationFailed { fd })
+        }
+    }
+
+    /// Reading the master of a pty fails with `EIO` instead of returning EOF once
+    /// all file descriptors of the slave are closed, i.e. the child exited.
+    fn is_pty_eof(&self, errno: libc::c_int) -> bool {
+        self.pty && errno == libc::EIO
+    }
+
+    /// Closes the file descriptor of the given end, if it's still open.
+    fn close_end(&mut self, end: PipeEnd) -> Result<(), UECOError> {
+        let fd = match end {
+            PipeEnd::Read => &mut self.read_fd,
+            PipeEnd::Write => &mut self.write_fd,
+        };
+        if *fd == CLOSED_FD {
+            return Ok(());
+        }
+        let ret = unsafe { libc::close(*fd) };
+        *fd = CLOSED_FD;
+        if ret == -1 {
+            return close_error_to_result(errno::errno().0);
+        }
+        Ok(())
+    }
+}
+
+/// Decides whether a failed `close()` is an error.
+/// * `EINTR`: On Linux, the fd is closed anyway and must not be closed again, because
+///   the number may be reused already. This is treated as success.
+/// * `EBADF`: The fd was closed already, e.g. by the child setup. Nothing is lost.
+fn close_error_to_result(errno: libc::c_int) -> Result<(), UECOError> {
+    match errno {
+        libc::EINTR => {
+            trace!("close() was interrupted; the fd is closed anyway");
+            Ok(())
+        }
+        libc::EBADF => {
+            trace!("fd was already closed");
+            Ok(())
         }
+        errno => Err(UECOError::CloseFailed { errno }),
+    }
+}
+
+impl Drop for Pipe {
+    /// Closes all file descriptors that are still open.
+    fn drop(&mut self) {
+        // errors can't be handled here in a meaningful way
+        let _ = self.close_end(PipeEnd::Read);
+        let _ = self.close_end(PipeEnd::Write);
+    }
+}
+
+/// Wrapper around `fstat()`.
+fn fstat(fd: libc::c_int) -> Result<libc::stat, UECOError> {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::fstat(fd, &mut stat) };
+    libc_ret_to_result(ret, LibcSyscall::Fstat)?;
+    Ok(stat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_twice() {
+        let mut pipe = Pipe::new().unwrap();
+        pipe.close_write_end().unwrap();
+        pipe.close_write_end().unwrap();
+        // an fd that was closed behind the back of the pipe; not the real one, because
+        // its number could be reused by another test in the meantime
+        unsafe { libc::close(pipe.read_fd) };
+        pipe.read_fd = 1_000_000;
+        pipe.close_end(PipeEnd::Read).unwrap();
+    }
+
+    #[test]
+    fn test_close_error_to_result() {
+        assert!(close_error_to_result(libc::EINTR).is_ok());
+        assert!(close_error_to_result(libc::EBADF).is_ok());
+        assert!(matches!(
+            close_error_to_result(libc::EIO),
+            Err(UECOError::CloseFailed { errno: libc::EIO })
+        ));
+    }
+
+    #[test]
+    fn test_verify_connected() {
+        let pipe = Pipe::new().unwrap();
+        let other = Pipe::new().unwrap();
+        pipe.verify_connected(pipe.write_fd).unwrap();
+        assert!(matches!(
+            pipe.verify_connected(other.write_fd),
+            Err(UECOError::RedirectVerificationFailed { fd }) if fd == other.write_fd
+        ));
+    }
+
+    #[test]
+    fn test_read_line_buffered() {
+        let mut pipe = Pipe::new().unwrap();
+        let fd = unsafe { libc::dup(pipe.write_fd) };
+        pipe.mark_as_parent_process().unwrap();
+        // longer than a single read and split across reads
+        let long_line = "x".repeat(3 * LINE_READ_BUF_LEN + 7);
+        let output = format!("a\n\nb\n{}\nlast", long_line);
+        let writer = std::thread::spawn(move || {
+            let ret = unsafe { libc::write(fd, output.as_ptr() as *const _, output.len()) };
+            assert_eq!(output.len() as isize, ret);
+            unsafe { libc::close(fd) };
+        });
+        let mut lines = vec![];
+        while let ReadLine::Line(_, line) = pipe.read_line(None, true, None).unwrap() {
+            lines.push(line);
+        }
+        writer.join().unwrap();
+        assert_eq!(vec!["a", "", "b", long_line.as_str(), "last"], lines);
+    }
+
+    #[test]
+    fn test_read_line_too_long() {
+        let mut pipe = Pipe::new().unwrap();
+        let output = b"12345\n123456\n";
+        let ret = unsafe { libc::write(pipe.write_fd, output.as_ptr() as *const _, output.len()) };
+        assert_eq!(output.len() as isize, ret);
+        pipe.mark_as_parent_process().unwrap();
+        assert!(matches!(
+            pipe.read_line(Some(5), false, None).unwrap(),
+            ReadLine::Line(_, line) if line == "12345"
+        ));
+        assert!(matches!(
+            pipe.read_line(Some(5), false, None),
+            Err(UECOError::LineTooLong { limit: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_read_line_timeout() {
+        let mut pipe = Pipe::new().unwrap();
+        let output = b"12";
+        let ret = unsafe { libc::write(pipe.write_fd, output.as_ptr() as *const _, output.len()) };
+        assert_eq!(output.len() as isize, ret);
+        let fd = unsafe { libc::dup(pipe.write_fd) };
+        pipe.mark_as_parent_process().unwrap();
+        let timeout = Some(Duration::from_millis(20));
+        assert_eq!(
+            ReadLine::TimedOut,
+            pipe.read_line(None, false, timeout).unwrap()
+        );
+        // the incomplete line is kept
+        let ret = unsafe { libc::write(fd, b"3\n".as_ptr() as *const _, 2) };
+        assert_eq!(2, ret);
+        unsafe { libc::close(fd) };
+        assert!(matches!(
+            pipe.read_line(None, false, timeout).unwrap(),
+            ReadLine::Line(_, line) if line == "123"
+        ));
+        assert_eq!(ReadLine::Eof, pipe.read_line(None, false, timeout).unwrap());
     }
 
-    /// Closes the specified file descriptor.
-    fn close_fd(&self, fd: libc::c_int) -> Result<(), UECOError> {
-        let ret = unsafe { libc::close(fd) };
-        libc_ret_to_result(ret, LibcSyscall::Close)
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_set_capacity() {
+        let pipe = Pipe::new().unwrap();
+        // one page; the minimum
+        pipe.set_capacity(1).unwrap();
+        let capacity = unsafe { libc::fcntl(pipe.write_fd, libc::F_GETPIPE_SZ) };
+        assert!(capacity > 0 && capacity < 65536);
+        pipe.set_capacity(1 << 18).unwrap();
+        let capacity = unsafe { libc::fcntl(pipe.write_fd, libc::F_GETPIPE_SZ) };
+        assert_eq!(1 << 18, capacity);
     }
 }
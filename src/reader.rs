@@ -1,14 +1,19 @@
 //! Abstraction and functions related to the reading of the output.
 
-use crate::child::{ChildProcess, ProcessState};
+use crate::ansi::strip_ansi_escapes;
+use crate::child::{wait_until_exited, ChildProcess, ProcessState};
 use crate::error::UECOError;
-use crate::pipe::Pipe;
-use crate::{OCatchStrategy, ProcessOutput};
-use std::collections::BTreeMap;
-use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use crate::label;
+use crate::pipe::{Pipe, ReadLine};
+use crate::{ExitStatus, Lines, OCatchStrategy, ProcessOutput, Source};
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use std::thread;
-use std::time::Instant;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 /// Read all content from the child process output
 /// as long as it's running. Catches STDOUT and STDERR.
@@ -20,64 +25,774 @@ pub trait OutputReader {
     fn read_all_bl(&mut self) -> Result<ProcessOutput, UECOError>;
 
     /// The strategy this reader is responsible for.
-    fn strategy() -> OCatchStrategy;
+    fn strategy(&self) -> OCatchStrategy;
 }
 
-/// Reader for [`crate::OCatchStrategy::StdCombined`].
-/// Catches `"STDCOMBINED"` in right order but `STDOUT`
-/// and `STDERR` not at all.
+/// Options that influence how the output gets read.
+#[derive(Debug, Default, Clone)]
+pub struct ReaderOptions {
+    /// Maximum length of a single line in bytes. See [`crate::Command::max_line_bytes`].
+    pub max_line_bytes: Option<usize>,
+    /// Remove ANSI escape sequences from each line. See [`crate::Command::strip_ansi`].
+    pub strip_ansi: bool,
+    /// See [`crate::Command::normalize_text`].
+    pub normalize_text: bool,
+    /// Maximum size of all lines in bytes. See [`crate::Command::max_output_bytes`].
+    pub max_output_bytes: Option<usize>,
+    /// See [`crate::Command::on_overflow`].
+    pub overflow_policy: OverflowPolicy,
+    /// See [`crate::Command::trim_line_prefix`].
+    pub trim_line_prefix: Option<String>,
+    /// See [`crate::Command::trim_line_suffix`].
+    pub trim_line_suffix: Option<String>,
+    /// See [`crate::Command::max_lines`].
+    pub max_lines: Option<usize>,
+    /// See [`crate::Command::map_line`].
+    pub map_line: Option<LineMapper>,
+    /// See [`crate::Command::stop_when`].
+    pub stop_when: Option<StopPredicate>,
+    /// See [`crate::Command::timeout`].
+    pub timeout: Option<Duration>,
+    /// See [`crate::Command::read_timeout`].
+    pub read_timeout: Option<Duration>,
+    /// See [`crate::Command::label`].
+    pub label: Option<Arc<str>>,
+    /// See [`crate::Command::state_poll_interval`]. [`DEFAULT_STATE_POLL_INTERVAL`] if `None`.
+    pub state_poll_interval: Option<Duration>,
+    /// See [`crate::Command::reader_stack_size`]. The default of std if `None`.
+    pub reader_stack_size: Option<usize>,
+    /// Splits the output on matches of this regex instead of newlines.
+    /// See [`crate::Command::record_separator_regex`].
+    #[cfg(feature = "regex")]
+    pub record_separator: Option<regex::bytes::Regex>,
+    /// See [`crate::Command::keep_trailing_empty_line`].
+    pub keep_trailing_empty_line: bool,
+    /// Read and discard the remaining output instead of killing the child when reading
+    /// stops early. See [`crate::Command::kill_on_stop`].
+    pub keep_child_on_stop: bool,
+    /// See [`crate::Command::read_termination`].
+    pub read_termination: ReadTermination,
+    /// Detect whether descendants of the child keep the output open after it exited.
+    /// See [`crate::Command::capture_descendants`].
+    pub track_descendants: bool,
+    /// See [`crate::Command::dedup_consecutive`].
+    pub dedup_consecutive: bool,
+    /// See [`crate::Command::dedup_count_suffix`].
+    pub dedup_count_suffix: bool,
+    /// See [`crate::Command::line_stats`].
+    pub line_stats: bool,
+    /// See [`crate::Command::retain_timestamps`].
+    pub retain_timestamps: bool,
+}
+
+/// Closure of [`crate::Command::map_line`].
+type MapLineFn = dyn FnMut(Option<Source>, String) -> Option<String> + Send;
+
+/// Closure of [`crate::Command::map_line`]. Shared by all reader threads of a child
+/// and by all clones of the [`crate::Command`].
+#[derive(Clone)]
+pub struct LineMapper(Arc<Mutex<Box<MapLineFn>>>);
+
+impl LineMapper {
+    /// Constructor.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: FnMut(Option<Source>, String) -> Option<String> + Send + 'static,
+    {
+        Self(Arc::new(Mutex::new(Box::new(f))))
+    }
+}
+
+impl Debug for LineMapper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LineMapper")
+    }
+}
+
+/// Predicate of [`crate::Command::stop_when`].
+#[derive(Clone)]
+pub struct StopPredicate(Arc<dyn Fn(&str) -> bool + Send + Sync>);
+
+impl StopPredicate {
+    /// Constructor.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl Debug for StopPredicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StopPredicate")
+    }
+}
+
+/// What happens if the output exceeds [`crate::Command::max_output_bytes`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The child gets killed and [`UECOError::OutputTooLarge`] is returned.
+    #[default]
+    Error,
+    /// Keeps the first lines that fit into the limit. All further output is read
+    /// but discarded, so the child can run until it finishes.
+    Truncate,
+    /// Keeps the last lines that fit into the limit by dropping the oldest lines.
+    Tail,
+}
+
+/// Decides when reading the output is complete. See [`crate::Command::read_termination`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ReadTermination {
+    /// Reading is complete once the child exited and all pipes reached EOF. A pipe reaches
+    /// EOF once all processes closed its write end, which includes background processes
+    /// that inherited it. At the end of a PTY, `read()` fails with `EIO`; this counts as
+    /// EOF too.
+    #[default]
+    Eof,
+    /// Reading is complete once the child exited and the output that was written until
+    /// then was read. Doesn't wait for EOF, so it also works if the write end stays open,
+    /// e.g. because the child started a daemon that inherited STDOUT. Output that such
+    /// processes write later gets lost.
+    ChildExit,
+}
+
+/// Why the output was not read completely. See [`crate::ProcessOutput::stop_reason`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StopReason {
+    /// [`crate::Command::max_lines`] lines were caught.
+    MaxLines,
+    /// A line matched the predicate of [`crate::Command::stop_when`].
+    StopCondition,
+    /// The child didn't finish within [`crate::Command::timeout`].
+    Timeout,
+}
+
+/// Statistics about the lengths of the caught lines in bytes. See
+/// [`crate::ProcessOutput::stats`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineStats {
+    /// Number of lines.
+    pub lines: usize,
+    /// Length of the shortest line.
+    pub min_len: usize,
+    /// Length of the longest line.
+    pub max_len: usize,
+    /// Length of all lines together.
+    pub total_bytes: usize,
+}
+
+impl LineStats {
+    /// Average length of a line. `0.0` if there are no lines.
+    pub fn average_len(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.total_bytes as f64 / self.lines as f64
+        }
+    }
+
+    /// Accounts a line with `len` bytes.
+    fn record(&mut self, len: usize) {
+        self.min_len = if self.lines == 0 {
+            len
+        } else {
+            self.min_len.min(len)
+        };
+        self.max_len = self.max_len.max(len);
+        self.lines += 1;
+        self.total_bytes += len;
+    }
+
+    /// Combines the statistics of two streams.
+    fn merge(self, other: Self) -> Self {
+        match (self.lines, other.lines) {
+            (0, _) => other,
+            (_, 0) => self,
+            _ => Self {
+                lines: self.lines + other.lines,
+                min_len: self.min_len.min(other.min_len),
+                max_len: self.max_len.max(other.max_len),
+                total_bytes: self.total_bytes + other.total_bytes,
+            },
+        }
+    }
+}
+
+impl ReaderOptions {
+    /// Empty [`LineStats`] if [`ReaderOptions::line_stats`] is set.
+    fn new_line_stats(&self) -> Option<LineStats> {
+        self.line_stats.then(LineStats::default)
+    }
+}
+
+/// Bytes and number of all lines that were read so far. Shared by all reader threads of a
+/// child, because [`crate::Command::max_output_bytes`] and [`crate::Command::max_lines`]
+/// limit the total output.
+#[derive(Debug, Default)]
+struct OutputBudget {
+    used: AtomicUsize,
+    lines: AtomicUsize,
+    /// Set once reading stops early. The first reason wins.
+    stop_reason: OnceLock<StopReason>,
+}
+
+impl OutputBudget {
+    /// Checks whether a line with `len` bytes may be kept according to
+    /// [`ReaderOptions::overflow_policy`]. For [`OverflowPolicy::Tail`] lines are always
+    /// kept; the oldest lines get dropped afterwards, see [`push_line`].
+    fn admit(&self, options: &ReaderOptions, len: usize) -> Result<bool, UECOError> {
+        let limit = match options.max_output_bytes {
+            Some(limit) => limit,
+            None => return Ok(true),
+        };
+        if options.overflow_policy == OverflowPolicy::Tail {
+            return Ok(true);
+        }
+        // also counts discarded lines, so once the limit is reached, all further lines
+        // get discarded
+        let within_limit = self.used.fetch_add(len, Ordering::SeqCst) + len <= limit;
+        match options.overflow_policy {
+            OverflowPolicy::Error if !within_limit => Err(UECOError::OutputTooLarge { limit }),
+            _ => Ok(within_limit),
+        }
+    }
+
+    /// Counts a line that is about to be stored, see [`ReaderOptions::max_lines`] and
+    /// [`ReaderOptions::stop_when`]. Returns `None` if reading stopped already and the line
+    /// must be discarded. Otherwise, returns whether this is the last line; reading must
+    /// stop after it then.
+    fn admit_line(&self, options: &ReaderOptions, line: &str) -> Option<bool> {
+        if self.stop_reason().is_some() {
+            return None;
+        }
+        if let Some(limit) = options.max_lines {
+            let count = self.lines.fetch_add(1, Ordering::SeqCst) + 1;
+            if count > limit {
+                return None;
+            }
+            if count == limit {
+                self.stop(StopReason::MaxLines);
+                return Some(true);
+            }
+        }
+        if let Some(StopPredicate(predicate)) = &options.stop_when {
+            if predicate(line) {
+                self.stop(StopReason::StopCondition);
+                return Some(true);
+            }
+        }
+        Some(false)
+    }
+
+    /// Records why reading stops early.
+    fn stop(&self, reason: StopReason) {
+        let _ = self.stop_reason.set(reason);
+    }
+
+    /// See [`OutputBudget::stop`].
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason.get().copied()
+    }
+}
+
+/// Default of [`crate::Command::state_poll_interval`].
+pub const DEFAULT_STATE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Read timeout of the line based readers if only [`crate::Command::timeout`] is set.
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Phase of the line readers, see [`StatePoll`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ReadPhase {
+    /// Phase 1: the child runs. Its state is checked from time to time.
+    ChildRunning,
+    /// Phase 2: `waitpid()` reported that the child exited. The output that it wrote right
+    /// before may still be in the pipe, so the pipe is drained until genuine EOF.
+    Draining,
+}
+
+/// Termination logic of the line readers. Decides when they check the state of the child
+/// with `waitpid()`, see [`crate::Command::state_poll_interval`], and when reading is
+/// complete: only after the child exited ([`ReadPhase::Draining`]) and EOF was read
+/// afterwards. At EOF, the readers block until the child exited instead of polling.
+struct StatePoll {
+    interval: Duration,
+    last_check: Option<Instant>,
+    phase: ReadPhase,
+}
+
+impl StatePoll {
+    /// Constructor.
+    fn new(options: &ReaderOptions) -> Self {
+        Self {
+            interval: options
+                .state_poll_interval
+                .unwrap_or(DEFAULT_STATE_POLL_INTERVAL),
+            last_check: None,
+            phase: ReadPhase::ChildRunning,
+        }
+    }
+
+    /// Must be called after each read. `eof` tells whether the read returned EOF and
+    /// `child_running` checks the state of the child; with `true` as argument, it blocks
+    /// until the child exited. Returns `true` once reading is complete.
+    fn after_read<F>(&mut self, eof: bool, child_running: F) -> Result<bool, UECOError>
+    where
+        F: FnOnce(bool) -> Result<bool, UECOError>,
+    {
+        if self.phase == ReadPhase::ChildRunning {
+            // at EOF, there is nothing left to do but to wait; blocking instead of polling
+            // with WNOHANG doesn't waste CPU time and reacts immediately to the exit
+            let running = if eof {
+                child_running(true)?
+            } else {
+                !self.due() || child_running(false)?
+            };
+            if !running {
+                trace!("child exited; draining the pipe");
+                self.phase = ReadPhase::Draining;
+            }
+        }
+        Ok(self.phase == ReadPhase::Draining && eof)
+    }
+
+    /// Whether the state must be checked now: at most once per interval.
+    fn due(&mut self) -> bool {
+        let due = self
+            .last_check
+            .map_or(true, |last_check| last_check.elapsed() >= self.interval);
+        if due {
+            self.last_check.replace(Instant::now());
+        }
+        due
+    }
+}
+
+/// Extracts the message of a panic from the payload that `JoinHandle::join` returns.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "<unknown panic payload>".to_string()
+    }
+}
+
+/// Appends a line to the lines of one stream. `bytes` is the size of all lines. If `tail_limit`
+/// is set, the oldest lines are dropped until all lines fit into it. The line is accounted
+/// in `stats`, if there are any; dropped lines stay accounted.
+fn push_line<T>(
+    lines: &mut VecDeque<(T, String)>,
+    bytes: &mut usize,
+    line: (T, String),
+    tail_limit: Option<usize>,
+    stats: Option<&mut LineStats>,
+) {
+    if let Some(stats) = stats {
+        stats.record(line.1.len());
+    }
+    *bytes += line.1.len();
+    lines.push_back(line);
+    if let Some(limit) = tail_limit {
+        while *bytes > limit {
+            let (_, dropped) = lines.pop_front().expect("bytes > 0 implies lines");
+            *bytes -= dropped.len();
+        }
+    }
+}
+
+/// State of [`crate::Command::dedup_consecutive`] for the lines of one [`push_line`] target.
+/// `K` tells lines of different streams apart that are stored together.
+#[derive(Debug)]
+struct Dedup<K> {
+    /// See [`ReaderOptions::dedup_consecutive`].
+    enabled: bool,
+    /// See [`ReaderOptions::dedup_count_suffix`].
+    count_suffix: bool,
+    /// Key and original text of the last stored line and how often it occurred in a row.
+    previous: Option<(K, String, usize)>,
+}
+
+impl<K> Default for Dedup<K> {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            count_suffix: false,
+            previous: None,
+        }
+    }
+}
+
+impl<K: PartialEq> Dedup<K> {
+    /// Constructor.
+    fn new(options: &ReaderOptions) -> Self {
+        Self {
+            enabled: options.dedup_consecutive,
+            count_suffix: options.dedup_count_suffix,
+            previous: None,
+        }
+    }
+
+    /// Must be called right before `line` gets stored with [`push_line`]. Returns `true` if
+    /// it repeats the last stored line and must be dropped. With
+    /// [`crate::Command::dedup_count_suffix`], the last stored line gets the count then.
+    fn collapse<T>(
+        &mut self,
+        lines: &mut VecDeque<(T, String)>,
+        bytes: &mut usize,
+        key: K,
+        line: &str,
+    ) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match &mut self.previous {
+            Some((previous_key, text, count)) if *previous_key == key && text == line => {
+                *count += 1;
+                if self.count_suffix {
+                    if let Some((_, last)) = lines.back_mut() {
+                        *bytes -= last.len();
+                        *last = format!("{} (repeated {}x)", text, count);
+                        *bytes += last.len();
+                    }
+                }
+                true
+            }
+            _ => {
+                self.previous.replace((key, line.to_string(), 1));
+                false
+            }
+        }
+    }
+}
+
+impl ReaderOptions {
+    /// When the line based readers stop because of [`ReaderOptions::timeout`], counted
+    /// from now.
+    fn read_deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    /// How long a single `read()` of the line based readers may block: the
+    /// [`ReaderOptions::read_timeout`] or, if only a [`ReaderOptions::timeout`] is set,
+    /// [`DEFAULT_READ_TIMEOUT`], so that the deadline is checked regularly.
+    fn effective_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+            .or_else(|| self.timeout.map(|_| DEFAULT_READ_TIMEOUT))
+    }
+
+    /// The limit for [`push_line`], if [`OverflowPolicy::Tail`] is used.
+    fn tail_limit(&self) -> Option<usize> {
+        match self.overflow_policy {
+            OverflowPolicy::Tail => self.max_output_bytes,
+            _ => None,
+        }
+    }
+
+    /// Whether only the chunk based [`MultiplexedOutputReader`] supports the options:
+    /// a [`ReaderOptions::record_separator`], [`ReadTermination::ChildExit`],
+    /// [`ReaderOptions::track_descendants`], or a [`ReaderOptions::timeout`] without a
+    /// [`ReaderOptions::read_timeout`]. The latter works with the line readers as well, but
+    /// `poll()` wakes up exactly at the deadline instead of every [`DEFAULT_READ_TIMEOUT`].
+    pub(crate) fn needs_chunk_reader(&self) -> bool {
+        #[cfg(feature = "regex")]
+        if self.record_separator.is_some() {
+            return true;
+        }
+        self.read_termination == ReadTermination::ChildExit
+            || self.track_descendants
+            || (self.timeout.is_some() && self.read_timeout.is_none())
+    }
+
+    /// Where the search for the next separator may start in a line of which `scanned` bytes
+    /// were already searched. A newline can't appear in bytes that were searched before,
+    /// but a match of [`ReaderOptions::record_separator`] may start in them.
+    fn separator_search_start(&self, scanned: usize) -> usize {
+        #[cfg(feature = "regex")]
+        if self.record_separator.is_some() {
+            return 0;
+        }
+        scanned
+    }
+
+    /// Finds the next separator in `buf`: a newline or, if set, the first non-empty match of
+    /// [`ReaderOptions::record_separator`]. Returns its start and end. Unless `eof`, a match
+    /// that touches the end of `buf` is ignored, because it may continue in the next chunk.
+    fn find_separator(&self, buf: &[u8], eof: bool) -> Option<(usize, usize)> {
+        #[cfg(feature = "regex")]
+        if let Some(regex) = &self.record_separator {
+            return regex
+                .find_iter(buf)
+                .find(|m| m.start() < m.end())
+                .filter(|m| eof || m.end() < buf.len())
+                .map(|m| (m.start(), m.end()));
+        }
+        let _ = eof;
+        memchr::memchr(b'\n', buf).map(|pos| (pos, pos + 1))
+    }
+
+    /// Passes the line to [`ReaderOptions::map_line`], if there is one. `None` means the
+    /// line gets dropped. Must be called after [`ReaderOptions::process_line`].
+    pub(crate) fn map_line(&self, source: Option<Source>, line: String) -> Option<String> {
+        match &self.map_line {
+            // a panic in the closure must not take down the other reader thread too
+            Some(LineMapper(f)) => (f.lock().unwrap_or_else(PoisonError::into_inner))(source, line),
+            None => Some(line),
+        }
+    }
+
+    /// Applies all configured transformations to a line that was just read. `first_line`
+    /// tells whether it's the first line of its stream.
+    pub(crate) fn process_line(&self, mut line: String, first_line: bool) -> String {
+        if self.normalize_text {
+            if line.ends_with('\r') {
+                line.pop();
+            }
+            if first_line && line.starts_with('\u{FEFF}') {
+                line.drain(..'\u{FEFF}'.len_utf8());
+            }
+        }
+        let mut line = if self.strip_ansi {
+            strip_ansi_escapes(&line)
+        } else {
+            line
+        };
+        if let Some(prefix) = self.trim_line_prefix.as_deref() {
+            if line.starts_with(prefix) {
+                line.drain(..prefix.len());
+            }
+        }
+        if let Some(suffix) = self.trim_line_suffix.as_deref() {
+            if line.ends_with(suffix) {
+                line.truncate(line.len() - suffix.len());
+            }
+        }
+        line
+    }
+}
+
+/// Reader for [`crate::OCatchStrategy::StdCombined`] and
+/// [`crate::OCatchStrategy::CombinedPrefixed`]. Catches `"STDCOMBINED"`
+/// in right order but `STDOUT` and `STDERR` not at all.
 // #[derive(Debug)]
 pub struct SimpleOutputReader<'a> {
     pipe: Arc<Mutex<Pipe>>,
     child: &'a mut ChildProcess,
+    options: ReaderOptions,
+    strategy: OCatchStrategy,
 }
 
 impl<'a> SimpleOutputReader<'a> {
-    pub fn new(child: &'a mut ChildProcess) -> Self {
+    pub fn new(
+        child: &'a mut ChildProcess,
+        options: ReaderOptions,
+        strategy: OCatchStrategy,
+    ) -> Self {
         // in this case stdout and stderr both use the same pipe
         SimpleOutputReader {
             pipe: child.stdout_pipe().clone(),
             child,
+            options,
+            strategy,
         }
     }
 }
 
 impl<'a> OutputReader for SimpleOutputReader<'a> {
     fn read_all_bl(&mut self) -> Result<ProcessOutput, UECOError> {
-        let pipe = self.pipe.lock().unwrap();
-        let mut lines = vec![];
+        let mut pipe = self.pipe.lock().unwrap();
+        let classify = match self.strategy {
+            OCatchStrategy::CombinedPrefixed(classify) => Some(classify),
+            _ => None,
+        };
+        let budget = OutputBudget::default();
+        let mut lines_with_source = VecDeque::new();
+        let mut bytes = 0;
+        let mut dedup = Dedup::new(&self.options);
+        let mut state_poll = StatePoll::new(&self.options);
+        let deadline = self.options.read_deadline();
+        let mut stats = self.options.new_line_stats();
+        let mut first_line = true;
 
         let mut eof;
         loop {
-            let line = pipe.read_line()?;
+            let line = pipe
+                .read_line(
+                    self.options.max_line_bytes,
+                    self.options.keep_trailing_empty_line,
+                    self.options.effective_read_timeout(),
+                )
+                .and_then(|line| match line {
+                    ReadLine::Line(_, line) => {
+                        let first_line = std::mem::replace(&mut first_line, false);
+                        let line = self.options.process_line(line, first_line);
+                        let source = classify.map(|classify| classify(&line));
+                        let line = match self.options.map_line(source, line) {
+                            Some(line) => line,
+                            None => return Ok(Some(None)),
+                        };
+                        let keep = budget.admit(&self.options, line.len())?;
+                        Ok(Some(keep.then_some((source, line))))
+                    }
+                    ReadLine::TimedOut => Ok(Some(None)),
+                    ReadLine::Eof => Ok(None),
+                });
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    // don't leave the child behind
+                    self.child.kill()?;
+                    return Err(err);
+                }
+            };
             match line {
                 None => eof = true,
-                Some((_, line)) => {
+                Some(line) => {
                     eof = false;
-                    lines.push(line)
+                    if let Some(line) = line {
+                        let last = match budget.admit_line(&self.options, &line.1) {
+                            Some(last) => last,
+                            None => continue,
+                        };
+                        if dedup.collapse(&mut lines_with_source, &mut bytes, line.0, &line.1) {
+                            continue;
+                        }
+                        push_line(
+                            &mut lines_with_source,
+                            &mut bytes,
+                            line,
+                            self.options.tail_limit(),
+                            stats.as_mut(),
+                        );
+                        if last && !self.options.keep_child_on_stop {
+                            trace!("stopped reading: {:?}", budget.stop_reason());
+                            self.child.kill()?;
+                            break;
+                        }
+                    }
                 }
             }
 
-            let process_is_running = self.child.check_state_nbl() == ProcessState::Running;
-            let process_finished = !process_is_running;
-            if process_finished && eof {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                trace!("timeout expired");
+                budget.stop(StopReason::Timeout);
+                self.child.kill()?;
+                break;
+            }
+
+            let child = &mut self.child;
+            if state_poll.after_read(eof, |blocking| {
+                if blocking {
+                    child.wait_bl()?;
+                }
+                Ok(child.check_state_nbl()? == ProcessState::Running)
+            })? {
                 break;
             }
         }
 
-        let lines = lines.into_iter().map(|s| Rc::new(s)).collect();
-        let output = ProcessOutput::new(
-            None,
-            None,
-            lines,
-            self.child.exit_code().unwrap(),
-            Self::strategy(),
-        );
+        let mut lines = Lines::new();
+        let mut sources = classify.map(|_| vec![]);
+        for (source, line) in &lines_with_source {
+            lines.push(line);
+            if let (Some(source), Some(sources)) = (source, sources.as_mut()) {
+                sources.push(*source);
+            }
+        }
+
+        // usually reaped already; waits otherwise instead of assuming that
+        let exit_status = self.child.wait_bl()?;
+        let output = ProcessOutput::new(None, None, lines, sources, exit_status, self.strategy())
+            .with_stop_reason(budget.stop_reason())
+            .with_resource_usage(self.child.resource_usage())
+            .with_stats(stats);
         Ok(output)
     }
 
-    fn strategy() -> OCatchStrategy {
-        OCatchStrategy::StdCombined
+    fn strategy(&self) -> OCatchStrategy {
+        self.strategy
+    }
+}
+
+/// Stores the lines without their timestamps in [`Lines`].
+fn to_lines(lines_by_timestamp: &VecDeque<(Instant, String)>) -> Lines {
+    let mut lines = Lines::new();
+    for (_, line) in lines_by_timestamp {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Merges the lines of STDOUT and STDERR into one list that is ordered by the timestamps.
+/// Each input must be ordered already, which is the case because each stream is read
+/// sequentially. The merge is stable: If two lines have the same timestamp, the line
+/// of STDOUT comes first. No line gets lost. Returns the lines and the [`Source`] and the
+/// timestamp of each line.
+pub(crate) fn combine_by_timestamp<T: Ord + Copy, S: AsRef<str>>(
+    stdout: &[(T, S)],
+    stderr: &[(T, S)],
+) -> (Lines, Vec<Source>, Vec<T>) {
+    let mut combined = Lines::new();
+    let mut sources = Vec::with_capacity(stdout.len() + stderr.len());
+    let mut timestamps = Vec::with_capacity(stdout.len() + stderr.len());
+    let mut stdout = stdout.iter().peekable();
+    let mut stderr = stderr.iter().peekable();
+    loop {
+        let (source, next) = match (stdout.peek(), stderr.peek()) {
+            (Some((out_ts, _)), Some((err_ts, _))) if err_ts < out_ts => {
+                (Source::Stderr, stderr.next())
+            }
+            (Some(_), _) => (Source::Stdout, stdout.next()),
+            (None, Some(_)) => (Source::Stderr, stderr.next()),
+            (None, None) => break,
+        };
+        let (timestamp, line) = next.unwrap();
+        combined.push(line.as_ref());
+        sources.push(source);
+        timestamps.push(*timestamp);
+    }
+    (combined, sources, timestamps)
+}
+
+/// The smallest gap between two consecutive lines of `timestamps` that were read from
+/// different streams, see [`crate::ProcessOutput::min_interline_gap`]. `None` if the
+/// stream never changes.
+fn min_interline_gap(sources: &[Source], timestamps: &[Instant]) -> Option<Duration> {
+    sources
+        .windows(2)
+        .zip(timestamps.windows(2))
+        .filter(|(sources, _)| sources[0] != sources[1])
+        .map(|(_, timestamps)| timestamps[1].saturating_duration_since(timestamps[0]))
+        .min()
+}
+
+/// Result of a reader thread: all lines with the timestamp when they were read.
+type ThreadResult = Result<(VecDeque<(Instant, String)>, Option<LineStats>), UECOError>;
+
+/// Drops the oldest lines of STDOUT and STDERR until all lines fit into `limit`. "Oldest" has
+/// the same meaning as in [`combine_by_timestamp`], so `stdcombined` is the tail of the output.
+fn drop_oldest_lines(
+    stdout: &mut VecDeque<(Instant, String)>,
+    stderr: &mut VecDeque<(Instant, String)>,
+    limit: usize,
+) {
+    let mut bytes = stdout
+        .iter()
+        .chain(stderr.iter())
+        .map(|(_, line)| line.len())
+        .sum::<usize>();
+    while bytes > limit {
+        let oldest = match (stdout.front(), stderr.front()) {
+            (Some((out_ts, _)), Some((err_ts, _))) if err_ts < out_ts => stderr.pop_front(),
+            (Some(_), _) => stdout.pop_front(),
+            _ => stderr.pop_front(),
+        };
+        bytes -= oldest.expect("bytes > 0 implies lines").1.len();
     }
 }
 
@@ -89,10 +804,13 @@ pub struct SimultaneousOutputReader {
     stdout_pipe: Arc<Mutex<Pipe>>,
     stderr_pipe: Arc<Mutex<Pipe>>,
     child: Arc<Mutex<ChildProcess>>,
+    options: ReaderOptions,
+    /// Shared by both reader threads.
+    budget: Arc<OutputBudget>,
 }
 
 impl SimultaneousOutputReader {
-    pub fn new(child: Arc<Mutex<ChildProcess>>) -> Self {
+    pub fn new(child: Arc<Mutex<ChildProcess>>, options: ReaderOptions) -> Self {
         let stdout_pipe = {
             child
                 .as_ref()
@@ -115,103 +833,846 @@ impl SimultaneousOutputReader {
             stdout_pipe,
             stderr_pipe,
             child,
+            options,
+            budget: Arc::new(OutputBudget::default()),
         }
     }
 
     /// Thread function that reads all lines either for STDERR or STDOUT. There will be two
     /// thread instances of this, if this strategy is choosen.
     fn thread_fn(
+        source: Source,
         pipe: Arc<Mutex<Pipe>>,
         child: Arc<Mutex<ChildProcess>>,
-    ) -> Result<Vec<(Instant, String)>, UECOError> {
-        let pipe = pipe.lock().unwrap();
-        let mut lines_by_timestamp = vec![];
+        options: ReaderOptions,
+        budget: Arc<OutputBudget>,
+    ) -> ThreadResult {
+        let mut pipe = pipe.lock().unwrap();
+        let mut lines_by_timestamp = VecDeque::new();
+        let mut bytes = 0;
+        let mut dedup = Dedup::new(&options);
+        let mut state_poll = StatePoll::new(&options);
+        let deadline = options.read_deadline();
+        let mut stats = options.new_line_stats();
+        let pid = child.lock().unwrap().pid().expect("child is dispatched");
+        let mut first_line = true;
 
         let mut eof;
         loop {
-            let line = pipe.read_line()?;
+            let line = pipe
+                .read_line(
+                    options.max_line_bytes,
+                    options.keep_trailing_empty_line,
+                    options.effective_read_timeout(),
+                )
+                .and_then(|line| match line {
+                    ReadLine::Line(instant, line) => {
+                        let first_line = std::mem::replace(&mut first_line, false);
+                        let line = options.process_line(line, first_line);
+                        let line = match options.map_line(Some(source), line) {
+                            Some(line) => line,
+                            None => return Ok(Some(None)),
+                        };
+                        let keep = budget.admit(&options, line.len())?;
+                        Ok(Some(keep.then_some((instant, line))))
+                    }
+                    ReadLine::TimedOut => Ok(Some(None)),
+                    ReadLine::Eof => Ok(None),
+                });
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    // don't leave the child behind; this also lets the other thread reach EOF
+                    child.lock().unwrap().kill()?;
+                    return Err(err);
+                }
+            };
             match line {
                 None => eof = true,
-                Some((instant, line)) => {
+                Some(line) => {
                     eof = false;
-                    lines_by_timestamp.push((instant, line))
+                    if let Some(line) = line {
+                        let last = match budget.admit_line(&options, &line.1) {
+                            Some(last) => last,
+                            None => continue,
+                        };
+                        if dedup.collapse(&mut lines_by_timestamp, &mut bytes, (), &line.1) {
+                            continue;
+                        }
+                        push_line(
+                            &mut lines_by_timestamp,
+                            &mut bytes,
+                            line,
+                            options.tail_limit(),
+                            stats.as_mut(),
+                        );
+                        if last && !options.keep_child_on_stop {
+                            // the other thread reaches EOF once the child is dead
+                            trace!("stopped reading: {:?}", budget.stop_reason());
+                            child.lock().unwrap().kill()?;
+                            break;
+                        }
+                    }
                 }
             }
 
-            let process_is_running =
-                child.lock().unwrap().check_state_nbl() == ProcessState::Running;
-            let process_finished = !process_is_running;
-            if process_finished && eof {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                // the other thread reaches EOF once the child is dead
+                trace!("timeout expired");
+                budget.stop(StopReason::Timeout);
+                child.lock().unwrap().kill()?;
+                break;
+            }
+
+            if state_poll.after_read(eof, |blocking| {
+                if blocking {
+                    // without the lock; the other thread must still be able to kill the child
+                    wait_until_exited(pid)?;
+                }
+                Ok(child.lock().unwrap().check_state_nbl()? == ProcessState::Running)
+            })? {
                 trace!("Child finished & read EOF");
                 break;
             }
         }
 
-        Ok(lines_by_timestamp)
+        Ok((lines_by_timestamp, stats))
+    }
+
+    /// Joins a reader thread. If the thread panicked, the child gets killed (so that the
+    /// other thread reaches EOF) and [`UECOError::ReaderThreadPanicked`] is returned.
+    fn join_thread(&self, handle: JoinHandle<ThreadResult>) -> ThreadResult {
+        handle.join().unwrap_or_else(|payload| {
+            let message = panic_message(payload.as_ref());
+            // the panicking thread may have poisoned the lock
+            self.child
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .kill()?;
+            Err(UECOError::ReaderThreadPanicked { message })
+        })
+    }
+
+    /// Spawns a named thread that executes [`SimultaneousOutputReader::thread_fn`] for the pipe.
+    /// The name makes the thread identifiable in panics, debuggers, and profilers.
+    fn spawn_thread(
+        &self,
+        name: &str,
+        source: Source,
+        pipe: Arc<Mutex<Pipe>>,
+    ) -> Result<JoinHandle<ThreadResult>, UECOError> {
+        let child = self.child.clone();
+        let options = self.options.clone();
+        let budget = self.budget.clone();
+        let mut builder = thread::Builder::new().name(name.to_string());
+        if let Some(stack_size) = options.reader_stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        let res = builder.spawn(move || {
+            let _label = label::enter(options.label.clone());
+            SimultaneousOutputReader::thread_fn(source, pipe, child, options, budget)
+        });
+        match res {
+            Ok(handle) => Ok(handle),
+            Err(err) => {
+                // without the reader thread nobody would wait for the child
+                self.child.lock().unwrap().kill()?;
+                Err(UECOError::ThreadSpawnFailed {
+                    errno: err.raw_os_error().unwrap_or(0),
+                })
+            }
+        }
     }
 }
 
 impl OutputReader for SimultaneousOutputReader {
     fn read_all_bl(&mut self) -> Result<ProcessOutput, UECOError> {
-        let stdout_pipe_t = self.stdout_pipe.clone();
-        let stderr_pipe_t = self.stderr_pipe.clone();
-        let child_t = self.child.clone();
-        let stdout_t =
-            thread::spawn(move || SimultaneousOutputReader::thread_fn(stdout_pipe_t, child_t));
-        let child_t = self.child.clone();
-        let stderr_t =
-            thread::spawn(move || SimultaneousOutputReader::thread_fn(stderr_pipe_t, child_t));
-
-        // get lines from threads with timestamps
-        let stdout = stdout_t.join().unwrap()?;
-        let stderr = stderr_t.join().unwrap()?;
-
-        // transform string to Rc<String>
-        let stdout = stdout
-            .into_iter()
-            .map(|(i, l)| (i, Rc::new(l)))
-            .collect::<Vec<(Instant, Rc<String>)>>();
-        let stderr = stderr
-            .into_iter()
-            .map(|(i, l)| (i, Rc::new(l)))
-            .collect::<Vec<(Instant, Rc<String>)>>();
+        let stdout_t = self.spawn_thread(
+            "ueco-stdout-reader",
+            Source::Stdout,
+            self.stdout_pipe.clone(),
+        )?;
+        let stderr_t = self.spawn_thread(
+            "ueco-stderr-reader",
+            Source::Stderr,
+            self.stderr_pipe.clone(),
+        )?;
+
+        // get lines from threads with timestamps; join both before propagating errors
+        let stdout = self.join_thread(stdout_t);
+        let stderr = self.join_thread(stderr_t);
+        let (mut stdout, stdout_stats) = stdout?;
+        let (mut stderr, stderr_stats) = stderr?;
+        let stats = stdout_stats
+            .zip(stderr_stats)
+            .map(|(out, err)| out.merge(err));
+        if let Some(limit) = self.options.tail_limit() {
+            // each thread only knows its own lines
+            drop_oldest_lines(&mut stdout, &mut stderr, limit);
+        }
 
         // build combined lines, sorted by timestamp
-        let mut combined = BTreeMap::new();
-        for (instant, line) in &stdout {
-            combined.insert(instant.clone(), line.clone());
-        }
-        for (instant, line) in &stderr {
-            combined.insert(instant.clone(), line.clone());
-        }
-
-        // remove timestamp from vector
-        let stdout = stdout
-            .into_iter()
-            .map(|(_, l)| l)
-            .collect::<Vec<Rc<String>>>();
-        // remove timestamp from vector
-        let stderr = stderr
-            .into_iter()
-            .map(|(_, l)| l)
-            .collect::<Vec<Rc<String>>>();
-        // owned vector
-        let stdcombined = combined
-            .values()
-            .map(|v| v.to_owned())
-            .collect::<Vec<Rc<String>>>();
+        let (stdcombined, sources, timestamps) =
+            combine_by_timestamp(stdout.make_contiguous(), stderr.make_contiguous());
+
+        let gap = min_interline_gap(&sources, &timestamps);
+
+        // remove timestamps
+        let stdout = to_lines(&stdout);
+        let stderr = to_lines(&stderr);
 
+        // usually reaped by a reader thread already; waits otherwise
+        let mut child = self.child.lock().unwrap_or_else(PoisonError::into_inner);
+        let exit_status = child.wait_bl()?;
         Ok(ProcessOutput::new(
             Some(stdout),
             Some(stderr),
             stdcombined,
-            self.child.lock().unwrap().exit_code().unwrap(),
-            Self::strategy(),
-        ))
+            Some(sources),
+            exit_status,
+            self.strategy(),
+        )
+        .with_stop_reason(self.budget.stop_reason())
+        .with_resource_usage(child.resource_usage())
+        .with_stats(stats)
+        .with_min_interline_gap(gap)
+        .with_timestamps(self.options.retain_timestamps.then_some(timestamps)))
     }
 
     /// Getter for the used strategy to obtain the output.
-    fn strategy() -> OCatchStrategy {
+    fn strategy(&self) -> OCatchStrategy {
         OCatchStrategy::StdSeparately
     }
 }
+
+/// Reads the raw output as it arrives in chunks, without splitting it into lines.
+/// Uses `poll()` to wait for all pipes in a single thread, so for
+/// [`crate::OCatchStrategy::StdSeparately`] no extra threads are required.
+pub struct ChunkReader<'a> {
+    child: &'a mut ChildProcess,
+    poller: ChunkPoller,
+    timeout: Option<Duration>,
+    read_termination: ReadTermination,
+    /// See [`ReaderOptions::track_descendants`].
+    track_descendants: bool,
+    /// How often the state of the child is checked for [`ReadTermination::ChildExit`] and
+    /// [`ReaderOptions::track_descendants`].
+    state_poll_interval: Duration,
+    /// Whether the pipes were still open after the child exited.
+    held_open_by_descendants: bool,
+}
+
+/// Why [`ChunkReader`] stopped reading.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadEnd {
+    /// All pipes reached EOF.
+    Eof,
+    /// The callback stopped reading.
+    Stopped,
+    /// The timeout expired, see [`crate::Command::timeout`].
+    TimedOut,
+    /// The child exited and the remaining output was read, see
+    /// [`ReadTermination::ChildExit`].
+    ChildExited,
+}
+
+impl<'a> ChunkReader<'a> {
+    pub fn new(child: &'a mut ChildProcess, strategy: OCatchStrategy, chunk_size: usize) -> Self {
+        let poller = ChunkPoller::new(child, strategy, chunk_size);
+        ChunkReader {
+            child,
+            poller,
+            timeout: None,
+            read_termination: ReadTermination::Eof,
+            track_descendants: false,
+            state_poll_interval: DEFAULT_STATE_POLL_INTERVAL,
+            held_open_by_descendants: false,
+        }
+    }
+
+    /// Applies [`ReaderOptions::read_termination`], [`ReaderOptions::track_descendants`],
+    /// and [`ReaderOptions::state_poll_interval`].
+    pub fn read_termination(mut self, options: &ReaderOptions) -> Self {
+        self.read_termination = options.read_termination;
+        self.track_descendants = options.track_descendants;
+        self.state_poll_interval = options
+            .state_poll_interval
+            .unwrap_or(DEFAULT_STATE_POLL_INTERVAL);
+        self
+    }
+
+    /// Stops reading and kills the child once `timeout` expired. The time starts
+    /// when reading starts.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Reads all output in a blocking way until all pipes reached EOF and passes each
+    /// chunk to `on_chunk`. Afterwards the child gets reaped. Returns
+    /// [`UECOError::TimedOut`] if the timeout expired.
+    pub fn read_all_bl<F>(&mut self, mut on_chunk: F) -> Result<ExitStatus, UECOError>
+    where
+        F: FnMut(Option<Source>, Vec<u8>),
+    {
+        let (exit_status, end) = self.read_until_bl(|source, chunk| {
+            on_chunk(source, chunk);
+            Ok(true)
+        })?;
+        match (end, self.timeout) {
+            (ReadEnd::TimedOut, Some(timeout)) => Err(UECOError::TimedOut { timeout }),
+            _ => Ok(exit_status),
+        }
+    }
+
+    /// Like [`ChunkReader::read_all_bl`] but `on_chunk` can stop reading early by returning
+    /// `Ok(false)`. The child gets killed then, like on errors and after the timeout.
+    pub fn read_until_bl<F>(&mut self, mut on_chunk: F) -> Result<(ExitStatus, ReadEnd), UECOError>
+    where
+        F: FnMut(Option<Source>, Vec<u8>) -> Result<bool, UECOError>,
+    {
+        self.poller.start_timeout(self.timeout);
+        let mut watch_child =
+            self.read_termination == ReadTermination::ChildExit || self.track_descendants;
+        let mut last_check = Instant::now();
+        let res = loop {
+            let until = watch_child.then(|| last_check + self.state_poll_interval);
+            match self.poller.poll_once_until(&mut on_chunk, until) {
+                Ok(None) => {}
+                Ok(Some(end)) => break Ok(end),
+                Err(err) => break Err(err),
+            }
+            if watch_child && last_check.elapsed() >= self.state_poll_interval {
+                last_check = Instant::now();
+                match self.child.check_state_nbl() {
+                    Ok(ProcessState::Running) => {}
+                    Ok(_) => match self.drain(&mut on_chunk) {
+                        Ok(ReadEnd::ChildExited) => {
+                            trace!("descendants of the child keep the output open");
+                            self.held_open_by_descendants = true;
+                            if self.read_termination == ReadTermination::ChildExit {
+                                break Ok(ReadEnd::ChildExited);
+                            }
+                            // read the output of the descendants until EOF
+                            watch_child = false;
+                        }
+                        res => break res,
+                    },
+                    Err(err) => break Err(err),
+                }
+            }
+        };
+        if !matches!(res, Ok(ReadEnd::Eof) | Ok(ReadEnd::ChildExited)) {
+            // don't leave the child behind
+            self.child.kill()?;
+        }
+        let end = res?;
+        Ok((self.child.wait_bl()?, end))
+    }
+
+    /// Whether the pipes were still open after the child exited, i.e. descendants of the
+    /// child inherited them. Only detected with [`ReaderOptions::track_descendants`] or
+    /// [`ReadTermination::ChildExit`].
+    pub fn held_open_by_descendants(&self) -> bool {
+        self.held_open_by_descendants
+    }
+
+    /// Reads the output that is left in the pipes after the child exited, without waiting
+    /// for EOF. Returns [`ReadEnd::ChildExited`] if they are still open then.
+    fn drain<F>(&mut self, on_chunk: &mut F) -> Result<ReadEnd, UECOError>
+    where
+        F: FnMut(Option<Source>, Vec<u8>) -> Result<bool, UECOError>,
+    {
+        trace!("child exited; reading the remaining output");
+        loop {
+            let mut read = false;
+            let end = self.poller.poll_once_until(
+                &mut |source, chunk| {
+                    read = true;
+                    on_chunk(source, chunk)
+                },
+                Some(Instant::now()),
+            )?;
+            if let Some(end) = end {
+                return Ok(end);
+            }
+            if !read {
+                return Ok(ReadEnd::ChildExited);
+            }
+        }
+    }
+}
+
+/// Waits for the pipes of a child with `poll()` and reads the data in chunks, one round
+/// per call. The building block of [`ChunkReader`] and [`crate::LineReader`].
+pub(crate) struct ChunkPoller {
+    /// All pipes to read from together with the source of their data.
+    pipes: Vec<(Option<Source>, Arc<Mutex<Pipe>>)>,
+    /// Whether each pipe is still open, i.e. didn't reach EOF yet.
+    open: Vec<bool>,
+    chunk_size: usize,
+    deadline: Option<Instant>,
+}
+
+impl ChunkPoller {
+    /// Constructor. Reads the pipes that belong to the strategy.
+    pub(crate) fn new(child: &ChildProcess, strategy: OCatchStrategy, chunk_size: usize) -> Self {
+        let pipes = match strategy {
+            // in this case stdout and stderr both use the same pipe
+            OCatchStrategy::StdCombined
+            | OCatchStrategy::CombinedPrefixed(_)
+            | OCatchStrategy::Pty => {
+                vec![(None, child.stdout_pipe().clone())]
+            }
+            OCatchStrategy::StdSeparately | OCatchStrategy::Multiplexed => vec![
+                (Some(Source::Stdout), child.stdout_pipe().clone()),
+                (Some(Source::Stderr), child.stderr_pipe().clone()),
+            ],
+        };
+        Self {
+            open: vec![true; pipes.len()],
+            pipes,
+            chunk_size,
+            deadline: None,
+        }
+    }
+
+    /// Starts the timeout, if there is one. See [`crate::Command::timeout`].
+    pub(crate) fn start_timeout(&mut self, timeout: Option<Duration>) {
+        self.deadline = timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Waits until at least one pipe is ready and passes the chunks of all ready pipes to
+    /// `on_chunk`. Returns `Some` once reading ends: all pipes reached EOF, `on_chunk`
+    /// returned `Ok(false)`, or the timeout expired. Waits at most until `until`; returns
+    /// `Ok(None)` without reading anything if no pipe got ready until then.
+    pub(crate) fn poll_once_until<F>(
+        &mut self,
+        on_chunk: &mut F,
+        until: Option<Instant>,
+    ) -> Result<Option<ReadEnd>, UECOError>
+    where
+        F: FnMut(Option<Source>, Vec<u8>) -> Result<bool, UECOError>,
+    {
+        if !self.open.iter().any(|open| *open) {
+            return Ok(Some(ReadEnd::Eof));
+        }
+        let now = Instant::now();
+        if let Some(deadline) = self.deadline {
+            if now >= deadline {
+                trace!("timeout expired");
+                return Ok(Some(ReadEnd::TimedOut));
+            }
+        }
+        let poll_timeout = match self.deadline.into_iter().chain(until).min() {
+            None => -1,
+            // round up; otherwise poll() returns immediately shortly before the deadline
+            Some(wake_up) => {
+                let millis = wake_up
+                    .saturating_duration_since(now)
+                    .as_micros()
+                    .div_ceil(1000);
+                millis.min(libc::c_int::MAX as u128) as libc::c_int
+            }
+        };
+        let mut poll_fds = self
+            .pipes
+            .iter()
+            .zip(self.open.iter())
+            .map(|((_, pipe), open)| libc::pollfd {
+                // negative fds are ignored by poll()
+                fd: if *open {
+                    pipe.lock().unwrap().read_fd()
+                } else {
+                    -1
+                },
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect::<Vec<libc::pollfd>>();
+        let ret = unsafe {
+            libc::poll(
+                poll_fds.as_mut_ptr(),
+                poll_fds.len() as libc::nfds_t,
+                poll_timeout,
+            )
+        };
+        if ret == -1 {
+            return match errno::errno().0 {
+                libc::EINTR => Ok(None),
+                errno => Err(UECOError::PollFailed { errno }),
+            };
+        }
+
+        for (i, poll_fd) in poll_fds.iter().enumerate() {
+            // POLLHUP without POLLIN: the write end was closed; read() returns EOF
+            if poll_fd.revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) == 0 {
+                continue;
+            }
+            let (source, pipe) = &self.pipes[i];
+            let chunk = pipe.lock().unwrap().read_chunk(self.chunk_size)?;
+            match chunk {
+                Some(chunk) => {
+                    if !on_chunk(*source, chunk)? {
+                        return Ok(Some(ReadEnd::Stopped));
+                    }
+                }
+                None => {
+                    trace!("pipe reached EOF");
+                    self.open[i] = false;
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Receives the lines during [`crate::Command::catch_with_visitor`] as soon as they were read,
+/// after all [`ReaderOptions`] were applied. Unlike a closure, an implementation can carry
+/// rich state and can be tested on its own.
+pub trait LineVisitor {
+    /// Called for each line, without the trailing newline.
+    /// * `source` the stream the line was written to. Always known for
+    ///   [`OCatchStrategy::StdSeparately`] and [`OCatchStrategy::Multiplexed`], determined by
+    ///   the classifier for [`OCatchStrategy::CombinedPrefixed`], and `None` otherwise.
+    /// * `instant` when the line was read.
+    fn visit(&mut self, source: Option<Source>, line: &str, instant: Instant);
+}
+
+impl<V: LineVisitor + ?Sized> LineVisitor for &mut V {
+    fn visit(&mut self, source: Option<Source>, line: &str, instant: Instant) {
+        (**self).visit(source, line, instant)
+    }
+}
+
+/// Appends the lines to buffers that the caller owns, so they can be reused across many
+/// invocations of [`crate::Command::catch_with_visitor`]. Existing content is kept. Lines
+/// of STDERR go to `stderr`, all other lines to `stdout`; so for
+/// [`OCatchStrategy::StdCombined`] all lines end up in `stdout`.
+#[derive(Debug)]
+pub struct BufferVisitor<'a> {
+    /// Receives the lines of STDOUT and of the combined pipe.
+    pub stdout: &'a mut Vec<String>,
+    /// Receives the lines of STDERR.
+    pub stderr: &'a mut Vec<String>,
+}
+
+impl LineVisitor for BufferVisitor<'_> {
+    fn visit(&mut self, source: Option<Source>, line: &str, _instant: Instant) {
+        match source {
+            Some(Source::Stderr) => self.stderr.push(line.to_string()),
+            _ => self.stdout.push(line.to_string()),
+        }
+    }
+}
+
+/// Collects all lines, used by [`MultiplexedOutputReader::read_all_bl`].
+#[derive(Debug, Default)]
+struct CollectingVisitor {
+    /// The lines with their source and when they were read.
+    lines: VecDeque<((Option<Source>, Instant), String)>,
+    /// Bytes of all `lines`.
+    bytes: usize,
+    /// See [`ReaderOptions::tail_limit`].
+    tail_limit: Option<usize>,
+    dedup: Dedup<Option<Source>>,
+    /// See [`ReaderOptions::line_stats`].
+    stats: Option<LineStats>,
+}
+
+impl LineVisitor for CollectingVisitor {
+    fn visit(&mut self, source: Option<Source>, line: &str, instant: Instant) {
+        if self
+            .dedup
+            .collapse(&mut self.lines, &mut self.bytes, source, line)
+        {
+            return;
+        }
+        push_line(
+            &mut self.lines,
+            &mut self.bytes,
+            ((source, instant), line.to_string()),
+            self.tail_limit,
+            self.stats.as_mut(),
+        );
+    }
+}
+
+/// Size of the buffer for a single `read()` of the [`MultiplexedOutputReader`]. Same as the
+/// default capacity of a pipe on Linux, so a single `read()` usually gets everything that
+/// is inside the pipe.
+pub(crate) const MULTIPLEXED_READ_BUF_LEN: usize = 65536;
+
+/// Reader for [`crate::OCatchStrategy::Multiplexed`]. Reads STDOUT and STDERR in a single
+/// thread with a [`ChunkReader`] and splits the chunks into lines in the order they arrive.
+/// Also reads the output of the other strategies for [`crate::Command::catch_with_visitor`]
+/// and for some [`ReaderOptions`], e.g. [`ReaderOptions::timeout`] without
+/// [`ReaderOptions::read_timeout`]. The [`crate::ProcessOutput`] is the same as
+/// the one of the line readers then; for [`crate::OCatchStrategy::StdSeparately`] this
+/// includes the timestamps and [`crate::ProcessOutput::min_interline_gap`].
+/// [`ReaderOptions::read_timeout`] and [`ReaderOptions::reader_stack_size`] don't apply,
+/// because `poll()` never blocks longer than necessary and there are no reader threads.
+pub struct MultiplexedOutputReader<'a> {
+    child: &'a mut ChildProcess,
+    options: ReaderOptions,
+    strategy: OCatchStrategy,
+    /// See [`crate::ProcessOutput::includes_descendant_output`].
+    descendant_output: Option<bool>,
+}
+
+impl<'a> MultiplexedOutputReader<'a> {
+    pub fn new(
+        child: &'a mut ChildProcess,
+        options: ReaderOptions,
+        strategy: OCatchStrategy,
+    ) -> Self {
+        MultiplexedOutputReader {
+            child,
+            options,
+            strategy,
+            descendant_output: None,
+        }
+    }
+
+    /// Reads all output in a blocking way and passes each line to the visitor. Returns the
+    /// exit status and why reading stopped early, if it did. [`OverflowPolicy::Tail`]
+    /// doesn't drop lines here, because they were passed to the visitor already.
+    pub fn read_all_bl_with_visitor(
+        &mut self,
+        visitor: &mut dyn LineVisitor,
+    ) -> Result<(ExitStatus, Option<StopReason>), UECOError> {
+        let options = &self.options;
+        let mut state = LineSplitter::new(self.strategy, visitor);
+        let mut reader = ChunkReader::new(self.child, self.strategy, MULTIPLEXED_READ_BUF_LEN)
+            .timeout(options.timeout)
+            .read_termination(options);
+        let (exit_status, end) = reader
+            .read_until_bl(|source, chunk| state.push_chunk(options, source, &chunk, false))?;
+        state.finish(options, end)?;
+        if options.track_descendants {
+            self.descendant_output.replace(
+                reader.held_open_by_descendants()
+                    && options.read_termination == ReadTermination::Eof,
+            );
+        }
+        Ok((exit_status, state.stop_reason()))
+    }
+}
+
+/// Splits chunks into lines, applies the [`ReaderOptions`], and passes the lines to a
+/// [`LineVisitor`]. State of [`MultiplexedOutputReader::read_all_bl_with_visitor`] and
+/// [`crate::LineReader`].
+pub(crate) struct LineSplitter<V> {
+    /// Bytes of the current, not yet finished line, how many of them were already searched
+    /// for a separator, and whether the last bytes were a separator; for the combined pipe
+    /// or STDOUT at index 0 and for STDERR at index 1.
+    bufs: [(Vec<u8>, usize, bool); 2],
+    /// Whether no line of the stream was passed on yet; indexed like `bufs`.
+    first_lines: [bool; 2],
+    /// See [`OCatchStrategy::CombinedPrefixed`].
+    classify: Option<fn(&str) -> Source>,
+    /// The streams that have their own pipe; `None` if both share one.
+    sources: Vec<Option<Source>>,
+    budget: OutputBudget,
+    pub(crate) visitor: V,
+}
+
+impl<V: LineVisitor> LineSplitter<V> {
+    /// Constructor.
+    pub(crate) fn new(strategy: OCatchStrategy, visitor: V) -> Self {
+        Self {
+            bufs: Default::default(),
+            first_lines: [true; 2],
+            classify: match strategy {
+                OCatchStrategy::CombinedPrefixed(classify) => Some(classify),
+                _ => None,
+            },
+            sources: match strategy {
+                OCatchStrategy::StdSeparately | OCatchStrategy::Multiplexed => {
+                    vec![Some(Source::Stdout), Some(Source::Stderr)]
+                }
+                _ => vec![None],
+            },
+            budget: OutputBudget::default(),
+            visitor,
+        }
+    }
+
+    /// Must be called once reading ended. After a timeout, the [`StopReason`] gets recorded.
+    /// At EOF, the remaining bytes of each stream are passed on as last line.
+    pub(crate) fn finish(
+        &mut self,
+        options: &ReaderOptions,
+        end: ReadEnd,
+    ) -> Result<(), UECOError> {
+        if end == ReadEnd::TimedOut {
+            self.budget.stop(StopReason::Timeout);
+        }
+        // lines without a trailing newline
+        if self.budget.stop_reason().is_none() {
+            for source in self.sources.clone() {
+                if !self.push_chunk(options, source, &[], true)? {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Why reading stopped early, if it did.
+    pub(crate) fn stop_reason(&self) -> Option<StopReason> {
+        self.budget.stop_reason()
+    }
+
+    /// Appends a chunk to the buffer of its stream and passes all lines that are complete
+    /// afterwards to the visitor. At EOF, the remaining bytes are a line too. Returns
+    /// `false` if reading must stop.
+    pub(crate) fn push_chunk(
+        &mut self,
+        options: &ReaderOptions,
+        source: Option<Source>,
+        chunk: &[u8],
+        eof: bool,
+    ) -> Result<bool, UECOError> {
+        let instant = Instant::now();
+        let index = match source {
+            Some(Source::Stderr) => 1,
+            _ => 0,
+        };
+        let (buf, scanned, after_newline) = &mut self.bufs[index];
+        if !chunk.is_empty() {
+            *after_newline = false;
+        }
+        buf.extend_from_slice(chunk);
+        let mut complete = vec![];
+        // remove the complete lines at once instead of one by one
+        let mut consumed = 0;
+        // don't search the start of a long line again for every chunk
+        let mut skip = options.separator_search_start(*scanned);
+        while let Some((start, end)) = options.find_separator(&buf[consumed + skip..], eof) {
+            let (start, end) = (skip + start, skip + end);
+            complete.push(String::from_utf8_lossy(&buf[consumed..consumed + start]).into_owned());
+            consumed += end;
+            skip = 0;
+            *after_newline = consumed == buf.len();
+        }
+        buf.drain(..consumed);
+        *scanned = buf.len();
+        if let Some(limit) = options.max_line_bytes {
+            if buf.len() > limit {
+                return Err(UECOError::LineTooLong { limit });
+            }
+        }
+        if eof && (!buf.is_empty() || (*after_newline && options.keep_trailing_empty_line)) {
+            complete.push(String::from_utf8_lossy(buf).into_owned());
+            buf.clear();
+            *scanned = 0;
+        }
+        for line in complete {
+            let first_line = std::mem::replace(&mut self.first_lines[index], false);
+            if !self.push_line(options, source, line, first_line, instant)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Applies the [`ReaderOptions`] and passes the line to the visitor. Returns `false`
+    /// if reading must stop.
+    fn push_line(
+        &mut self,
+        options: &ReaderOptions,
+        source: Option<Source>,
+        line: String,
+        first_line: bool,
+        instant: Instant,
+    ) -> Result<bool, UECOError> {
+        if let Some(limit) = options.max_line_bytes {
+            if line.len() > limit {
+                return Err(UECOError::LineTooLong { limit });
+            }
+        }
+        let line = options.process_line(line, first_line);
+        let source = match self.classify {
+            Some(classify) => Some(classify(&line)),
+            None => source,
+        };
+        let line = match options.map_line(source, line) {
+            Some(line) => line,
+            None => return Ok(true),
+        };
+        if !self.budget.admit(options, line.len())? {
+            return Ok(true);
+        }
+        let last = match self.budget.admit_line(options, &line) {
+            Some(last) => last,
+            None => return Ok(true),
+        };
+        self.visitor.visit(source, &line, instant);
+        Ok(!last || options.keep_child_on_stop)
+    }
+}
+
+impl<'a> OutputReader for MultiplexedOutputReader<'a> {
+    fn read_all_bl(&mut self) -> Result<ProcessOutput, UECOError> {
+        let mut collector = CollectingVisitor {
+            tail_limit: self.options.tail_limit(),
+            dedup: Dedup::new(&self.options),
+            stats: self.options.new_line_stats(),
+            ..CollectingVisitor::default()
+        };
+        let (exit_status, stop_reason) = self.read_all_bl_with_visitor(&mut collector)?;
+
+        let separately = matches!(
+            self.strategy,
+            OCatchStrategy::StdSeparately | OCatchStrategy::Multiplexed
+        );
+        let mut stdout = Lines::new();
+        let mut stderr = Lines::new();
+        let mut stdcombined = Lines::new();
+        let mut sources = Vec::with_capacity(collector.lines.len());
+        let mut timestamps = Vec::with_capacity(collector.lines.len());
+        for ((source, instant), line) in &collector.lines {
+            match source {
+                Some(Source::Stdout) if separately => stdout.push(line),
+                Some(Source::Stderr) if separately => stderr.push(line),
+                _ => {}
+            }
+            stdcombined.push(line);
+            if let Some(source) = source {
+                sources.push(*source);
+            }
+            timestamps.push(*instant);
+        }
+        // like SimultaneousOutputReader; the order of the other strategies is exact
+        let (gap, timestamps) = match self.strategy {
+            OCatchStrategy::StdSeparately => (
+                min_interline_gap(&sources, &timestamps),
+                self.options.retain_timestamps.then_some(timestamps),
+            ),
+            _ => (None, None),
+        };
+        let sources = match self.strategy {
+            OCatchStrategy::StdCombined | OCatchStrategy::Pty => None,
+            _ => Some(sources),
+        };
+        Ok(ProcessOutput::new(
+            separately.then_some(stdout),
+            separately.then_some(stderr),
+            stdcombined,
+            sources,
+            exit_status,
+            self.strategy(),
+        )
+        .with_stop_reason(stop_reason)
+        .with_resource_usage(self.child.resource_usage())
+        .with_descendant_output(self.descendant_output)
+        .with_stats(collector.stats)
+        .with_min_interline_gap(gap)
+        .with_timestamps(timestamps))
+    }
+
+    fn strategy(&self) -> OCatchStrategy {
+        self.strategy
+    }
+}
@@ -0,0 +1,188 @@
+//! A child process that was dispatched but whose output wasn't read yet.
+//! See [`crate::Command::spawn`].
+
+use crate::child::{ChildProcess, ProcessState};
+use crate::error::UECOError;
+use crate::exec::join_stdin_writer;
+use crate::label;
+#[cfg(target_os = "linux")]
+use crate::memory::MemorySampler;
+use crate::pipe::Pipe;
+use crate::reader::{
+    MultiplexedOutputReader, OutputReader, ReaderOptions, SimpleOutputReader,
+    SimultaneousOutputReader,
+};
+use crate::{ExitStatus, OCatchStrategy, ProcessOutput};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Handle to the write end of the STDIN pipe of a child, see [`RunningChild::stdin`].
+/// Closing or dropping the handle closes the pipe, so the child reads EOF. Programs
+/// like `cat` or `sort` only exit after that.
+///
+/// Writes block if the pipe is full, i.e. if the child doesn't read its input. A child
+/// that doesn't read its input because it blocks on its own full output pipe deadlocks
+/// with a writer that never reads the output. Therefore, write from a separate thread
+/// while [`RunningChild::wait_with_output`] reads the output, unless the input is small.
+#[derive(Debug)]
+pub struct ChildStdin {
+    pipe: Arc<Mutex<Pipe>>,
+}
+
+impl ChildStdin {
+    /// Constructor. `pipe` must be marked as write end.
+    pub(crate) fn new(pipe: Arc<Mutex<Pipe>>) -> Self {
+        Self { pipe }
+    }
+
+    /// Writes all bytes to STDIN of the child. Blocks if the pipe is full. If the child
+    /// exited or closed its STDIN already, the bytes are discarded.
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<(), UECOError> {
+        self.pipe.lock().unwrap().write_all(bytes)
+    }
+
+    /// Does nothing; the handle doesn't buffer, every write goes directly into the pipe.
+    /// Exists for symmetry with [`std::io::Write`].
+    pub fn flush(&mut self) -> Result<(), UECOError> {
+        Ok(())
+    }
+
+    /// Closes STDIN of the child, so it reads EOF. Same as dropping the handle
+    /// but reports errors.
+    pub fn close(self) -> Result<(), UECOError> {
+        self.pipe.lock().unwrap().close_write_end()
+    }
+}
+
+impl Drop for ChildStdin {
+    /// Closes STDIN of the child if it's still open.
+    fn drop(&mut self) {
+        // errors can't be handled here in a meaningful way
+        let _ = self.pipe.lock().unwrap().close_write_end();
+    }
+}
+
+/// A dispatched child process whose output is read by [`RunningChild::wait_with_output`].
+/// Created by [`crate::Command::spawn`]. If it's dropped before, the child gets killed.
+pub struct RunningChild {
+    /// `None` once the output was read.
+    child: Option<ChildProcess>,
+    stdin: Option<ChildStdin>,
+    stdin_writer: Option<JoinHandle<Result<(), UECOError>>>,
+    resolved_executable: PathBuf,
+    strategy: OCatchStrategy,
+    reader_options: ReaderOptions,
+    #[cfg(target_os = "linux")]
+    memory_sampler: Option<MemorySampler>,
+}
+
+impl RunningChild {
+    /// Constructor. The child must be dispatched.
+    pub(crate) fn new(
+        child: ChildProcess,
+        stdin: Option<ChildStdin>,
+        stdin_writer: Option<JoinHandle<Result<(), UECOError>>>,
+        resolved_executable: PathBuf,
+        strategy: OCatchStrategy,
+        reader_options: ReaderOptions,
+        #[cfg(target_os = "linux")] memory_sampler: Option<MemorySampler>,
+    ) -> Self {
+        Self {
+            child: Some(child),
+            stdin,
+            stdin_writer,
+            resolved_executable,
+            strategy,
+            reader_options,
+            #[cfg(target_os = "linux")]
+            memory_sampler,
+        }
+    }
+
+    /// Takes the handle to STDIN of the child. Returns `None` if it was taken already
+    /// or if the input was set via [`crate::Command::input`].
+    pub fn stdin(&mut self) -> Option<ChildStdin> {
+        self.stdin.take()
+    }
+
+    /// Pid of the child.
+    pub fn pid(&self) -> libc::pid_t {
+        self.child
+            .as_ref()
+            .and_then(ChildProcess::pid)
+            .expect("child is dispatched")
+    }
+
+    /// Checks without blocking whether the child exited. Returns its [`ExitStatus`] if it
+    /// did and `None` if it still runs. The output stays available for
+    /// [`RunningChild::wait_with_output`].
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, UECOError> {
+        let child = self.child.as_mut().expect("child is dispatched");
+        match child.check_state_nbl()? {
+            ProcessState::Running => Ok(None),
+            // returns immediately; the child was reaped already
+            _ => child.wait_bl().map(Some),
+        }
+    }
+
+    /// Waits with a blocking `waitpid()` until the child exited and returns its
+    /// [`ExitStatus`]. The output stays available for [`RunningChild::wait_with_output`].
+    /// The output isn't read in the meantime, so a child that writes more than the
+    /// capacity of the pipe blocks forever; use [`RunningChild::wait_with_output`] then.
+    pub fn wait_blocking(&mut self) -> Result<ExitStatus, UECOError> {
+        self.child.as_mut().expect("child is dispatched").wait_bl()
+    }
+
+    /// Reads all output and waits until the child exited. This is blocking. If the
+    /// handle from [`RunningChild::stdin`] wasn't taken, STDIN of the child gets closed
+    /// first, so it doesn't wait for input forever.
+    pub fn wait_with_output(mut self) -> Result<ProcessOutput, UECOError> {
+        let _label = label::enter(self.reader_options.label.clone());
+        drop(self.stdin.take());
+        let mut child = self.child.take().expect("output is read only once");
+        let reader_options = self.reader_options.clone();
+        let output = match self.strategy {
+            _ if reader_options.needs_chunk_reader() => {
+                MultiplexedOutputReader::new(&mut child, reader_options, self.strategy)
+                    .read_all_bl()
+            }
+            OCatchStrategy::StdCombined
+            | OCatchStrategy::CombinedPrefixed(_)
+            | OCatchStrategy::Pty => {
+                SimpleOutputReader::new(&mut child, reader_options, self.strategy).read_all_bl()
+            }
+            OCatchStrategy::StdSeparately => {
+                SimultaneousOutputReader::new(Arc::new(Mutex::new(child)), reader_options)
+                    .read_all_bl()
+            }
+            OCatchStrategy::Multiplexed => {
+                MultiplexedOutputReader::new(&mut child, reader_options, self.strategy)
+                    .read_all_bl()
+            }
+        };
+        // stop sampling in any case; the child is reaped at this point
+        #[cfg(target_os = "linux")]
+        let peak_rss_kib = self.memory_sampler.take().and_then(MemorySampler::stop);
+        let output = output?;
+        join_stdin_writer(self.stdin_writer.take())?;
+        let output = output.with_resolved_executable(self.resolved_executable.clone());
+        #[cfg(target_os = "linux")]
+        let output = output.with_peak_rss_kib(peak_rss_kib);
+        Ok(output)
+    }
+}
+
+impl Drop for RunningChild {
+    /// Kills the child if the output wasn't read and stops the memory sampling.
+    fn drop(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            // errors can't be handled here in a meaningful way
+            let _ = child.kill();
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(memory_sampler) = self.memory_sampler.take() {
+            memory_sampler.stop();
+        }
+    }
+}
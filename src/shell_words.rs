@@ -0,0 +1,133 @@
+//! Minimal shell-like word splitting for [`crate::Command::parse`] and quoting for
+//! [`crate::Command::login_shell`].
+
+use crate::error::UECOError;
+
+/// Splits a command line into words like a POSIX shell does, but without any evaluation:
+/// no variables, globs, pipes, or redirects. Supported are whitespace as separator,
+/// single quotes (everything literal), double quotes (backslash only escapes
+/// `"`, `\`, `$`, and `` ` ``), and backslash escapes outside of quotes.
+pub(crate) fn split(line: &str) -> Result<Vec<String>, UECOError> {
+    let mut words = vec![];
+    // `None` between words; quotes start a word even if it stays empty
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(invalid("unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            // line continuation
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(invalid("unterminated double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(invalid("unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // line continuation
+                Some('\n') => {}
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(invalid("trailing backslash")),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(word) = word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn invalid(reason: &'static str) -> UECOError {
+    UECOError::InvalidCommandLine { reason }
+}
+
+/// Joins words into a command line that a POSIX shell splits into exactly these words
+/// again, without evaluating anything. The reverse of [`split`].
+pub(crate) fn join<S: AsRef<str>>(words: &[S]) -> String {
+    words
+        .iter()
+        .map(|word| quote(word.as_ref()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes a word with single quotes, unless it only consists of characters that a shell
+/// doesn't interpret. A single quote can't be escaped inside single quotes; it becomes `'\''`.
+fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split(line).unwrap()
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(vec!["ls", "-la"], words("  ls \t -la\n"));
+        assert_eq!(vec!["ls", "some file"], words(r#"ls "some file""#));
+        assert_eq!(vec!["echo", "a b", "c"], words(r"echo a\ b c"));
+        assert_eq!(vec!["echo", "$HOME \\n"], words(r"echo '$HOME \n'"));
+        assert_eq!(vec!["echo", "\"$`\\ \\n"], words(r#"echo "\"\$\`\\ \n""#));
+        assert_eq!(vec!["echo", "", "x"], words(r#"echo "" ''x"#));
+        assert_eq!(vec!["echo", "abc"], words(r#"echo a"b"'c'"#));
+        assert_eq!(vec!["echo", "ab"], words("echo a\\\nb"));
+        assert_eq!(Vec::<String>::new(), words("   "));
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!("ls -la", join(&["ls", "-la"]));
+        assert_eq!(
+            "echo '' 'a b' '$HOME' 'it'\\''s'",
+            join(&["echo", "", "a b", "$HOME", "it's"])
+        );
+        let words = ["echo", "a \"b\" \\n", "'", "*"];
+        assert_eq!(words.to_vec(), self::words(&join(&words)));
+    }
+
+    #[test]
+    fn test_split_errors() {
+        for line in ["echo 'a", "echo \"a", "echo \"a\\", "echo a\\"] {
+            assert!(
+                matches!(split(line), Err(UECOError::InvalidCommandLine { .. })),
+                "{}",
+                line
+            );
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Async consumption of the output as a [`Stream`]. Requires the feature `tokio`.
+
+use crate::child::ChildProcess;
+use crate::error::UECOError;
+use crate::exec::join_stdin_writer;
+use crate::line_reader::OutputLine;
+use crate::pipe::Pipe;
+use crate::reader::ReaderOptions;
+use crate::{ExitStatus, OCatchStrategy, Source};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+use std::time::Instant;
+use tokio::io::unix::AsyncFd;
+
+/// Size of the buffer for a single `read()`.
+const READ_BUF_LEN: usize = 4096;
+
+/// Pipe that is read by the [`OutputStream`] together with its state.
+struct StreamPipe {
+    /// `None` if both streams share the pipe.
+    source: Option<Source>,
+    /// Registration of the read end at the tokio reactor. Declared before `pipe`
+    /// so that it gets deregistered before the file descriptor gets closed.
+    fd: AsyncFd<RawFd>,
+    pipe: Arc<Mutex<Pipe>>,
+    /// Bytes of the current, not yet finished line.
+    buf: Vec<u8>,
+    /// How many bytes of `buf` were already searched for a newline.
+    scanned: usize,
+    /// Whether no line of this pipe was passed on yet.
+    first_line: bool,
+    /// Whether the last byte that was read was a newline.
+    after_newline: bool,
+    eof: bool,
+}
+
+type WaitHandle = tokio::task::JoinHandle<Result<ExitStatus, UECOError>>;
+
+/// Output of a child process as [`Stream`] of [`OutputLine`]s, see [`crate::Command::stream`].
+/// The pipes are read without blocking via the tokio reactor. After all lines,
+/// [`OutputLine::Eof`] carries the exit status. If the stream gets dropped early,
+/// the child gets killed.
+pub struct OutputStream {
+    /// `None` once it was moved into the task that reaps it.
+    child: Option<ChildProcess>,
+    stdin_writer: Option<JoinHandle<Result<(), UECOError>>>,
+    pipes: Vec<StreamPipe>,
+    strategy: OCatchStrategy,
+    options: ReaderOptions,
+    /// Lines that were read but not yet returned.
+    pending: VecDeque<OutputLine>,
+    /// Sequence number of the next line.
+    next_seq: u64,
+    /// Task that reaps the child after all pipes reached EOF.
+    wait: Option<WaitHandle>,
+    finished: bool,
+}
+
+impl OutputStream {
+    /// Constructor. Must be called inside a tokio runtime.
+    pub(crate) fn new(
+        mut child: ChildProcess,
+        stdin_writer: Option<JoinHandle<Result<(), UECOError>>>,
+        strategy: OCatchStrategy,
+        options: ReaderOptions,
+    ) -> Result<Self, UECOError> {
+        let pipes = match strategy {
+            // in this case stdout and stderr both use the same pipe
+            OCatchStrategy::StdCombined
+            | OCatchStrategy::CombinedPrefixed(_)
+            | OCatchStrategy::Pty => {
+                vec![(None, child.stdout_pipe().clone())]
+            }
+            OCatchStrategy::StdSeparately | OCatchStrategy::Multiplexed => vec![
+                (Some(Source::Stdout), child.stdout_pipe().clone()),
+                (Some(Source::Stderr), child.stderr_pipe().clone()),
+            ],
+        };
+        let pipes = pipes
+            .into_iter()
+            .map(|(source, pipe)| {
+                let fd = {
+                    let pipe = pipe.lock().unwrap();
+                    pipe.set_read_end_nonblocking()?;
+                    pipe.read_fd()
+                };
+                let fd = AsyncFd::new(fd).map_err(|err| UECOError::AsyncRegistrationFailed {
+                    errno: err.raw_os_error().unwrap_or(0),
+                })?;
+                Ok(StreamPipe {
+                    source,
+                    fd,
+                    pipe,
+                    buf: Vec::new(),
+                    scanned: 0,
+                    first_line: true,
+                    after_newline: false,
+                    eof: false,
+                })
+            })
+            .collect::<Result<Vec<StreamPipe>, UECOError>>();
+        let pipes = match pipes {
+            Ok(pipes) => pipes,
+            Err(err) => {
+                // don't leave the child behind
+                child.kill()?;
+                return Err(err);
+            }
+        };
+        Ok(Self {
+            child: Some(child),
+            stdin_writer,
+            pipes,
+            strategy,
+            options,
+            pending: VecDeque::new(),
+            next_seq: 0,
+            wait: None,
+            finished: false,
+        })
+    }
+
+    /// Reads from all pipes that are ready. Returns `Poll::Ready` if there was progress,
+    /// i.e. data or EOF was read.
+    fn poll_read_pipes(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), UECOError>> {
+        let mut progress = false;
+        for i in 0..self.pipes.len() {
+            if self.pipes[i].eof {
+                continue;
+            }
+            let res = loop {
+                let mut guard = match self.pipes[i].fd.poll_read_ready(cx) {
+                    Poll::Ready(Ok(guard)) => guard,
+                    Poll::Ready(Err(err)) => {
+                        break Err(UECOError::AsyncRegistrationFailed {
+                            errno: err.raw_os_error().unwrap_or(0),
+                        })
+                    }
+                    Poll::Pending => break Ok(None),
+                };
+                match self.pipes[i].pipe.lock().unwrap().read_chunk(READ_BUF_LEN) {
+                    // spurious wakeup; poll again to wait for the next readiness event
+                    Err(UECOError::ReadFailed { errno }) if errno == libc::EAGAIN => {
+                        guard.clear_ready()
+                    }
+                    res => break res.map(Some),
+                }
+            };
+            match res? {
+                // not ready
+                None => continue,
+                Some(Some(bytes)) => {
+                    self.pipes[i].after_newline = bytes.last() == Some(&b'\n');
+                    self.pipes[i].buf.extend(bytes);
+                    self.split_lines(i, false)?;
+                }
+                Some(None) => {
+                    self.pipes[i].eof = true;
+                    self.split_lines(i, true)?;
+                }
+            }
+            progress = true;
+        }
+        if progress {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Moves all complete lines of the buffer of the given pipe to `pending`.
+    /// At EOF, the remaining bytes are a line too.
+    fn split_lines(&mut self, pipe_index: usize, eof: bool) -> Result<(), UECOError> {
+        let source = self.pipes[pipe_index].source;
+        let after_newline = self.pipes[pipe_index].after_newline;
+        let pipe = &mut self.pipes[pipe_index];
+        let buf = &mut pipe.buf;
+        let mut texts = vec![];
+        // remove the complete lines at once instead of one by one
+        let mut consumed = 0;
+        // don't search the start of a long line again for every chunk
+        let mut skip = pipe.scanned;
+        while let Some(pos) = memchr::memchr(b'\n', &buf[consumed + skip..]) {
+            let pos = skip + pos;
+            texts.push(String::from_utf8_lossy(&buf[consumed..consumed + pos]).into_owned());
+            consumed += pos + 1;
+            skip = 0;
+        }
+        buf.drain(..consumed);
+        pipe.scanned = buf.len();
+        if let Some(limit) = self.options.max_line_bytes {
+            if buf.len() > limit {
+                return Err(UECOError::LineTooLong { limit });
+            }
+        }
+        if eof && (!buf.is_empty() || (after_newline && self.options.keep_trailing_empty_line)) {
+            texts.push(String::from_utf8_lossy(buf).into_owned());
+            buf.clear();
+            pipe.scanned = 0;
+        }
+        for text in texts {
+            let first_line = std::mem::replace(&mut self.pipes[pipe_index].first_line, false);
+            self.push_pending(source, text, first_line);
+        }
+        Ok(())
+    }
+
+    /// Applies the [`ReaderOptions`], determines the [`Source`] of a line, and
+    /// appends it to `pending` unless [`ReaderOptions::map_line`] drops it.
+    fn push_pending(&mut self, source: Option<Source>, text: String, first_line: bool) {
+        let text = self.options.process_line(text, first_line);
+        let source = match self.strategy {
+            OCatchStrategy::CombinedPrefixed(classify) => Some(classify(&text)),
+            _ => source,
+        };
+        let text = match self.options.map_line(source, text) {
+            Some(text) => text,
+            None => return,
+        };
+        self.pending.push_back(OutputLine::Line {
+            seq: self.next_seq,
+            text,
+            source,
+            timestamp: Instant::now(),
+        });
+        self.next_seq += 1;
+    }
+
+    /// Reaps the child in a blocking task once all pipes reached EOF.
+    fn poll_wait(&mut self, cx: &mut Context<'_>) -> Poll<Result<ExitStatus, UECOError>> {
+        if self.wait.is_none() {
+            let mut child = self.child.take().expect("child is reaped only once");
+            let stdin_writer = self.stdin_writer.take();
+            self.wait.replace(tokio::task::spawn_blocking(move || {
+                let exit_status = child.wait_bl()?;
+                join_stdin_writer(stdin_writer)?;
+                Ok(exit_status)
+            }));
+        }
+        let wait = self.wait.as_mut().unwrap();
+        match Pin::new(wait).poll(cx) {
+            Poll::Ready(res) => Poll::Ready(res.unwrap_or(Err(UECOError::Unknown))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Kills the child after an error.
+    fn fail(&mut self, err: UECOError) -> Poll<Option<Result<OutputLine, UECOError>>> {
+        self.finished = true;
+        if let Some(child) = self.child.as_mut() {
+            if let Err(kill_err) = child.kill() {
+                return Poll::Ready(Some(Err(kill_err)));
+            }
+        }
+        Poll::Ready(Some(Err(err)))
+    }
+}
+
+impl Stream for OutputStream {
+    type Item = Result<OutputLine, UECOError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(line) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(line)));
+            }
+            if self.finished {
+                return Poll::Ready(None);
+            }
+            if self.pipes.iter().all(|pipe| pipe.eof) {
+                return match self.poll_wait(cx) {
+                    Poll::Ready(Ok(exit_status)) => {
+                        self.finished = true;
+                        Poll::Ready(Some(Ok(OutputLine::Eof { exit_status })))
+                    }
+                    Poll::Ready(Err(err)) => {
+                        self.finished = true;
+                        Poll::Ready(Some(Err(err)))
+                    }
+                    Poll::Pending => Poll::Pending,
+                };
+            }
+            match self.poll_read_pipes(cx) {
+                Poll::Ready(Ok(())) => continue,
+                Poll::Ready(Err(err)) => return self.fail(err),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Drop for OutputStream {
+    /// Kills the child if the stream wasn't consumed completely.
+    fn drop(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            // errors can't be handled here in a meaningful way
+            let _ = child.kill();
+        }
+    }
+}
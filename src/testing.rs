@@ -0,0 +1,71 @@
+//! Hooks that make internals of the crate testable in a deterministic way.
+//! Only available with the feature `test-hooks`. Not part of the stable API.
+
+use crate::child::PreExecHook;
+use crate::error::UECOError;
+use crate::{Command, OCatchStrategy, ProcessOutput};
+use std::cell::Cell;
+
+/// Combines the lines of STDOUT and STDERR exactly like [`crate::OCatchStrategy::StdSeparately`]
+/// does, but with explicit sequence numbers instead of the timestamps of the reader threads.
+/// This way a test can verify the combining logic with known interleavings, independent of
+/// the scheduling of the child and the reader threads.
+/// * `stdout` lines of STDOUT with their sequence number, in the order they were read
+/// * `stderr` lines of STDERR with their sequence number, in the order they were read
+pub fn combine_with_sequence(stdout: &[(u64, &str)], stderr: &[(u64, &str)]) -> Vec<String> {
+    crate::reader::combine_by_timestamp(stdout, stderr)
+        .0
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Forks a child that runs `f` instead of executing a program and catches its output with
+/// `strategy`. The parent starts reading when `f` starts, like after exec(), and the child
+/// exits with code 0 once `f` returns. This way a test controls exactly what the child
+/// writes and when, without an external test binary. The
+/// [`ProcessOutput::resolved_executable`] is meaningless.
+///
+/// # Safety
+/// `f` runs in the forked child like the closure of [`Command::pre_exec`], so the same
+/// restrictions apply. Especially, `println!()` may deadlock if another thread held the
+/// lock of STDOUT during fork(); write to the file descriptors with `libc::write()` instead.
+pub unsafe fn fork_closure_and_catch<F>(
+    f: F,
+    strategy: OCatchStrategy,
+) -> Result<ProcessOutput, UECOError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    // the executable must exist, but it's never executed
+    Command::new("true")
+        .strategy(strategy)
+        .run_instead_of_exec(PreExecHook::new(move || {
+            f();
+            Ok(())
+        }))
+        .catch()
+}
+
+thread_local! {
+    /// Number of the next calls of `fork()` on this thread that fail, see [`fail_next_forks`].
+    static INJECTED_FORK_FAILURES: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Makes the next `n` attempts to fork a child on the current thread fail with `EAGAIN`,
+/// as if the process limit was reached. This way a test can verify the handling of
+/// transient errors, see [`crate::Command::retries`].
+pub fn fail_next_forks(n: u32) {
+    INJECTED_FORK_FAILURES.with(|failures| failures.set(n));
+}
+
+/// Consumes one injected failure of [`fail_next_forks`]. Returns whether `fork()` must fail.
+pub(crate) fn take_injected_fork_failure() -> bool {
+    INJECTED_FORK_FAILURES.with(|failures| {
+        let n = failures.get();
+        if n > 0 {
+            failures.set(n - 1);
+        }
+        n > 0
+    })
+}
@@ -0,0 +1,29 @@
+#![cfg(target_os = "linux")]
+
+use unix_exec_output_catcher::Command;
+
+/// Prints the args of the shell itself, one per line.
+const PRINT_OWN_ARGS: &str = "tr '\\0' '\\n' < /proc/$$/cmdline";
+
+#[test]
+fn arg0_reaches_exec() {
+    let res = Command::new("sh")
+        .arg0("multi-call-tool")
+        .args(["-c", PRINT_OWN_ARGS])
+        .catch()
+        .unwrap();
+    let stdout = res.stdout().unwrap();
+    assert_eq!(Some("multi-call-tool"), stdout.get(0));
+    assert_eq!(Some("-c"), stdout.get(1));
+    // the executable is still resolved from the original name
+    assert!(res.resolved_executable().ends_with("sh"));
+}
+
+#[test]
+fn arg0_defaults_to_executable() {
+    let res = Command::new("sh")
+        .args(["-c", PRINT_OWN_ARGS])
+        .catch()
+        .unwrap();
+    assert_eq!(Some("sh"), res.stdout().unwrap().get(0));
+}
@@ -0,0 +1,47 @@
+use std::convert::TryFrom;
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{capture, fork_exec_and_catch, Command, OCatchStrategy};
+
+#[test]
+fn args_from_different_collections() {
+    let res = fork_exec_and_catch("echo", ["echo", "array"], OCatchStrategy::StdCombined).unwrap();
+    assert_eq!("array", res.stdcombined_lines()[0].as_str());
+
+    let args = vec!["echo".to_string(), "strings".to_string()];
+    let res = fork_exec_and_catch("echo", &args, OCatchStrategy::StdCombined).unwrap();
+    assert_eq!("strings", res.stdcombined_lines()[0].as_str());
+
+    let args = "echo from iter".split(' ');
+    assert_eq!("from iter", capture("echo", args).unwrap());
+
+    let res = Command::new("echo")
+        .args(&["slice"][..])
+        .args((1..=2).map(|i| i.to_string()))
+        .catch()
+        .unwrap();
+    assert_eq!("slice 1 2", res.stdout_lines().unwrap()[0].as_str());
+}
+
+#[test]
+fn parse_command_line() {
+    let res = Command::parse(r#"sh -c 'echo "$0" "$1"' "some file" a\ b"#)
+        .unwrap()
+        .catch()
+        .unwrap();
+    assert_eq!(Some("some file a b"), res.stdout().unwrap().get(0));
+
+    let res = Command::try_from("printf '%s|' \"\" x")
+        .unwrap()
+        .catch()
+        .unwrap();
+    assert_eq!(Some("|x|"), res.stdout().unwrap().get(0));
+
+    assert!(matches!(
+        Command::parse("echo 'oops"),
+        Err(UECOError::InvalidCommandLine { .. })
+    ));
+    assert!(matches!(
+        Command::parse(" "),
+        Err(UECOError::EmptyExecutable)
+    ));
+}
@@ -0,0 +1,30 @@
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::{catch_in_background, Command, OCatchStrategy};
+
+#[test]
+fn catch_in_background_returns_immediately() {
+    let start = Instant::now();
+    let handle = Command::new("sh")
+        .args(vec!["-c", "sleep 0.3; echo done; exit 5"])
+        .catch_in_background()
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_millis(300));
+    assert!(handle.pid() > 0);
+    assert!(!handle.is_finished());
+    let res = handle.output().unwrap();
+    assert_eq!(5, res.exit_code());
+    assert_eq!(Some("done"), res.stdout().unwrap().get(0));
+}
+
+#[test]
+fn catch_in_background_large_output() {
+    // more than the capacity of the pipe; only works because the thread drains it
+    let handle = catch_in_background(
+        "sh",
+        ["sh", "-c", "seq 1 100000"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    let res = handle.output().unwrap();
+    assert_eq!(100000, res.stdcombined().len());
+}
@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{batch_run, batch_run_with_context, Command, OCatchStrategy};
+
+#[test]
+fn batch_run_keeps_order() {
+    let commands = (0..20)
+        .map(|i| {
+            let mut command = Command::new("sh");
+            // later commands finish first
+            command
+                .args(["-c", &format!("sleep 0.0{}; echo {}", 9 - i % 10, i)])
+                .strategy(OCatchStrategy::StdCombined);
+            command
+        })
+        .collect::<Vec<_>>();
+    let results = batch_run(commands, 4);
+    assert_eq!(20, results.len());
+    for (i, res) in results.iter().enumerate() {
+        let res = res.as_ref().unwrap();
+        assert_eq!(
+            vec![i.to_string()],
+            res.stdcombined().iter().collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn batch_run_runs_in_parallel() {
+    let commands = (0..8)
+        .map(|_| {
+            let mut command = Command::new("sleep");
+            command.arg("0.5");
+            command
+        })
+        .collect::<Vec<_>>();
+    let begin = Instant::now();
+    let results = batch_run(commands, 8);
+    // sequentially, this would take 4s
+    assert!(begin.elapsed() < Duration::from_secs(3));
+    assert!(results
+        .iter()
+        .all(|res| res.as_ref().unwrap().exit_status().success()));
+}
+
+#[test]
+fn batch_run_failing_command() {
+    let results = batch_run(
+        vec![
+            Command::new("true"),
+            Command::new("does-not-exist-ueco"),
+            Command::new("false"),
+        ],
+        0,
+    );
+    assert!(results[0].as_ref().unwrap().exit_status().success());
+    assert!(matches!(
+        results[1],
+        Err(UECOError::ExecutableNotFound { .. })
+    ));
+    assert_eq!(1, results[2].as_ref().unwrap().exit_code());
+}
+
+#[test]
+fn batch_run_with_context_correlates_results() {
+    // the context doesn't need to be Send
+    let jobs = ["b", "a", "c"]
+        .iter()
+        .map(|name| {
+            let mut command = Command::new("echo");
+            command.arg(name);
+            (std::rc::Rc::new(name.to_string()), command)
+        })
+        .collect::<Vec<_>>();
+    for res in batch_run_with_context(jobs, 2) {
+        let output = res.output.unwrap();
+        assert_eq!(Some(res.context.as_str()), output.stdcombined().get(0));
+    }
+
+    let res = Command::new("false").catch_with_context(42);
+    assert_eq!(42, res.context);
+    assert_eq!(1, res.output.unwrap().exit_code());
+}
@@ -0,0 +1,29 @@
+use unix_exec_output_catcher::{catch, OCatchStrategy};
+
+#[test]
+fn catch_positional_args() {
+    let res = catch!("echo", "hello", "world").unwrap();
+    assert_eq!(Some("hello world"), res.stdout().unwrap().get(0));
+    let res = catch!("true").unwrap();
+    assert!(res.exit_status().success());
+}
+
+#[test]
+fn catch_options() {
+    let script = String::from("echo $FOO; echo err >&2");
+    let res = catch!(
+        "sh",
+        "-c",
+        args = [script],
+        strategy = StdCombined,
+        env = [("FOO", "bar")],
+    )
+    .unwrap();
+    assert!(matches!(res.strategy(), OCatchStrategy::StdCombined));
+    let lines = res
+        .stdcombined_lines()
+        .iter()
+        .map(|line| line.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(vec!["bar", "err"], lines);
+}
@@ -0,0 +1,59 @@
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{Catcher, Command, ForkCatcher, MockCatcher, OCatchStrategy};
+
+/// Code under test that only knows the trait.
+fn first_line(catcher: &dyn Catcher, command: &Command) -> Result<String, UECOError> {
+    let res = catcher.catch(command)?;
+    Ok(res
+        .stdcombined()
+        .get(0)
+        .map(|line| line.to_string())
+        .unwrap_or_default())
+}
+
+#[test]
+fn mock_catcher_replays_recording() {
+    let mut command = Command::new("echo");
+    command.arg("hello").strategy(OCatchStrategy::StdSeparately);
+    let recorded = ForkCatcher.catch(&command).unwrap();
+
+    let mut mock = MockCatcher::new();
+    mock.record(&command, recorded);
+    assert_eq!("hello", first_line(&mock, &command).unwrap());
+    // can be replayed multiple times
+    assert_eq!("hello", first_line(&mock, &command).unwrap());
+
+    let other = Command::new("ls");
+    assert!(matches!(
+        first_line(&mock, &other),
+        Err(UECOError::NoRecordedOutput { .. })
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn fixture_round_trip() {
+    use unix_exec_output_catcher::{ExitStatus, ProcessOutput};
+
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo out; echo err >&2; exit 4"])
+        .strategy(OCatchStrategy::StdSeparately)
+        .catch()
+        .unwrap();
+    let path = std::env::temp_dir().join(format!("ueco-fixture-{}.json", std::process::id()));
+    res.save_fixture(&path).unwrap();
+    let loaded = ProcessOutput::load_fixture(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(ExitStatus::Exited(4), loaded.exit_status());
+    assert_eq!(res.stdout(), loaded.stdout());
+    assert_eq!(res.stderr(), loaded.stderr());
+    assert_eq!(res.stdcombined(), loaded.stdcombined());
+    assert_eq!(res.resolved_executable(), loaded.resolved_executable());
+    assert_eq!(res.resource_usage(), loaded.resource_usage());
+
+    assert!(matches!(
+        ProcessOutput::from_fixture("{}"),
+        Err(UECOError::InvalidFixture { .. })
+    ));
+}
@@ -0,0 +1,13 @@
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+#[test]
+fn exec_failure_is_reported_to_parent() {
+    let res = Command::new("/nonexistent/bin/foo")
+        .strategy(OCatchStrategy::StdCombined)
+        .catch();
+    match res {
+        Err(UECOError::ExecvpFailed { errno }) => assert_eq!(libc::ENOENT, errno),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,42 @@
+use std::env;
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn chroot_changes_working_directory() {
+    if unsafe { libc::geteuid() } != 0 {
+        // requires privileges
+        return;
+    }
+    let res = Command::new("pwd").chroot("/").catch().unwrap();
+    assert_eq!(Some("/"), res.stdout().unwrap().get(0));
+}
+
+#[test]
+fn chroot_looks_up_executable_in_new_root() {
+    let root = env::temp_dir();
+    assert!(matches!(
+        Command::new("ueco-not-in-root").chroot(&root).catch(),
+        Err(UECOError::ExecutableNotFound { .. })
+    ));
+}
+
+#[test]
+fn chroot_failed() {
+    assert!(matches!(
+        Command::new("/bin/true")
+            .chroot("/ueco-does-not-exist")
+            .catch(),
+        Err(UECOError::ChrootFailed {
+            errno: libc::ENOENT
+        })
+    ));
+}
+
+#[test]
+fn chroot_with_null_byte() {
+    match Command::new("true").chroot("/tmp\0/root").catch() {
+        Err(UECOError::ContainsNul { what }) => assert_eq!("root directory", what),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
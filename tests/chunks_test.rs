@@ -0,0 +1,67 @@
+use unix_exec_output_catcher::{Command, ExitStatus, OCatchStrategy, Source};
+
+#[test]
+fn catch_chunks_combined() {
+    let mut output = vec![];
+    let exit_status = Command::new("sh")
+        .args(vec![
+            "-c",
+            "printf 'no\\nnewline at'; printf ' the end' >&2; exit 4",
+        ])
+        .strategy(OCatchStrategy::StdCombined)
+        .catch_chunks(3, |source, chunk| {
+            assert_eq!(None, source);
+            assert!(chunk.len() <= 3);
+            output.extend(chunk);
+        })
+        .unwrap();
+    assert_eq!(ExitStatus::Exited(4), exit_status);
+    assert_eq!(b"no\nnewline at the end".to_vec(), output);
+}
+
+#[test]
+fn catch_chunks_separately() {
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    let exit_status = Command::new("sh")
+        .args(vec!["-c", "printf '\\000\\001\\002'; printf '\\377' >&2"])
+        .strategy(OCatchStrategy::StdSeparately)
+        .catch_chunks(4096, |source, chunk| match source {
+            Some(Source::Stdout) => stdout.extend(chunk),
+            Some(Source::Stderr) => stderr.extend(chunk),
+            None => panic!("source must be known"),
+        })
+        .unwrap();
+    assert!(exit_status.success());
+    assert_eq!(vec![0, 1, 2], stdout);
+    assert_eq!(vec![255], stderr);
+}
+
+#[test]
+fn catch_read_groups() {
+    let (exit_status, groups) = Command::new("sh")
+        .args(vec!["-c", "printf 'a\\nb\\n'; sleep 0.2; printf 'c' >&2"])
+        .strategy(OCatchStrategy::StdSeparately)
+        .catch_read_groups()
+        .unwrap();
+    assert!(exit_status.success());
+    assert_eq!(
+        vec![
+            (Some(Source::Stdout), b"a\nb\n".to_vec()),
+            (Some(Source::Stderr), b"c".to_vec())
+        ],
+        groups
+    );
+}
+
+#[test]
+fn pipe_capacity() {
+    let mut bytes = 0;
+    let exit_status = Command::new("head")
+        .args(["-c", "1000000", "/dev/zero"])
+        .pipe_capacity(1 << 20)
+        .catch_chunks(1 << 16, |_, chunk| bytes += chunk.len())
+        .unwrap();
+    assert!(exit_status.success());
+    assert_eq!(1000000, bytes);
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "test-hooks")]
+
+use unix_exec_output_catcher::testing::combine_with_sequence;
+
+#[test]
+fn combine_interleaved() {
+    let stdout = [(1, "out 1"), (3, "out 3"), (4, "out 4"), (7, "out 7")];
+    let stderr = [(2, "err 2"), (5, "err 5"), (6, "err 6")];
+    assert_eq!(
+        vec!["out 1", "err 2", "out 3", "out 4", "err 5", "err 6", "out 7"],
+        combine_with_sequence(&stdout, &stderr)
+    );
+}
+
+#[test]
+fn combine_one_stream_empty() {
+    let stdout = [(1, "out 1"), (2, "out 2")];
+    assert_eq!(vec!["out 1", "out 2"], combine_with_sequence(&stdout, &[]));
+    assert_eq!(vec!["out 1", "out 2"], combine_with_sequence(&[], &stdout));
+}
+
+#[test]
+fn combine_equal_timestamps_keeps_all_lines() {
+    let stdout = [(1, "out a"), (1, "out b")];
+    let stderr = [(1, "err a"), (2, "err b")];
+    assert_eq!(
+        vec!["out a", "out b", "err a", "err b"],
+        combine_with_sequence(&stdout, &stderr)
+    );
+}
@@ -0,0 +1,45 @@
+use unix_exec_output_catcher::{Command, OCatchStrategy, Source};
+
+fn classify(line: &str) -> Source {
+    if line.starts_with("error:") {
+        Source::Stderr
+    } else {
+        Source::Stdout
+    }
+}
+
+#[test]
+fn combined_prefixed() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo a; echo 'error: b' >&2; echo c"])
+        .strategy(OCatchStrategy::CombinedPrefixed(classify))
+        .catch()
+        .unwrap();
+    assert_eq!(
+        Some(vec![
+            (Source::Stdout, "a"),
+            (Source::Stderr, "error: b"),
+            (Source::Stdout, "c")
+        ]),
+        res.stdcombined_tagged()
+    );
+    assert!(res.stdout_lines().is_none());
+}
+
+#[test]
+fn classify_combined() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo a; echo 'error: b' >&2; echo c"])
+        .strategy(OCatchStrategy::StdCombined)
+        .catch()
+        .unwrap();
+    let (stdout, stderr) = res.classify_combined(classify);
+    assert_eq!(
+        vec!["a", "c"],
+        stdout.iter().map(|l| l.as_str()).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["error: b"],
+        stderr.iter().map(|l| l.as_str()).collect::<Vec<_>>()
+    );
+}
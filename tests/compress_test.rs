@@ -0,0 +1,25 @@
+#![cfg(feature = "flate2")]
+
+use flate2::read::GzDecoder;
+use std::io::Read;
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+#[test]
+fn catch_compressed() {
+    let mut archive = vec![];
+    let (exit_status, size) = Command::new("sh")
+        .args(vec!["-c", "seq 1000; echo err >&2; exit 3"])
+        .strategy(OCatchStrategy::StdCombined)
+        .catch_compressed(&mut archive)
+        .unwrap();
+    assert_eq!(3, exit_status.code());
+    assert_eq!(archive.len() as u64, size);
+
+    let mut output = String::new();
+    GzDecoder::new(archive.as_slice())
+        .read_to_string(&mut output)
+        .unwrap();
+    let expected = (1..=1000).map(|i| format!("{}\n", i)).collect::<String>() + "err\n";
+    assert_eq!(expected, output);
+    assert!(size < output.len() as u64);
+}
@@ -0,0 +1,21 @@
+#![cfg(target_os = "linux")]
+
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn cpu_affinity() {
+    let res = Command::new("grep")
+        .args(vec!["Cpus_allowed_list", "/proc/self/status"])
+        .cpu_affinity(vec![0])
+        .catch()
+        .unwrap();
+    let line = res.stdout().unwrap().iter().next().unwrap();
+    assert_eq!("0", line.split_whitespace().last().unwrap());
+}
+
+#[test]
+fn cpu_affinity_invalid_cpu() {
+    let res = Command::new("true").cpu_affinity(vec![1 << 20]).catch();
+    assert!(matches!(res, Err(UECOError::SetAffinityFailed { .. })));
+}
@@ -0,0 +1,43 @@
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+const SCRIPT: &str = "echo a; echo a; echo a; echo b; echo a; echo a; echo c";
+
+#[test]
+fn dedup_consecutive() {
+    for strategy in [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Multiplexed,
+    ] {
+        let res = Command::new("sh")
+            .args(vec!["-c", SCRIPT])
+            .strategy(strategy)
+            .dedup_consecutive(true)
+            .catch()
+            .unwrap();
+        assert_eq!(
+            vec!["a", "b", "a", "c"],
+            res.stdcombined().iter().collect::<Vec<_>>(),
+            "{}",
+            strategy
+        );
+    }
+}
+
+#[test]
+fn dedup_count_suffix() {
+    let res = Command::new("sh")
+        .args(vec!["-c", SCRIPT])
+        .dedup_consecutive(true)
+        .dedup_count_suffix(true)
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["a (repeated 3x)", "b", "a (repeated 2x)", "c"],
+        res.stdout().unwrap().iter().collect::<Vec<_>>()
+    );
+
+    // disabled by default
+    let res = Command::new("sh").args(vec!["-c", SCRIPT]).catch().unwrap();
+    assert_eq!(7, res.stdout().unwrap().len());
+}
@@ -0,0 +1,18 @@
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn detach_tty_creates_new_session() {
+    // a session leader has the same session id as its pid
+    let script = "echo $$; ps -o sid= -p $$";
+    let res = Command::new("sh")
+        .args(vec!["-c", script])
+        .detach_tty(true)
+        .catch()
+        .unwrap();
+    let lines = res.stdout().unwrap();
+    assert_eq!(lines.get(0).unwrap().trim(), lines.get(1).unwrap().trim());
+
+    let res = Command::new("sh").args(vec!["-c", script]).catch().unwrap();
+    let lines = res.stdout().unwrap();
+    assert_ne!(lines.get(0).unwrap().trim(), lines.get(1).unwrap().trim());
+}
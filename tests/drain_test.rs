@@ -0,0 +1,22 @@
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+/// Writes a burst of output to both streams and exits right away. All of it must be
+/// read even though the child is gone before the reader sees EOF.
+const BURST: &str = "seq 1 5000; seq 1 5000 >&2; exit 0";
+
+#[test]
+fn drain_after_exit() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately] {
+        for _ in 0..10 {
+            let res = Command::new("sh")
+                .args(vec!["-c", BURST])
+                .strategy(strategy)
+                // check the state as often as possible to provoke the race
+                .state_poll_interval(std::time::Duration::ZERO)
+                .catch()
+                .unwrap();
+            assert!(res.exit_status().success());
+            assert_eq!(10000, res.stdcombined().len());
+        }
+    }
+}
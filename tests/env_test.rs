@@ -0,0 +1,73 @@
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{Command, ReproducibleEnv};
+
+#[test]
+fn env_clear() {
+    let res = Command::new("env")
+        .env_clear()
+        .env("FOO", "bar")
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["FOO=bar"],
+        res.stdout().unwrap().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn env_clear_with_path() {
+    let res = Command::new("env")
+        .env_clear()
+        .env("PATH", "/this/does/not/exist")
+        .catch();
+    // the lookup uses PATH of the child
+    assert!(matches!(res, Err(UECOError::ExecutableNotFound { .. })));
+}
+
+#[test]
+fn env_inherited_and_overridden() {
+    std::env::set_var("UECO_ENV_TEST", "parent");
+    let res = Command::new("env")
+        .env("UECO_ENV_TEST", "child")
+        .catch()
+        .unwrap();
+    let env = res.stdout().unwrap().iter().collect::<Vec<&str>>();
+    assert!(env.contains(&"UECO_ENV_TEST=child"));
+    assert!(!env.contains(&"UECO_ENV_TEST=parent"));
+    assert!(env.iter().any(|var| var.starts_with("PATH=")));
+}
+
+#[test]
+fn reproducible_env() {
+    std::env::set_var("UECO_KEEP_TEST", "kept");
+    std::env::set_var("UECO_DROP_TEST", "dropped");
+    let res = Command::new("sh")
+        .args(vec!["-c", "umask; env"])
+        .reproducible_env(ReproducibleEnv {
+            umask: 0o077,
+            keep: vec!["UECO_KEEP_TEST".to_string()],
+            ..ReproducibleEnv::default()
+        })
+        .catch()
+        .unwrap();
+    let lines = res.stdout().unwrap().iter().collect::<Vec<&str>>();
+    assert_eq!("0077", lines[0]);
+    for var in [
+        "PATH=/usr/bin:/bin",
+        "LC_ALL=C",
+        "TZ=UTC",
+        "UECO_KEEP_TEST=kept",
+    ] {
+        assert!(lines.contains(&var), "{} missing in {:?}", var, lines);
+    }
+    assert!(!lines.iter().any(|var| var.starts_with("UECO_DROP_TEST=")));
+    assert!(!lines.iter().any(|var| var.starts_with("HOME=")));
+}
+
+#[test]
+fn env_with_null_byte() {
+    match Command::new("true").env("KEY", "va\0lue").catch() {
+        Err(UECOError::ContainsNul { what }) => assert_eq!("environment", what),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
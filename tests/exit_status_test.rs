@@ -0,0 +1,92 @@
+use unix_exec_output_catcher::error::CaptureError;
+use unix_exec_output_catcher::{capture, fork_exec_and_catch, ExitStatus, OCatchStrategy};
+
+#[test]
+fn exit_status_exited() {
+    let res = fork_exec_and_catch(
+        "sh",
+        vec!["sh", "-c", "exit 3"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    assert_eq!(ExitStatus::Exited(3), res.exit_status());
+    assert_eq!(3, res.exit_code());
+}
+
+#[test]
+fn exit_status_signaled() {
+    let res = fork_exec_and_catch(
+        "sh",
+        vec!["sh", "-c", "kill -9 $$"],
+        OCatchStrategy::StdSeparately,
+    )
+    .unwrap();
+    assert_eq!(
+        ExitStatus::Signaled {
+            signal: libc::SIGKILL,
+            core_dumped: false
+        },
+        res.exit_status()
+    );
+    // shell convention: 128 + signal
+    assert_eq!(137, res.exit_code());
+    assert_eq!(
+        "killed by SIGKILL (signal 9, core dumped: false)",
+        res.exit_status().to_string()
+    );
+}
+
+#[test]
+fn capture_success_and_failure() {
+    assert_eq!(
+        "a\nb",
+        capture("sh", vec!["sh", "-c", "echo a; echo b >&2"]).unwrap()
+    );
+    match capture("sh", vec!["sh", "-c", "echo oops; exit 2"]) {
+        Err(CaptureError::Failed { output, code }) => {
+            assert_eq!("oops", output);
+            assert_eq!(2, code);
+        }
+        res => panic!("unexpected result: {:?}", res),
+    }
+    match capture("/nonexistent/program", vec!["program"]) {
+        Err(CaptureError::Spawn(_)) => {}
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn exit_status_fast_child() {
+    // exits before the reader had a chance to read anything; repeated to hit the race
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately] {
+        for _ in 0..50 {
+            for (executable, code) in [("true", 0), ("false", 1)] {
+                let res = fork_exec_and_catch(executable, [executable], strategy).unwrap();
+                assert_eq!(ExitStatus::Exited(code), res.exit_status());
+                assert!(res.stdcombined().is_empty());
+                if let (Some(stdout), Some(stderr)) = (res.stdout(), res.stderr()) {
+                    assert!(stdout.is_empty() && stderr.is_empty());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn exit_status_details() {
+    let res =
+        fork_exec_and_catch("sh", ["sh", "-c", "exit 3"], OCatchStrategy::StdCombined).unwrap();
+    assert_eq!(Some(3), res.exit_status().exited());
+    assert_eq!(None, res.exit_status().signal());
+    assert!(!res.exit_status().core_dumped());
+
+    let res = fork_exec_and_catch(
+        "sh",
+        ["sh", "-c", "kill -9 $$"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    assert_eq!(None, res.exit_status().exited());
+    assert_eq!(Some(libc::SIGKILL), res.exit_status().signal());
+    assert!(!res.exit_status().core_dumped());
+}
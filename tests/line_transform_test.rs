@@ -0,0 +1,146 @@
+use unix_exec_output_catcher::{Command, OCatchStrategy, Source};
+
+#[test]
+fn trim_line_prefix_and_suffix() {
+    let res = Command::new("printf")
+        .arg("[app] started;\\n[app] \\033[31mfailed\\033[0m;\\nno prefix\\n")
+        .strategy(OCatchStrategy::StdCombined)
+        .strip_ansi(true)
+        .trim_line_prefix("[app] ")
+        .trim_line_suffix(";")
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["started", "failed", "no prefix"],
+        res.stdcombined().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn map_line_filters_and_transforms() {
+    let res = Command::new("sh")
+        .args(["-c", "echo token=secret; echo debug: x; echo keep >&2"])
+        .strategy(OCatchStrategy::StdSeparately)
+        .map_line(|source, line| {
+            if line.starts_with("debug:") {
+                None
+            } else if source == Some(Source::Stderr) {
+                Some(format!("err: {}", line))
+            } else {
+                Some(line.replace("secret", "***"))
+            }
+        })
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["token=***"],
+        res.stdout().unwrap().iter().collect::<Vec<&str>>()
+    );
+    assert_eq!(
+        vec!["err: keep"],
+        res.stderr().unwrap().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn map_line_without_source() {
+    let res = Command::new("printf")
+        .arg("a\\nb\\n")
+        .strategy(OCatchStrategy::StdCombined)
+        .max_lines(1)
+        .map_line(|source, line| {
+            assert_eq!(None, source);
+            Some(line.to_uppercase())
+        })
+        .catch()
+        .unwrap();
+    assert_eq!(vec!["A"], res.stdcombined().iter().collect::<Vec<&str>>());
+}
+
+#[test]
+fn trailing_empty_line() {
+    let strategies = [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Multiplexed,
+    ];
+    let cases: [(&str, bool, &[&str]); 6] = [
+        ("a\\n", false, &["a"]),
+        ("a\\n", true, &["a", ""]),
+        ("a\\n\\n", false, &["a", ""]),
+        ("a\\n\\n", true, &["a", "", ""]),
+        // a last line without newline is always kept
+        ("a\\nb", true, &["a", "b"]),
+        ("", true, &[]),
+    ];
+    for strategy in strategies {
+        for (output, keep, expected) in cases.iter() {
+            let res = Command::new("printf")
+                .arg(output)
+                .strategy(strategy)
+                .keep_trailing_empty_line(*keep)
+                .catch()
+                .unwrap();
+            assert_eq!(
+                expected.to_vec(),
+                res.stdcombined().iter().collect::<Vec<&str>>(),
+                "{:?} keep={} {}",
+                output,
+                keep,
+                strategy
+            );
+        }
+    }
+}
+
+#[test]
+fn normalize_text() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::Multiplexed] {
+        let res = Command::new("printf")
+            .args(["\\357\\273\\277first\\r\\nsecond\\nthird\\r\\n"])
+            .strategy(strategy)
+            .normalize_text(true)
+            .catch()
+            .unwrap();
+        assert_eq!(
+            vec!["first", "second", "third"],
+            res.stdcombined().iter().collect::<Vec<&str>>()
+        );
+    }
+}
+
+#[test]
+fn normalize_text_strips_only_leading_bom() {
+    for strategy in [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Multiplexed,
+    ] {
+        let res = Command::new("printf")
+            .args(["\\xEF\\xBB\\xBFfirst\\n\\xEF\\xBB\\xBFsecond\\n"])
+            .strategy(strategy)
+            .normalize_text(true)
+            .catch()
+            .unwrap();
+        assert_eq!(
+            vec!["first", "\u{FEFF}second"],
+            res.stdcombined().iter().collect::<Vec<&str>>(),
+            "{}",
+            strategy
+        );
+    }
+}
+
+#[test]
+fn utf8_is_decoded_per_line() {
+    // multi-byte sequences must not be decoded byte by byte
+    let res = Command::new("printf")
+        .args(["gr\\303\\274\\303\\237e \\342\\202\\254\\n"])
+        .strategy(OCatchStrategy::StdCombined)
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["grüße €"],
+        res.stdcombined().iter().collect::<Vec<&str>>()
+    );
+}
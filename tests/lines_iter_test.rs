@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{lines_iter, Command, OCatchStrategy, OutputLine, Source};
+
+#[test]
+fn lines_iter_separately() {
+    let lines = lines_iter(
+        "sh",
+        [
+            "sh",
+            "-c",
+            "echo a; sleep 0.1; echo b >&2; sleep 0.1; printf c; exit 3",
+        ],
+        OCatchStrategy::StdSeparately,
+    )
+    .unwrap()
+    .collect::<Result<Vec<OutputLine>, UECOError>>()
+    .unwrap();
+    let texts = lines
+        .iter()
+        .filter_map(|line| match line {
+            OutputLine::Line {
+                seq, text, source, ..
+            } => Some((*seq, text.as_str(), *source)),
+            OutputLine::Eof { .. } => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (0, "a", Some(Source::Stdout)),
+            (1, "b", Some(Source::Stderr)),
+            (2, "c", Some(Source::Stdout)),
+        ],
+        texts
+    );
+    match lines.last().unwrap() {
+        OutputLine::Eof { exit_status } => assert_eq!(3, exit_status.code()),
+        line => panic!("expected EOF, got {:?}", line),
+    }
+}
+
+#[test]
+fn lines_iter_is_lazy() {
+    // the program never ends by itself
+    let mut reader = Command::new("sh")
+        .args(vec!["-c", "echo first; exec sleep 60"])
+        .lines_iter()
+        .unwrap();
+    let start = Instant::now();
+    match reader.next().unwrap().unwrap() {
+        OutputLine::Line { text, source, .. } => {
+            assert_eq!("first", text);
+            assert_eq!(Some(Source::Stdout), source);
+        }
+        line => panic!("expected a line, got {:?}", line),
+    }
+    assert!(start.elapsed() < Duration::from_secs(10));
+    // kills the child
+    drop(reader);
+}
+
+#[test]
+fn lines_iter_timeout() {
+    let mut reader = Command::new("sleep")
+        .args(vec!["60"])
+        .timeout(Duration::from_millis(100))
+        .lines_iter()
+        .unwrap();
+    assert!(matches!(
+        reader.next(),
+        Some(Err(UECOError::TimedOut { .. }))
+    ));
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn lines_iter_throttle() {
+    // a burst of lines every 50ms for 0.5s; with an interval of 200ms they get coalesced
+    let start = Instant::now();
+    let batches = Command::new("sh")
+        .args(vec![
+            "-c",
+            "for i in 1 2 3 4 5 6 7 8 9 10; do echo a; echo b; sleep 0.05; done",
+        ])
+        .lines_iter()
+        .unwrap()
+        .throttle(Duration::from_millis(200))
+        .collect::<Result<Vec<Vec<OutputLine>>, UECOError>>()
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(450));
+    assert!(batches.len() <= 5, "too many batches: {}", batches.len());
+    assert!(batches.iter().all(|batch| !batch.is_empty()));
+    let lines = batches.concat();
+    assert_eq!(21, lines.len());
+    assert!(matches!(lines.last(), Some(OutputLine::Eof { .. })));
+}
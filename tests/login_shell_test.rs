@@ -0,0 +1,16 @@
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn login_shell() {
+    let res = Command::new("printf")
+        .args(vec!["%s\\n", "a b", "$HOME", "it's"])
+        .env("SHELL", "/bin/sh")
+        .login_shell(true)
+        .catch()
+        .unwrap();
+    assert!(res.exit_status().success());
+    assert_eq!("/bin/sh", res.resolved_executable().to_str().unwrap());
+    // the args are passed as they are; the profile may print something before
+    let lines = res.stdout().unwrap().iter().collect::<Vec<_>>();
+    assert_eq!(vec!["a b", "$HOME", "it's"], lines[lines.len() - 3..]);
+}
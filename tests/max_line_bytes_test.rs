@@ -0,0 +1,29 @@
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+#[test]
+fn max_line_bytes_not_exceeded() {
+    let res = Command::new("printf")
+        .arg("0123456789\n")
+        .strategy(OCatchStrategy::StdCombined)
+        .max_line_bytes(10)
+        .catch()
+        .unwrap();
+    assert_eq!("0123456789", res.stdcombined_lines()[0].as_str());
+}
+
+#[test]
+fn max_line_bytes_exceeded() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately].iter() {
+        // 1MB without a single newline
+        let res = Command::new("head")
+            .args(vec!["-c", "1000000", "/dev/zero"])
+            .strategy(*strategy)
+            .max_line_bytes(1000)
+            .catch();
+        match res {
+            Err(UECOError::LineTooLong { limit }) => assert_eq!(1000, limit),
+            _ => panic!("expected LineTooLong, got {:?}", res),
+        }
+    }
+}
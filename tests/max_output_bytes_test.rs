@@ -0,0 +1,57 @@
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{Command, OCatchStrategy, OverflowPolicy, ProcessOutput};
+
+/// 10 lines with 5 bytes each (without newline).
+const SCRIPT: &str = "for i in 0 1 2 3 4 5 6 7 8 9; do echo line$i; done";
+
+fn run(strategy: OCatchStrategy, policy: OverflowPolicy) -> Result<ProcessOutput, UECOError> {
+    Command::new("sh")
+        .args(["-c", SCRIPT])
+        .strategy(strategy)
+        .max_output_bytes(15)
+        .on_overflow(policy)
+        .catch()
+}
+
+fn combined(res: &ProcessOutput) -> Vec<&str> {
+    res.stdcombined().iter().collect()
+}
+
+#[test]
+fn overflow_error() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately] {
+        match run(strategy, OverflowPolicy::Error) {
+            Err(UECOError::OutputTooLarge { limit }) => assert_eq!(15, limit),
+            res => panic!("expected OutputTooLarge, got {:?}", res),
+        }
+    }
+}
+
+#[test]
+fn overflow_truncate() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately] {
+        let res = run(strategy, OverflowPolicy::Truncate).unwrap();
+        assert!(res.exit_status().success());
+        assert_eq!(vec!["line0", "line1", "line2"], combined(&res));
+    }
+}
+
+#[test]
+fn overflow_tail() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately] {
+        let res = run(strategy, OverflowPolicy::Tail).unwrap();
+        assert_eq!(vec!["line7", "line8", "line9"], combined(&res));
+    }
+    let res = run(OCatchStrategy::StdSeparately, OverflowPolicy::Tail).unwrap();
+    assert_eq!(3, res.stdout().unwrap().len());
+}
+
+#[test]
+fn no_overflow() {
+    let res = Command::new("sh")
+        .args(["-c", SCRIPT])
+        .max_output_bytes(50)
+        .catch()
+        .unwrap();
+    assert_eq!(10, res.stdcombined().len());
+}
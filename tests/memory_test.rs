@@ -0,0 +1,18 @@
+#![cfg(target_os = "linux")]
+
+use std::time::Duration;
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn sample_memory() {
+    let res = Command::new("sleep")
+        .arg("0.2")
+        .sample_memory(Duration::from_millis(10))
+        .catch()
+        .unwrap();
+    assert!(res.exit_status().success());
+    assert!(res.peak_rss_kib().unwrap() > 0);
+
+    let res = Command::new("true").catch().unwrap();
+    assert_eq!(None, res.peak_rss_kib());
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+use unix_exec_output_catcher::{
+    Command, OCatchStrategy, Source, TimedLine, INTERLEAVING_SAFETY_GAP,
+};
+
+/// The number at the start of the line.
+fn embedded_number(line: &TimedLine) -> u32 {
+    line.line.split(' ').next().unwrap().parse().unwrap()
+}
+
+#[test]
+fn merge_by_embedded_number() {
+    let res = Command::new("sh")
+        .args(vec![
+            "-c",
+            "echo '2 b' >&2; echo '3 c' >&2; echo '1 a'; echo '4 d'",
+        ])
+        .strategy(OCatchStrategy::StdSeparately)
+        .retain_timestamps(true)
+        .catch()
+        .unwrap();
+    let timed = res.timed_lines().unwrap();
+    assert_eq!(4, timed.len());
+    assert!(timed.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+    let merged = res
+        .merge_by(|a, b| embedded_number(a).cmp(&embedded_number(b)))
+        .unwrap();
+    assert_eq!(
+        vec!["1 a", "2 b", "3 c", "4 d"],
+        merged.iter().map(|line| line.as_str()).collect::<Vec<_>>()
+    );
+
+    let by_timestamp = res.merge_by(|a, b| a.timestamp.cmp(&b.timestamp)).unwrap();
+    assert_eq!(res.stdcombined_lines(), &by_timestamp);
+}
+
+#[test]
+fn merge_by_without_timestamps() {
+    let res = Command::new("true")
+        .strategy(OCatchStrategy::StdSeparately)
+        .catch()
+        .unwrap();
+    assert!(res.timed_lines().is_none());
+    assert!(res.merge_by(|a, b| a.timestamp.cmp(&b.timestamp)).is_none());
+}
+
+#[test]
+fn min_interline_gap() {
+    let res = Command::new("sh")
+        .args(vec![
+            "-c",
+            "echo out; sleep 0.05; echo err >&2; echo err2 >&2",
+        ])
+        .strategy(OCatchStrategy::StdSeparately)
+        .catch()
+        .unwrap();
+    let gap = res.min_interline_gap().unwrap();
+    assert!(gap >= INTERLEAVING_SAFETY_GAP, "{:?}", gap);
+
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo out; echo out2"])
+        .strategy(OCatchStrategy::StdSeparately)
+        .catch()
+        .unwrap();
+    assert_eq!(None, res.min_interline_gap());
+
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo out; echo err >&2"])
+        .strategy(OCatchStrategy::StdCombined)
+        .catch()
+        .unwrap();
+    assert_eq!(None, res.min_interline_gap());
+}
+
+/// The options that read the output like [`OCatchStrategy::Multiplexed`] keep the
+/// timestamps too.
+#[test]
+fn timestamps_with_chunk_reader() {
+    let script = "echo out; sleep 0.05; echo err >&2";
+    let with_timeout = Command::new("sh")
+        .args(vec!["-c", script])
+        .strategy(OCatchStrategy::StdSeparately)
+        .retain_timestamps(true)
+        .timeout(Duration::from_secs(10))
+        .catch()
+        .unwrap();
+    let with_descendants = Command::new("sh")
+        .args(vec!["-c", script])
+        .strategy(OCatchStrategy::StdSeparately)
+        .retain_timestamps(true)
+        .capture_descendants(true)
+        .catch()
+        .unwrap();
+    for res in &[with_timeout, with_descendants] {
+        let timed = res.timed_lines().unwrap();
+        assert_eq!(
+            vec![(Source::Stdout, "out"), (Source::Stderr, "err")],
+            timed
+                .iter()
+                .map(|line| (line.source, line.line.as_str()))
+                .collect::<Vec<_>>()
+        );
+        let gap = res.min_interline_gap().unwrap();
+        assert!(gap >= INTERLEAVING_SAFETY_GAP, "{:?}", gap);
+    }
+}
@@ -0,0 +1,71 @@
+use unix_exec_output_catcher::{Command, OCatchStrategy, Source, StopReason};
+
+#[test]
+fn multiplexed_all_three_views() {
+    let res = Command::new("sh")
+        .args([
+            "-c",
+            "echo 1; sleep 0.05; echo 2 >&2; sleep 0.05; echo 3; sleep 0.05; printf 4 >&2",
+        ])
+        .strategy(OCatchStrategy::Multiplexed)
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["1", "3"],
+        res.stdout().unwrap().iter().collect::<Vec<&str>>()
+    );
+    assert_eq!(
+        vec!["2", "4"],
+        res.stderr().unwrap().iter().collect::<Vec<&str>>()
+    );
+    assert_eq!(
+        Some(vec![
+            (Source::Stdout, "1"),
+            (Source::Stderr, "2"),
+            (Source::Stdout, "3"),
+            (Source::Stderr, "4"),
+        ]),
+        res.stdcombined_tagged()
+    );
+}
+
+#[test]
+fn multiplexed_much_output() {
+    let res = Command::new("sh")
+        .args(["-c", "seq 1 100000; seq 1 100000 >&2"])
+        .strategy(OCatchStrategy::Multiplexed)
+        .catch()
+        .unwrap();
+    assert_eq!(100_000, res.stdout().unwrap().len());
+    assert_eq!(100_000, res.stderr().unwrap().len());
+    assert_eq!(200_000, res.stdcombined().len());
+}
+
+#[test]
+fn multiplexed_max_lines() {
+    let res = Command::new("yes")
+        .strategy(OCatchStrategy::Multiplexed)
+        .max_lines(10)
+        .catch()
+        .unwrap();
+    assert_eq!(10, res.stdout().unwrap().len());
+    assert_eq!(Some(StopReason::MaxLines), res.stop_reason());
+}
+
+#[test]
+fn multiplexed_line_over_many_chunks() {
+    // a single line that spans many reads, followed by short lines
+    let res = Command::new("sh")
+        .args([
+            "-c",
+            "head -c 1000000 /dev/zero | tr '\\0' a; echo; echo b; printf c",
+        ])
+        .strategy(OCatchStrategy::Multiplexed)
+        .catch()
+        .unwrap();
+    let lines = res.stdout().unwrap().iter().collect::<Vec<&str>>();
+    assert_eq!(3, lines.len());
+    assert_eq!(1_000_000, lines[0].len());
+    assert!(lines[0].bytes().all(|b| b == b'a'));
+    assert_eq!(vec!["b", "c"], lines[1..].to_vec());
+}
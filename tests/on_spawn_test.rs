@@ -0,0 +1,37 @@
+use std::sync::{Arc, Mutex};
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn on_spawn_gets_the_pid() {
+    let pids = Arc::new(Mutex::new(vec![]));
+    let pids_hook = pids.clone();
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo $$"])
+        .on_spawn(move |pid| {
+            pids_hook.lock().unwrap().push(pid);
+            Ok(())
+        })
+        .catch()
+        .unwrap();
+    let pid = res
+        .stdout()
+        .unwrap()
+        .get(0)
+        .unwrap()
+        .parse::<i32>()
+        .unwrap();
+    assert_eq!(vec![pid], *pids.lock().unwrap());
+}
+
+#[test]
+fn on_spawn_error_kills_the_child() {
+    let res = Command::new("sleep")
+        .args(vec!["60"])
+        .on_spawn(|_| Err(std::io::Error::from_raw_os_error(libc::EPERM)))
+        .catch();
+    assert!(matches!(
+        res,
+        Err(UECOError::OnSpawnFailed { errno: libc::EPERM })
+    ));
+}
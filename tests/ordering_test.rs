@@ -0,0 +1,55 @@
+#![cfg(feature = "test-hooks")]
+
+use std::time::Duration;
+use unix_exec_output_catcher::testing::fork_closure_and_catch;
+use unix_exec_output_catcher::{OCatchStrategy, Source};
+
+/// Lines that the child writes alternately to STDOUT and STDERR, in irregular runs.
+const LINES: [(Source, &str); 10] = [
+    (Source::Stdout, "01"),
+    (Source::Stderr, "02"),
+    (Source::Stdout, "03"),
+    (Source::Stderr, "04"),
+    (Source::Stdout, "05"),
+    (Source::Stdout, "06"),
+    (Source::Stdout, "07"),
+    (Source::Stderr, "08"),
+    (Source::Stderr, "09"),
+    (Source::Stderr, "10"),
+];
+
+/// Writes [`LINES`] with a pause after each line, so that the reader sees them one by one.
+/// Uses `libc::write()` instead of `println!()`, which isn't safe after fork().
+fn write_lines() {
+    for (source, line) in LINES.iter() {
+        let fd = match source {
+            Source::Stdout => libc::STDOUT_FILENO,
+            Source::Stderr => libc::STDERR_FILENO,
+        };
+        let line = format!("{}\n", line);
+        unsafe { libc::write(fd, line.as_ptr() as *const _, line.len()) };
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn combined_order_is_exact() {
+    for strategy in [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Multiplexed,
+    ] {
+        let res = unsafe { fork_closure_and_catch(write_lines, strategy) }.unwrap();
+        assert!(res.exit_status().success());
+        let expected = LINES.iter().map(|(_, line)| *line).collect::<Vec<_>>();
+        assert_eq!(
+            expected,
+            res.stdcombined().iter().collect::<Vec<_>>(),
+            "{}",
+            strategy
+        );
+        if let Some(tagged) = res.stdcombined_tagged() {
+            assert_eq!(LINES.to_vec(), tagged, "{}", strategy);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use unix_exec_output_catcher::{Command, ExitStatus};
+
+#[test]
+fn output_like_std() {
+    let output = Command::new("sh")
+        .args(vec![
+            "-c",
+            "printf out1; sleep 0.05; printf err1 >&2; sleep 0.05; printf 'out2\\n'; exit 2",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(ExitStatus::Exited(2), output.status);
+    assert_eq!(b"out1out2\n".to_vec(), output.stdout);
+    assert_eq!(b"err1".to_vec(), output.stderr);
+    assert_eq!(b"out1err1out2\n".to_vec(), output.combined);
+}
+
+#[test]
+fn output_keeps_binary_data() {
+    let output = Command::new("printf")
+        .arg("\\000\\377\\n\\n")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(vec![0, 255, b'\n', b'\n'], output.stdout);
+    assert!(output.stderr.is_empty());
+}
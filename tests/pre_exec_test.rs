@@ -0,0 +1,35 @@
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn pre_exec_sets_rlimit() {
+    let mut command = Command::new("sh");
+    command.args(vec!["-c", "ulimit -n"]);
+    unsafe {
+        command.pre_exec(|| {
+            let limit = libc::rlimit {
+                rlim_cur: 42,
+                rlim_max: 42,
+            };
+            if libc::setrlimit(libc::RLIMIT_NOFILE, &limit) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+    let res = command.catch().unwrap();
+    assert_eq!(Some("42"), res.stdout().unwrap().get(0));
+}
+
+#[test]
+fn pre_exec_error() {
+    let mut command = Command::new("true");
+    unsafe {
+        command.pre_exec(|| Err(std::io::Error::from_raw_os_error(libc::EPERM)));
+    }
+    assert!(matches!(
+        command.catch(),
+        Err(UECOError::PreExecFailed { errno: libc::EPERM })
+    ));
+}
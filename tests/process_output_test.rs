@@ -0,0 +1,297 @@
+use std::path::Path;
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{
+    fork_exec_and_catch, Command, LineStats, OCatchStrategy, OrderingGuarantee, Source,
+};
+
+#[test]
+fn is_empty_and_has_output() {
+    let res = fork_exec_and_catch("true", vec!["true"], OCatchStrategy::StdSeparately).unwrap();
+    assert!(res.is_empty());
+    assert!(!res.has_stdout());
+    assert!(!res.has_stderr());
+
+    let res = fork_exec_and_catch(
+        "sh",
+        vec!["sh", "-c", "echo err >&2"],
+        OCatchStrategy::StdSeparately,
+    )
+    .unwrap();
+    assert!(!res.is_empty());
+    assert!(!res.has_stdout());
+    assert!(res.has_stderr());
+
+    let res =
+        fork_exec_and_catch("echo", vec!["echo", "out"], OCatchStrategy::StdCombined).unwrap();
+    assert!(!res.is_empty());
+    // not caught separately
+    assert!(!res.has_stdout());
+}
+
+#[test]
+fn resolved_executable() {
+    let res =
+        fork_exec_and_catch("sh", vec!["sh", "-c", "true"], OCatchStrategy::StdCombined).unwrap();
+    assert!(res.resolved_executable().is_absolute());
+    assert!(res.resolved_executable().ends_with("sh"));
+
+    let res = fork_exec_and_catch(
+        "/bin/sh",
+        vec!["sh", "-c", "true"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    assert_eq!(Path::new("/bin/sh"), res.resolved_executable());
+
+    let res = fork_exec_and_catch(
+        "this-executable-does-not-exist",
+        vec!["this-executable-does-not-exist"],
+        OCatchStrategy::StdCombined,
+    );
+    assert!(matches!(res, Err(UECOError::ExecutableNotFound { .. })));
+}
+
+#[test]
+fn empty_executable() {
+    let res = fork_exec_and_catch("", vec![""], OCatchStrategy::StdCombined);
+    assert!(matches!(res, Err(UECOError::EmptyExecutable)));
+}
+
+#[test]
+fn directory_as_executable() {
+    let res = fork_exec_and_catch("/tmp/", vec!["/tmp/"], OCatchStrategy::StdCombined);
+    match res {
+        Err(UECOError::NotAnExecutable { path }) => assert_eq!("/tmp/", path),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn last_lines() {
+    let res = fork_exec_and_catch(
+        "sh",
+        vec![
+            "sh",
+            "-c",
+            "echo out1; echo out2; echo err1 >&2; echo err2 >&2",
+        ],
+        OCatchStrategy::StdSeparately,
+    )
+    .unwrap();
+    assert_eq!("out2", res.last_stdout_line().unwrap().as_str());
+    assert_eq!("err2", res.last_stderr_line().unwrap().as_str());
+    assert!(res.last_combined_line().is_some());
+
+    let res = fork_exec_and_catch(
+        "sh",
+        vec!["sh", "-c", "echo out1; echo err1 >&2"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    assert_eq!(None, res.last_stdout_line());
+    assert_eq!(None, res.last_stderr_line());
+    assert_eq!("err1", res.last_combined_line().unwrap().as_str());
+
+    let res = fork_exec_and_catch("true", vec!["true"], OCatchStrategy::StdSeparately).unwrap();
+    assert_eq!(None, res.last_stdout_line());
+    assert_eq!(None, res.last_combined_line());
+}
+
+#[test]
+fn nth_lines() {
+    let res = fork_exec_and_catch(
+        "sh",
+        vec!["sh", "-c", "echo out1; echo out2; echo err1 >&2"],
+        OCatchStrategy::StdSeparately,
+    )
+    .unwrap();
+    assert_eq!("out1", res.stdout_line(0).unwrap().as_str());
+    assert_eq!("out2", res.stdout_line(1).unwrap().as_str());
+    assert_eq!(None, res.stdout_line(2));
+    assert_eq!("err1", res.stderr_line(0).unwrap().as_str());
+    assert_eq!(None, res.stderr_line(1));
+    assert!(res.combined_line(2).is_some());
+    assert_eq!(None, res.combined_line(3));
+
+    let res = fork_exec_and_catch(
+        "sh",
+        vec!["sh", "-c", "echo out1"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    assert_eq!(None, res.stdout_line(0));
+    assert_eq!(None, res.stderr_line(0));
+    assert_eq!("out1", res.combined_line(0).unwrap().as_str());
+}
+
+#[test]
+fn into_parts() {
+    let res = fork_exec_and_catch(
+        "sh",
+        vec!["sh", "-c", "echo out; echo err >&2; exit 3"],
+        OCatchStrategy::StdSeparately,
+    )
+    .unwrap();
+    // already created vectors are moved out
+    let stdout_ptr = res.stdout_lines().unwrap().as_ptr();
+    let (stdout, stderr, stdcombined, exit_code, strategy) = res.into_parts();
+    let stdout = stdout.unwrap();
+    assert_eq!(stdout_ptr, stdout.as_ptr());
+    assert_eq!("out", stdout[0].as_str());
+    assert_eq!("err", stderr.unwrap()[0].as_str());
+    assert_eq!(2, stdcombined.len());
+    assert_eq!(3, exit_code);
+    assert!(matches!(strategy, OCatchStrategy::StdSeparately));
+}
+
+#[test]
+fn into_strings() {
+    let run = || {
+        fork_exec_and_catch(
+            "sh",
+            vec!["sh", "-c", "echo out; echo err >&2"],
+            OCatchStrategy::StdSeparately,
+        )
+        .unwrap()
+    };
+    assert_eq!(Some(vec!["out".to_string()]), run().into_stdout_strings());
+    assert_eq!(Some(vec!["err".to_string()]), run().into_stderr_strings());
+    assert_eq!(2, run().into_combined_strings().len());
+}
+
+#[test]
+fn stdcombined_tagged() {
+    let res = fork_exec_and_catch(
+        "sh",
+        ["sh", "-c", "echo a; sleep 0.1; echo b >&2"],
+        OCatchStrategy::StdSeparately,
+    )
+    .unwrap();
+    assert_eq!(
+        Some(vec![(Source::Stdout, "a"), (Source::Stderr, "b")]),
+        res.stdcombined_tagged()
+    );
+    let res = fork_exec_and_catch("echo", ["echo", "a"], OCatchStrategy::StdCombined).unwrap();
+    assert!(res.stdcombined_tagged().is_none());
+}
+
+#[test]
+fn write_to() {
+    let res = fork_exec_and_catch(
+        "sh",
+        ["sh", "-c", "echo a; echo b >&2; printf c"],
+        OCatchStrategy::Multiplexed,
+    )
+    .unwrap();
+    let mut out = vec![];
+    let mut err = vec![];
+    res.write_to(&mut out, &mut err).unwrap();
+    assert_eq!(b"a\nc\n".to_vec(), out);
+    assert_eq!(b"b\n".to_vec(), err);
+
+    let res = fork_exec_and_catch(
+        "sh",
+        ["sh", "-c", "echo a; echo b >&2"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    let mut out = vec![];
+    let mut err = vec![];
+    res.write_to(&mut out, &mut err).unwrap();
+    assert_eq!(b"a\nb\n".to_vec(), out);
+    assert!(err.is_empty());
+}
+
+#[test]
+fn ordering_guarantee() {
+    for (strategy, expected) in [
+        (OCatchStrategy::StdCombined, OrderingGuarantee::Exact),
+        (OCatchStrategy::StdSeparately, OrderingGuarantee::BestEffort),
+        (OCatchStrategy::Multiplexed, OrderingGuarantee::BestEffort),
+    ] {
+        let res = fork_exec_and_catch("true", ["true"], strategy).unwrap();
+        assert_eq!(expected, res.ordering_guarantee());
+    }
+}
+
+#[test]
+fn stdout_as_str() {
+    let script = ["sh", "-c", "echo a; echo err >&2; echo b"];
+    let res = fork_exec_and_catch("sh", script, OCatchStrategy::StdSeparately).unwrap();
+    assert_eq!("a\nb", res.stdout_as_str().unwrap());
+
+    let res = fork_exec_and_catch("sh", script, OCatchStrategy::StdCombined).unwrap();
+    assert!(matches!(
+        res.stdout_as_str(),
+        Err(UECOError::StdoutUnavailable {
+            strategy: OCatchStrategy::StdCombined
+        })
+    ));
+}
+
+#[test]
+fn combined_paragraphs() {
+    let res = fork_exec_and_catch(
+        "printf",
+        vec!["printf", "\\n\\na\\nb\\n\\n  \\nc\\n\\n"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    let paragraphs = res
+        .combined_paragraphs()
+        .iter()
+        .map(|p| {
+            p.iter()
+                .map(|line| line.as_str())
+                .collect::<Vec<_>>()
+                .join("|")
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["a|b", "c"], paragraphs);
+}
+
+#[test]
+fn line_stats() {
+    for strategy in [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Multiplexed,
+    ] {
+        let res = Command::new("sh")
+            .args(vec!["-c", "echo a; echo abcd >&2; echo abc"])
+            .strategy(strategy)
+            .line_stats(true)
+            .catch()
+            .unwrap();
+        let stats = LineStats {
+            lines: 3,
+            min_len: 1,
+            max_len: 4,
+            total_bytes: 8,
+        };
+        assert_eq!(Some(stats), res.stats(), "{}", strategy);
+        assert!((res.stats().unwrap().average_len() - 8.0 / 3.0).abs() < 1e-9);
+    }
+
+    let res = Command::new("true").catch().unwrap();
+    assert_eq!(None, res.stats());
+}
+
+#[test]
+fn captured_or_empty() {
+    let res = fork_exec_and_catch("true", vec!["true"], OCatchStrategy::StdSeparately).unwrap();
+    assert!(res.stdout_was_captured());
+    assert!(res.stderr_was_captured());
+    assert_eq!(Some(0), res.stdout_lines().map(|lines| lines.len()));
+    assert!(res.stdout_or_empty().is_empty());
+
+    let res = fork_exec_and_catch("echo", vec!["echo", "hi"], OCatchStrategy::StdCombined).unwrap();
+    assert!(!res.stdout_was_captured());
+    assert!(!res.stderr_was_captured());
+    assert!(res.stdout_lines().is_none());
+    assert!(res.stdout_or_empty().is_empty());
+    assert!(res.stderr_or_empty().is_empty());
+
+    let res = fork_exec_and_catch("echo", vec!["echo", "hi"], OCatchStrategy::Multiplexed).unwrap();
+    assert_eq!("hi", res.stdout_or_empty()[0].as_str());
+}
@@ -0,0 +1,59 @@
+use unix_exec_output_catcher::{fork_exec_and_catch, OCatchStrategy};
+
+#[test]
+fn pty_is_a_terminal() {
+    let res = fork_exec_and_catch(
+        "sh",
+        [
+            "sh",
+            "-c",
+            "test -t 1 && echo tty || echo no tty; echo err >&2",
+        ],
+        OCatchStrategy::Pty,
+    )
+    .unwrap();
+    assert!(res.exit_status().success());
+    assert!(res.stdout().is_none());
+    // no "\r" from the translation of "\n" by the terminal
+    assert_eq!(
+        vec!["tty", "err"],
+        res.stdcombined().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn pipe_is_not_a_terminal() {
+    let res = fork_exec_and_catch(
+        "sh",
+        ["sh", "-c", "test -t 1 && echo tty || echo no tty"],
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    assert_eq!(
+        vec!["no tty"],
+        res.stdcombined().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn pty_much_output() {
+    let res = fork_exec_and_catch("seq", ["seq", "1", "50000"], OCatchStrategy::Pty).unwrap();
+    let lines = res.stdcombined();
+    assert_eq!(50000, lines.len());
+    assert_eq!("50000", lines.iter().last().unwrap());
+}
+
+#[test]
+fn pty_is_the_controlling_terminal() {
+    let res = fork_exec_and_catch(
+        "sh",
+        ["sh", "-c", "echo out; echo prompt > /dev/tty"],
+        OCatchStrategy::Pty,
+    )
+    .unwrap();
+    assert!(res.exit_status().success());
+    assert_eq!(
+        vec!["out", "prompt"],
+        res.stdcombined().iter().collect::<Vec<&str>>()
+    );
+}
@@ -0,0 +1,45 @@
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{read_parsed, Command};
+
+#[test]
+fn read_parsed_numbers() {
+    let numbers = read_parsed::<u32>("seq", vec!["seq", "3"]).unwrap();
+    assert_eq!(vec![1, 2, 3], numbers);
+}
+
+#[test]
+fn read_parsed_error() {
+    let res = Command::new("printf")
+        .arg("1\\n2\\nthree\\n")
+        .read_parsed::<i64>();
+    match res {
+        Err(UECOError::LineParseError { line_no, line }) => {
+            assert_eq!(3, line_no);
+            assert_eq!("three", line);
+        }
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn read_parsed_ignores_stderr() {
+    let numbers = Command::new("sh")
+        .args(vec!["-c", "echo 1; echo warning >&2; echo 2"])
+        .read_parsed::<u32>()
+        .unwrap();
+    assert_eq!(vec![1, 2], numbers);
+}
+
+#[test]
+fn read_parsed_unsuccessful_exit() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo broken >&2; exit 3"])
+        .read_parsed::<u32>();
+    match res {
+        Err(UECOError::UnsuccessfulExit { status, stderr }) => {
+            assert_eq!(3, status.code());
+            assert_eq!("broken", stderr);
+        }
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::{Command, OCatchStrategy, ReadTermination};
+
+/// The background process inherits STDOUT and keeps the write end open.
+const SCRIPT: &str = "sleep 3 & echo started; echo more >&2";
+
+#[test]
+fn read_termination_child_exit() {
+    for strategy in [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Pty,
+    ] {
+        let start = Instant::now();
+        let res = Command::new("sh")
+            .args(vec!["-c", SCRIPT])
+            .strategy(strategy)
+            .read_termination(ReadTermination::ChildExit)
+            .catch()
+            .unwrap();
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "{} waited for EOF",
+            strategy
+        );
+        assert_eq!(0, res.exit_code());
+        let lines = res.stdcombined().iter().collect::<Vec<_>>();
+        assert!(lines.contains(&"started"), "{}: {:?}", strategy, lines);
+        assert!(lines.contains(&"more"), "{}: {:?}", strategy, lines);
+    }
+}
+
+#[test]
+fn read_termination_eof_waits_for_background_process() {
+    let start = Instant::now();
+    let res = Command::new("sh")
+        .args(vec!["-c", "sleep 0.5 & echo started"])
+        .catch()
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    assert_eq!(Some("started"), res.stdout().unwrap().get(0));
+}
+
+#[test]
+fn capture_descendants() {
+    let script = "(sleep 0.3; echo late) & echo early";
+    let res = Command::new("sh")
+        .args(vec!["-c", script])
+        .capture_descendants(true)
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["early", "late"],
+        res.stdout().unwrap().iter().collect::<Vec<_>>()
+    );
+    assert_eq!(Some(true), res.includes_descendant_output());
+
+    let res = Command::new("sh")
+        .args(vec!["-c", script])
+        .capture_descendants(false)
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["early"],
+        res.stdout().unwrap().iter().collect::<Vec<_>>()
+    );
+    assert_eq!(Some(false), res.includes_descendant_output());
+
+    // no descendants
+    let res = Command::new("echo")
+        .capture_descendants(true)
+        .catch()
+        .unwrap();
+    assert_eq!(Some(false), res.includes_descendant_output());
+    assert_eq!(
+        None,
+        Command::new("echo")
+            .catch()
+            .unwrap()
+            .includes_descendant_output()
+    );
+}
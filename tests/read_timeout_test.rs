@@ -0,0 +1,38 @@
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::{Command, OCatchStrategy, StopReason};
+
+#[test]
+fn read_timeout_honors_timeout() {
+    for strategy in [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Pty,
+    ] {
+        let start = Instant::now();
+        let res = Command::new("sh")
+            .args(vec!["-c", "echo first; exec sleep 5"])
+            .strategy(strategy)
+            .timeout(Duration::from_millis(300))
+            .read_timeout(Duration::from_millis(20))
+            .catch()
+            .unwrap();
+        assert!(
+            start.elapsed() < Duration::from_secs(3),
+            "{} blocked in read",
+            strategy
+        );
+        assert_eq!(Some(StopReason::Timeout), res.stop_reason(), "{}", strategy);
+        assert_eq!(vec!["first"], res.stdcombined().iter().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn read_timeout_keeps_incomplete_lines() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "printf foo; sleep 0.2; echo bar"])
+        .read_timeout(Duration::from_millis(10))
+        .catch()
+        .unwrap();
+    assert_eq!(None, res.stop_reason());
+    assert_eq!(vec!["foobar"], res.stdcombined().iter().collect::<Vec<_>>());
+}
@@ -0,0 +1,13 @@
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+#[test]
+fn small_reader_stack() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "seq 1 1000; seq 1 1000 >&2"])
+        .strategy(OCatchStrategy::StdSeparately)
+        .reader_stack_size(64 * 1024)
+        .catch()
+        .unwrap();
+    assert_eq!(1000, res.stdout().unwrap().len());
+    assert_eq!(1000, res.stderr().unwrap().len());
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "regex")]
+
+use regex::bytes::{Regex, RegexBuilder};
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+#[test]
+fn split_on_regex() {
+    // the separator is split over two writes
+    let res = Command::new("sh")
+        .args(vec![
+            "-c",
+            "printf 'a\\nb\\n-'; sleep 0.1; printf -- '--\\nc\\n---\\nd'",
+        ])
+        .strategy(OCatchStrategy::StdCombined)
+        .record_separator_regex(Regex::new("\n-+\n").unwrap())
+        .catch()
+        .unwrap();
+    assert_eq!(None, res.stdout());
+    assert_eq!(
+        vec!["a\nb", "c", "d"],
+        res.stdcombined().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn split_on_regex_separately() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "printf 'a;b;;c'; printf 'x;' >&2"])
+        .strategy(OCatchStrategy::StdSeparately)
+        .record_separator_regex(Regex::new(";+").unwrap())
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["a", "b", "c"],
+        res.stdout().unwrap().iter().collect::<Vec<&str>>()
+    );
+    assert_eq!(
+        vec!["x"],
+        res.stderr().unwrap().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn split_on_regex_with_builder_flags() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "printf 'aENDbendc'"])
+        .record_separator_regex(
+            RegexBuilder::new("end")
+                .case_insensitive(true)
+                .build()
+                .unwrap(),
+        )
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["a", "b", "c"],
+        res.stdout().unwrap().iter().collect::<Vec<&str>>()
+    );
+}
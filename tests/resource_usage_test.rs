@@ -0,0 +1,29 @@
+use std::time::Duration;
+use unix_exec_output_catcher::{Command, ResourceUsage};
+
+fn cpu_time(usage: ResourceUsage) -> Duration {
+    usage.user_time + usage.system_time
+}
+
+#[test]
+fn resource_usage_is_per_child() {
+    let busy = Command::new("sh")
+        .args(vec![
+            "-c",
+            "i=0; while [ $i -lt 300000 ]; do i=$((i+1)); done",
+        ])
+        .catch()
+        .unwrap();
+    let idle = Command::new("true").catch().unwrap();
+    let busy = cpu_time(busy.resource_usage().unwrap());
+    let idle = cpu_time(idle.resource_usage().unwrap());
+    assert!(busy >= Duration::from_millis(50), "{:?}", busy);
+    // the usage of the first child must not be included
+    assert!(idle < busy, "{:?} >= {:?}", idle, busy);
+}
+
+#[test]
+fn resource_usage_has_max_rss() {
+    let res = Command::new("true").catch().unwrap();
+    assert!(res.resource_usage().unwrap().max_rss_kib > 0);
+}
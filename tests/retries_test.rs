@@ -0,0 +1,41 @@
+#![cfg(feature = "test-hooks")]
+
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::testing::fail_next_forks;
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn retries_transient_fork_failures() {
+    fail_next_forks(2);
+    let start = Instant::now();
+    let res = Command::new("echo").arg("hi").retries(3).catch().unwrap();
+    assert_eq!(Some("hi"), res.stdout().unwrap().get(0));
+    // backoff of 10ms and 20ms
+    assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[test]
+fn retries_exhausted_returns_last_error() {
+    fail_next_forks(3);
+    let res = Command::new("echo").retries(2).catch();
+    match res {
+        Err(err @ UECOError::ForkFailed { .. }) => assert!(err.is_transient()),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    fail_next_forks(0);
+}
+
+#[test]
+fn no_retries_by_default() {
+    fail_next_forks(1);
+    assert!(matches!(
+        Command::new("echo").catch(),
+        Err(UECOError::ForkFailed { .. })
+    ));
+    // permanent errors aren't retried
+    let res = Command::new("this-executable-does-not-exist")
+        .retries(5)
+        .catch();
+    assert!(matches!(res, Err(UECOError::ExecutableNotFound { .. })));
+}
@@ -0,0 +1,27 @@
+use std::panic;
+use unix_exec_output_catcher::run_expect;
+
+#[test]
+fn run_expect_success() {
+    let res = run_expect("echo", vec!["echo", "hello"]);
+    assert_eq!(
+        vec!["hello"],
+        res.stdout().unwrap().iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn run_expect_failure_message() {
+    let err = panic::catch_unwind(|| {
+        run_expect(
+            "sh",
+            vec!["sh", "-c", "echo progress; echo broken >&2; exit 3"],
+        )
+    })
+    .unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+    assert!(message.contains("exited with code 3"), "{}", message);
+    assert!(message.contains("--- stderr ---\nbroken\n"), "{}", message);
+    assert!(message.contains("--- combined output ---\n"), "{}", message);
+    assert!(message.contains("progress"), "{}", message);
+}
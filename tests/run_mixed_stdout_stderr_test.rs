@@ -9,8 +9,9 @@ fn main() {
     env_logger::init();
 
     let res = fork_exec_and_catch(
-        // build the binary first, like: "cargo build --all --all-targets"
-        "./target/debug/mixed_stdout_stderr_test",
+        // An example and not a test target, because only examples get a stable path; test
+        // binaries land in target/debug/deps/<name>-<hash>. `cargo test` builds it first.
+        "./target/debug/examples/mixed_stdout_stderr_test",
         vec!["mixed_stdout_stderr_test"],
         OCatchStrategy::StdSeparately,)
         .unwrap();
@@ -25,7 +26,7 @@ fn main() {
     assert_eq!(0, res.stdcombined_lines().len() % 10, "The test binary must output a total amount of lines so that % 10 equals 0.");
 
     let all_lines = res.stdcombined_lines()
-        .into_iter()
+        .iter()
         .map(|s| s.replace("STDERR ", ""))
         .map(|s| s.replace("STDOUT ", ""))
         .map(|s| s.split(" @")
@@ -0,0 +1,26 @@
+use unix_exec_output_catcher::{fork_exec_and_catch, OCatchStrategy};
+
+/// Other children of this process must neither be reaped nor confused
+/// with the child of the library.
+#[test]
+fn sibling_children_are_not_reaped() {
+    let mut siblings = (0..4)
+        .map(|_| {
+            std::process::Command::new("sh")
+                .args(["-c", "exit 7"])
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately].iter() {
+        let res =
+            fork_exec_and_catch("sh", vec!["sh", "-c", "sleep 0.1; exit 3"], *strategy).unwrap();
+        assert_eq!(3, res.exit_code());
+    }
+
+    // all siblings must still be available for std to reap
+    for sibling in siblings.iter_mut() {
+        assert_eq!(Some(7), sibling.wait().unwrap().code());
+    }
+}
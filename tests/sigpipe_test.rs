@@ -0,0 +1,28 @@
+use unix_exec_output_catcher::{Command, SigpipeDisposition};
+
+/// `yes` writes until `head` closes the pipe.
+const SCRIPT: &str = "yes | head -n 1";
+
+#[test]
+fn sigpipe_default() {
+    let res = Command::new("sh")
+        .args(["-c", SCRIPT])
+        .sigpipe(SigpipeDisposition::Default)
+        .catch()
+        .unwrap();
+    assert_eq!("y", res.stdout().unwrap().get(0).unwrap());
+    // `yes` got killed silently
+    assert!(!res.has_stderr());
+}
+
+#[test]
+fn sigpipe_ignore() {
+    let res = Command::new("sh")
+        .args(["-c", SCRIPT])
+        .sigpipe(SigpipeDisposition::Ignore)
+        .catch()
+        .unwrap();
+    assert_eq!("y", res.stdout().unwrap().get(0).unwrap());
+    // `yes` complains about the write error
+    assert!(res.has_stderr());
+}
@@ -0,0 +1,13 @@
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn start_delay() {
+    let begin = Instant::now();
+    let res = Command::new("true")
+        .start_delay(Duration::from_millis(200))
+        .catch()
+        .unwrap();
+    assert!(res.exit_status().success());
+    assert!(begin.elapsed() >= Duration::from_millis(200));
+}
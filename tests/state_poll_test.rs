@@ -0,0 +1,31 @@
+use std::time::Duration;
+use unix_exec_output_catcher::{Command, OCatchStrategy};
+
+#[test]
+fn child_closes_output_before_exit() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately] {
+        let res = Command::new("sh")
+            .args(vec!["-c", "echo out; exec >&- 2>&-; sleep 0.3; exit 2"])
+            .strategy(strategy)
+            .state_poll_interval(Duration::from_millis(20))
+            .catch()
+            .unwrap();
+        assert_eq!(2, res.exit_code());
+        assert_eq!(vec!["out"], res.stdcombined().iter().collect::<Vec<&str>>());
+    }
+}
+
+#[test]
+fn running_child_wait_variants() {
+    let mut child = Command::new("sh")
+        .args(vec!["-c", "echo out; sleep 0.2; exit 4"])
+        .spawn()
+        .unwrap();
+    assert_eq!(None, child.try_wait().unwrap());
+    assert_eq!(4, child.wait_blocking().unwrap().code());
+    assert_eq!(4, child.try_wait().unwrap().unwrap().code());
+    // the output is still there
+    let res = child.wait_with_output().unwrap();
+    assert_eq!(4, res.exit_code());
+    assert_eq!(Some("out"), res.stdout().unwrap().get(0));
+}
@@ -0,0 +1,72 @@
+use std::thread;
+use unix_exec_output_catcher::{run_with_input, Command, OCatchStrategy};
+
+#[test]
+fn run_with_input_small() {
+    let res = run_with_input(
+        "sort",
+        vec!["sort"],
+        b"b\nc\na\n",
+        OCatchStrategy::StdCombined,
+    )
+    .unwrap();
+    let lines = res.stdcombined().iter().collect::<Vec<&str>>();
+    assert_eq!(vec!["a", "b", "c"], lines);
+}
+
+#[test]
+fn run_with_input_larger_than_pipe_buffer() {
+    // much more than the pipe buffer (usually 64KiB); must not deadlock
+    let input = "0123456789\n".repeat(100_000);
+    let res = run_with_input(
+        "cat",
+        vec!["cat"],
+        input.as_bytes(),
+        OCatchStrategy::StdSeparately,
+    )
+    .unwrap();
+    assert_eq!(100_000, res.stdout().unwrap().len());
+}
+
+#[test]
+fn run_with_input_not_consumed() {
+    // the child doesn't read STDIN at all
+    let input = vec![b'x'; 1_000_000];
+    let res = run_with_input("true", vec!["true"], &input, OCatchStrategy::StdCombined).unwrap();
+    assert!(res.exit_status().success());
+}
+
+#[test]
+fn spawn_write_stdin_while_reading() {
+    let mut child = Command::new("cat")
+        .strategy(OCatchStrategy::StdSeparately)
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin().unwrap();
+    assert!(child.stdin().is_none());
+    // much more than the pipe buffer; only works if the output is read concurrently
+    let writer = thread::spawn(move || {
+        for i in 0..100_000 {
+            stdin.write_all(format!("{}\n", i).as_bytes()).unwrap();
+        }
+        stdin.flush().unwrap();
+        stdin.close().unwrap();
+    });
+    let res = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+    let stdout = res.stdout().unwrap();
+    assert_eq!(100_000, stdout.len());
+    assert_eq!("99999", stdout.iter().last().unwrap());
+}
+
+#[test]
+fn spawn_stdin_closed_if_not_taken() {
+    // cat would wait for input forever if STDIN stayed open
+    let res = Command::new("cat")
+        .spawn()
+        .unwrap()
+        .wait_with_output()
+        .unwrap();
+    assert!(res.exit_status().success());
+    assert!(res.is_empty());
+}
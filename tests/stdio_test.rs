@@ -0,0 +1,58 @@
+use unix_exec_output_catcher::{Command, OCatchStrategy, Stdio, StdioConfig};
+
+#[test]
+fn stdout_null() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo out; echo err >&2"])
+        .stdout(Stdio::Null)
+        .catch()
+        .unwrap();
+    assert!(res.stdout().unwrap().is_empty());
+    assert_eq!(
+        vec!["err"],
+        res.stderr().unwrap().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn stderr_inherit_combined() {
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo out; echo 'inherited stderr' >&2"])
+        .strategy(OCatchStrategy::StdCombined)
+        .stderr(Stdio::Inherit)
+        .catch()
+        .unwrap();
+    assert_eq!(vec!["out"], res.stdcombined().iter().collect::<Vec<&str>>());
+}
+
+#[test]
+fn stdin_null_and_capture() {
+    for stdin in [Stdio::Null, Stdio::Capture] {
+        let res = Command::new("cat")
+            .stdio(StdioConfig {
+                stdin,
+                ..StdioConfig::default()
+            })
+            .catch()
+            .unwrap();
+        assert!(res.exit_status().success());
+        assert!(res.stdcombined().is_empty());
+    }
+}
+
+#[test]
+fn verify_redirects() {
+    for strategy in [
+        OCatchStrategy::StdCombined,
+        OCatchStrategy::StdSeparately,
+        OCatchStrategy::Pty,
+    ] {
+        let res = Command::new("sh")
+            .args(vec!["-c", "echo out"])
+            .strategy(strategy)
+            .verify_redirects(true)
+            .catch()
+            .unwrap();
+        assert_eq!(vec!["out"], res.stdcombined().iter().collect::<Vec<&str>>());
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::{Command, ExitStatus, OCatchStrategy, StopReason};
+
+#[test]
+fn max_lines_stops_long_running_child() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately] {
+        let begin = Instant::now();
+        let res = Command::new("sh")
+            .args(["-c", "echo a; echo b >&2; echo c; exec sleep 10"])
+            .strategy(strategy)
+            .max_lines(3)
+            .catch()
+            .unwrap();
+        assert!(begin.elapsed() < Duration::from_secs(5));
+        assert_eq!(3, res.stdcombined().len());
+        assert_eq!(Some(StopReason::MaxLines), res.stop_reason());
+        assert!(matches!(
+            res.exit_status(),
+            ExitStatus::Signaled {
+                signal: libc::SIGKILL,
+                ..
+            }
+        ));
+    }
+}
+
+#[test]
+fn max_lines_stops_endless_output() {
+    let res = Command::new("yes")
+        .strategy(OCatchStrategy::StdSeparately)
+        .max_lines(1000)
+        .catch()
+        .unwrap();
+    assert_eq!(1000, res.stdout().unwrap().len());
+    assert_eq!(1000, res.stdcombined().len());
+}
+
+#[test]
+fn max_lines_not_reached() {
+    let res = Command::new("sh")
+        .args(["-c", "echo a; echo b"])
+        .max_lines(3)
+        .catch()
+        .unwrap();
+    assert_eq!(2, res.stdcombined().len());
+    assert_eq!(None, res.stop_reason());
+    assert!(res.exit_status().success());
+}
+
+#[test]
+fn stop_when_line_matches() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::StdSeparately] {
+        let begin = Instant::now();
+        let res = Command::new("sh")
+            .args(["-c", "echo starting; echo ready; echo more; exec sleep 10"])
+            .strategy(strategy)
+            .stop_when(|line| line == "ready")
+            .catch()
+            .unwrap();
+        assert!(begin.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            vec!["starting", "ready"],
+            res.stdcombined().iter().collect::<Vec<&str>>()
+        );
+        assert_eq!(Some(StopReason::StopCondition), res.stop_reason());
+    }
+}
+
+#[test]
+fn stop_when_without_kill() {
+    let res = Command::new("sh")
+        .args(["-c", "echo ready; echo more; exit 3"])
+        .strategy(OCatchStrategy::StdCombined)
+        .stop_when(|line| line == "ready")
+        .kill_on_stop(false)
+        .catch()
+        .unwrap();
+    assert_eq!(
+        vec!["ready"],
+        res.stdcombined().iter().collect::<Vec<&str>>()
+    );
+    assert_eq!(Some(StopReason::StopCondition), res.stop_reason());
+    assert_eq!(ExitStatus::Exited(3), res.exit_status());
+}
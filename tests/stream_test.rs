@@ -0,0 +1,84 @@
+#![cfg(feature = "tokio")]
+
+use futures_core::Stream;
+use std::future::poll_fn;
+use std::pin::Pin;
+use unix_exec_output_catcher::{Command, ExitStatus, OCatchStrategy, OutputLine, Source};
+
+/// Collects all items of the stream.
+async fn collect(
+    mut stream: impl Stream<Item = Result<OutputLine, unix_exec_output_catcher::error::UECOError>>
+        + Unpin,
+) -> Vec<OutputLine> {
+    let mut items = vec![];
+    while let Some(item) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+        items.push(item.unwrap());
+    }
+    items
+}
+
+/// Strips the timestamps.
+fn lines(items: &[OutputLine]) -> Vec<(Option<Source>, &str)> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            OutputLine::Line { text, source, .. } => Some((*source, text.as_str())),
+            OutputLine::Eof { .. } => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn stream_separately() {
+    let stream = Command::new("sh")
+        .args([
+            "-c",
+            "echo out; sleep 0.1; echo err >&2; printf 'no newline'; exit 3",
+        ])
+        .stream()
+        .unwrap();
+    let items = collect(stream).await;
+    assert_eq!(
+        vec![
+            (Some(Source::Stdout), "out"),
+            (Some(Source::Stderr), "err"),
+            (Some(Source::Stdout), "no newline")
+        ],
+        lines(&items)
+    );
+    assert_eq!(
+        Some(&OutputLine::Eof {
+            exit_status: ExitStatus::Exited(3)
+        }),
+        items.last()
+    );
+}
+
+#[tokio::test]
+async fn stream_seq() {
+    let stream = Command::new("sh")
+        .args(["-c", "echo a; echo b >&2; echo c"])
+        .stream()
+        .unwrap();
+    let seqs = collect(stream)
+        .await
+        .iter()
+        .filter_map(|item| match item {
+            OutputLine::Line { seq, .. } => Some(*seq),
+            OutputLine::Eof { .. } => None,
+        })
+        .collect::<Vec<u64>>();
+    assert_eq!(vec![0, 1, 2], seqs);
+}
+
+#[tokio::test]
+async fn stream_combined() {
+    let stream = Command::new("sh")
+        .args(["-c", "echo out; echo err >&2"])
+        .strategy(OCatchStrategy::StdCombined)
+        .stream()
+        .unwrap();
+    let items = collect(stream).await;
+    assert_eq!(vec![(None, "out"), (None, "err")], lines(&items));
+    assert_eq!(3, items.len());
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::{Command, OCatchStrategy, StopReason};
+
+#[test]
+fn catch_chunks_timeout() {
+    let mut output = vec![];
+    let start = Instant::now();
+    let res = Command::new("sh")
+        .args(vec!["-c", "echo before; exec sleep 10"])
+        .timeout(Duration::from_millis(300))
+        .catch_chunks(4096, |_, chunk| output.extend(chunk));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(matches!(res, Err(UECOError::TimedOut { .. })));
+    assert_eq!(b"before\n".to_vec(), output);
+}
+
+#[test]
+fn catch_chunks_finishes_before_timeout() {
+    let mut output = vec![];
+    let exit_status = Command::new("echo")
+        .args(vec!["hello"])
+        .timeout(Duration::from_secs(10))
+        .catch_chunks(4096, |_, chunk| output.extend(chunk))
+        .unwrap();
+    assert!(exit_status.success());
+    assert_eq!(b"hello\n".to_vec(), output);
+}
+
+#[test]
+fn multiplexed_timeout() {
+    let output = Command::new("sh")
+        .args(vec!["-c", "echo out; echo err >&2; exec sleep 10"])
+        .strategy(OCatchStrategy::Multiplexed)
+        .timeout(Duration::from_millis(300))
+        .catch()
+        .unwrap();
+    assert_eq!(Some(StopReason::Timeout), output.stop_reason());
+    assert_eq!(
+        vec!["out"],
+        output.stdout().unwrap().iter().collect::<Vec<&str>>()
+    );
+    assert_eq!(
+        vec!["err"],
+        output.stderr().unwrap().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn default_strategy_timeout() {
+    let start = Instant::now();
+    let output = Command::new("sh")
+        .args(vec!["-c", "echo out; echo err >&2; exec sleep 10"])
+        .timeout(Duration::from_millis(300))
+        .catch()
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(Some(StopReason::Timeout), output.stop_reason());
+    assert_eq!(
+        vec!["out"],
+        output.stdout().unwrap().iter().collect::<Vec<&str>>()
+    );
+    assert_eq!(
+        vec!["err"],
+        output.stderr().unwrap().iter().collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn combined_timeout() {
+    for strategy in [OCatchStrategy::StdCombined, OCatchStrategy::Pty] {
+        let start = Instant::now();
+        let output = Command::new("sh")
+            .args(vec!["-c", "echo first; exec sleep 10"])
+            .strategy(strategy)
+            .timeout(Duration::from_millis(300))
+            .catch()
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5), "{}", strategy);
+        assert_eq!(
+            Some(StopReason::Timeout),
+            output.stop_reason(),
+            "{}",
+            strategy
+        );
+        assert_eq!(
+            vec!["first"],
+            output.stdcombined().iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,40 @@
+#![cfg(target_os = "linux")]
+
+use unix_exec_output_catcher::error::UECOError;
+use unix_exec_output_catcher::Command;
+
+#[test]
+fn unshare_uts_namespace() {
+    if unsafe { libc::geteuid() } != 0 {
+        // requires privileges
+        return;
+    }
+    // the new hostname is only visible inside of the new UTS namespace
+    let res = Command::new("sh")
+        .args(vec!["-c", "hostname ueco-unshare-test && hostname"])
+        .unshare(libc::CLONE_NEWUTS)
+        .catch();
+    let res = match res {
+        Ok(res) if res.exit_code() == 0 => res,
+        // e.g. in a container without CAP_SYS_ADMIN
+        _ => return,
+    };
+    assert_eq!(
+        vec!["ueco-unshare-test"],
+        res.stdout().unwrap().iter().collect::<Vec<_>>()
+    );
+    let hostname = Command::new("hostname").catch().unwrap();
+    assert_ne!(Some("ueco-unshare-test"), hostname.stdout().unwrap().get(0));
+}
+
+#[test]
+fn unshare_invalid_flags() {
+    // not a namespace flag
+    let res = Command::new("true").unshare(libc::CLONE_VFORK).catch();
+    assert!(matches!(
+        res,
+        Err(UECOError::UnshareFailed {
+            errno: libc::EINVAL
+        })
+    ));
+}
@@ -0,0 +1,97 @@
+use std::time::Instant;
+use unix_exec_output_catcher::{
+    BufferVisitor, Command, LineVisitor, OCatchStrategy, Source, StopReason,
+};
+
+/// Collects the lines and checks that they are visited in order.
+#[derive(Default)]
+struct Recorder {
+    lines: Vec<(Option<Source>, String)>,
+    last_instant: Option<Instant>,
+}
+
+impl LineVisitor for Recorder {
+    fn visit(&mut self, source: Option<Source>, line: &str, instant: Instant) {
+        if let Some(last_instant) = self.last_instant {
+            assert!(instant >= last_instant);
+        }
+        self.last_instant.replace(instant);
+        self.lines.push((source, line.to_string()));
+    }
+}
+
+#[test]
+fn visit_separately() {
+    let mut recorder = Recorder::default();
+    let (exit_status, stop_reason) = Command::new("sh")
+        .args(vec![
+            "-c",
+            "echo a; sleep 0.1; echo b >&2; sleep 0.1; printf c",
+        ])
+        .strategy(OCatchStrategy::StdSeparately)
+        .catch_with_visitor(&mut recorder)
+        .unwrap();
+    assert!(exit_status.success());
+    assert_eq!(None, stop_reason);
+    assert_eq!(
+        vec![
+            (Some(Source::Stdout), "a".to_string()),
+            (Some(Source::Stderr), "b".to_string()),
+            (Some(Source::Stdout), "c".to_string()),
+        ],
+        recorder.lines
+    );
+}
+
+#[test]
+fn visit_combined_with_options() {
+    let mut recorder = Recorder::default();
+    let (_, stop_reason) = Command::new("sh")
+        .args(vec!["-c", "echo 1; echo 2 >&2; echo 3; echo 4"])
+        .strategy(OCatchStrategy::StdCombined)
+        .map_line(|_, line| Some(format!("#{}", line)))
+        .max_lines(3)
+        .catch_with_visitor(&mut recorder)
+        .unwrap();
+    assert_eq!(Some(StopReason::MaxLines), stop_reason);
+    assert_eq!(
+        vec![
+            (None, "#1".to_string()),
+            (None, "#2".to_string()),
+            (None, "#3".to_string()),
+        ],
+        recorder.lines
+    );
+}
+
+#[test]
+fn buffer_visitor_reused_buffers() {
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    for i in 0..3 {
+        stdout.clear();
+        stderr.clear();
+        let script = format!("echo out{}; echo err{} >&2; exit {}", i, i, i);
+        let (exit_status, _) = Command::new("sh")
+            .args(["-c", &script])
+            .strategy(OCatchStrategy::StdSeparately)
+            .catch_with_visitor(&mut BufferVisitor {
+                stdout: &mut stdout,
+                stderr: &mut stderr,
+            })
+            .unwrap();
+        assert_eq!(i, exit_status.code());
+        assert_eq!(vec![format!("out{}", i)], stdout);
+        assert_eq!(vec![format!("err{}", i)], stderr);
+    }
+
+    // appends to the existing content
+    Command::new("echo")
+        .arg("again")
+        .catch_with_visitor(&mut BufferVisitor {
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+        })
+        .unwrap();
+    assert_eq!(vec!["out2", "again"], stdout);
+}